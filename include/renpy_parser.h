@@ -0,0 +1,48 @@
+/* C interface of renpy_parser, built with the `ffi` feature.
+ *
+ * Strings passed in must be nul-terminated UTF-8. Strings returned for a
+ * script live as long as its handle, except those from the *_json functions,
+ * which are freed with renpy_parser_string_free. Indices out of range give
+ * NULL or 0. */
+
+#ifndef RENPY_PARSER_H
+#define RENPY_PARSER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RenpyScript RenpyScript;
+
+/* Parses a script, recovering from statements that fail to parse.
+ * Returns NULL when source is NULL or not UTF-8; filename may be NULL.
+ * Scripts that can't be read into statements at all, like ones indented
+ * with tabs, give a script without nodes whose only diagnostic is the error. */
+RenpyScript *renpy_parser_parse(const char *source, const char *filename);
+void renpy_parser_free(RenpyScript *script);
+
+/* Nodes in document order, nested ones included */
+size_t renpy_parser_node_count(const RenpyScript *script);
+const char *renpy_parser_node_kind(const RenpyScript *script, size_t index);
+size_t renpy_parser_node_line(const RenpyScript *script, size_t index);
+size_t renpy_parser_node_depth(const RenpyScript *script, size_t index);
+char *renpy_parser_node_json(const RenpyScript *script, size_t index);
+
+char *renpy_parser_to_json(const RenpyScript *script);
+void renpy_parser_string_free(char *text);
+
+/* Errors and warnings, sorted by line */
+size_t renpy_parser_diagnostic_count(const RenpyScript *script);
+bool renpy_parser_diagnostic_is_error(const RenpyScript *script, size_t index);
+const char *renpy_parser_diagnostic_code(const RenpyScript *script, size_t index);
+size_t renpy_parser_diagnostic_line(const RenpyScript *script, size_t index);
+const char *renpy_parser_diagnostic_message(const RenpyScript *script, size_t index);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,110 @@
+use crate::export::csv::csv_field;
+use crate::parsers::{walk_labeled, AST};
+use std::collections::BTreeMap;
+
+/// How much a speaker says across a script
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeakerStats {
+    /// Character saying the lines, `None` for the narrator
+    pub speaker: Option<String>,
+    pub lines: usize,
+    pub words: usize,
+    /// Labels the speaker has lines in, in order of first appearance
+    pub labels: Vec<String>,
+    pub longest_monologue: Monologue,
+}
+
+/// Lines said in a row by one speaker within a label, with only statements
+/// other than dialogue between them
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Monologue {
+    pub lines: usize,
+    pub words: usize,
+    /// Line the monologue starts on
+    pub line_number: usize,
+}
+
+/// Dialogue statistics per speaker, the narrator first and then by name
+pub fn speaker_stats(ast: &[AST]) -> Vec<SpeakerStats> {
+    let mut stats: BTreeMap<Option<&str>, SpeakerStats> = BTreeMap::new();
+    // Speaker and label of the monologue going on, with its length so far
+    let mut current: Option<(Option<&str>, Option<&str>, Monologue)> = None;
+
+    walk_labeled(ast, &mut |_, node, label| {
+        let AST::Say(line_number, speaker, text, _) = node else {
+            return;
+        };
+        let speaker = speaker.as_deref();
+        let words = text.split_whitespace().count();
+
+        let entry = stats.entry(speaker).or_insert_with(|| SpeakerStats {
+            speaker: speaker.map(str::to_string),
+            ..SpeakerStats::default()
+        });
+        entry.lines += 1;
+        entry.words += words;
+        if let Some(label) = label {
+            if !entry.labels.iter().any(|l| l == label) {
+                entry.labels.push(label.to_string());
+            }
+        }
+
+        let monologue = match &mut current {
+            Some((s, l, monologue)) if *s == speaker && *l == label => monologue,
+            _ => {
+                let started = Monologue {
+                    line_number: *line_number,
+                    ..Monologue::default()
+                };
+                &mut current.insert((speaker, label, started)).2
+            }
+        };
+        monologue.lines += 1;
+        monologue.words += words;
+        if monologue.lines > entry.longest_monologue.lines {
+            entry.longest_monologue = monologue.clone();
+        }
+    });
+
+    stats.into_values().collect()
+}
+
+const HEADER: [&str; 7] = [
+    "speaker",
+    "lines",
+    "words",
+    "labels",
+    "longest_monologue_lines",
+    "longest_monologue_words",
+    "longest_monologue_line",
+];
+
+/// One row per speaker, with the narrator's speaker field empty and labels
+/// separated by spaces
+pub fn to_csv(stats: &[SpeakerStats]) -> String {
+    let mut out = HEADER.join(",");
+    out.push('\n');
+
+    for speaker in stats {
+        let fields = [
+            csv_field(speaker.speaker.as_deref().unwrap_or_default()),
+            speaker.lines.to_string(),
+            speaker.words.to_string(),
+            csv_field(&speaker.labels.join(" ")),
+            speaker.longest_monologue.lines.to_string(),
+            speaker.longest_monologue.words.to_string(),
+            speaker.longest_monologue.line_number.to_string(),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(feature = "json")]
+pub fn to_json(stats: &[SpeakerStats]) -> String {
+    serde_json::to_string_pretty(stats).expect("stats are always serializable")
+}
@@ -0,0 +1,157 @@
+use crate::lexer::Lexer;
+use crate::parsers::{block, convert, from_ast, statements, Owned, Quoting, Stmt, Tree, AST};
+use crate::{group_logical_lines, parse_logical_lines};
+use anyhow::Result;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use std::borrow::Cow;
+
+/// AST allocated in a bump arena: strings and blocks borrow from the arena,
+/// so a whole batch of scripts is freed at once by dropping it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArenaAST<'a> {
+    Define(usize, &'a str),
+    Hide(usize, &'a str, Option<&'a str>),
+    Init(usize, &'a [ArenaAST<'a>], i32),
+    Jump(usize, &'a str, bool),
+    Label(usize, &'a str, &'a [ArenaAST<'a>], Option<&'a str>),
+    Play(usize, &'a str, &'a str),
+    Return(usize, Option<&'a str>),
+    Say(usize, Option<&'a str>, &'a str, Quoting),
+    Scene(usize, Option<&'a str>, &'a str, Option<&'a str>),
+    Show(usize, &'a str, Option<&'a str>),
+    Stop(usize, &'a str, Option<&'a str>, Option<f32>),
+    GameMechanic(usize, &'a str),
+    LLMGenerate(usize, &'a str, Option<&'a str>),
+    Include(usize, &'a str),
+    Custom(usize, &'a str, &'a str),
+    Error {
+        loc: usize,
+        message: &'a str,
+        raw_text: &'a str,
+    },
+}
+
+impl<'a> ArenaAST<'a> {
+    /// Copies a node and everything nested under it into the arena
+    pub fn alloc(bump: &'a Bump, node: &AST) -> Self {
+        from_ast(&mut Arena { bump }, node.clone())
+    }
+
+    /// Copies the node back out of the arena
+    pub fn to_owned_ast(&self) -> AST {
+        AST::from(self)
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            ArenaAST::Define(i, ..)
+            | ArenaAST::Hide(i, ..)
+            | ArenaAST::Init(i, ..)
+            | ArenaAST::Jump(i, ..)
+            | ArenaAST::Label(i, ..)
+            | ArenaAST::Play(i, ..)
+            | ArenaAST::Return(i, ..)
+            | ArenaAST::Say(i, ..)
+            | ArenaAST::Scene(i, ..)
+            | ArenaAST::Show(i, ..)
+            | ArenaAST::Stop(i, ..)
+            | ArenaAST::GameMechanic(i, ..)
+            | ArenaAST::LLMGenerate(i, ..)
+            | ArenaAST::Include(i, ..)
+            | ArenaAST::Custom(i, ..)
+            | ArenaAST::Error { loc: i, .. } => *i,
+        }
+    }
+
+    /// Statements nested under this node, for labels and init blocks
+    pub fn block(&self) -> Option<&'a [ArenaAST<'a>]> {
+        match self {
+            ArenaAST::Label(_, _, block, _) | ArenaAST::Init(_, block, _) => Some(block),
+            _ => None,
+        }
+    }
+}
+
+impl From<&ArenaAST<'_>> for AST {
+    fn from(node: &ArenaAST<'_>) -> Self {
+        let c = &mut Owned;
+        statements!(convert!(*node, ArenaAST => AST, c))
+    }
+}
+
+/// Copies a parsed AST into the arena
+pub fn alloc<'a>(bump: &'a Bump, ast: &[AST]) -> &'a [ArenaAST<'a>] {
+    bump.alloc_slice_fill_iter(ast.iter().map(|node| ArenaAST::alloc(bump, node)))
+}
+
+/// Parses a script into the arena, allocating each node and block there as
+/// it's parsed
+pub fn parse_in<'a>(
+    bump: &'a Bump,
+    content: &str,
+    filename: &str,
+) -> Result<(&'a [ArenaAST<'a>], Vec<String>)> {
+    let lines = parse_logical_lines(content, filename)?;
+    let blocks = group_logical_lines(lines)?;
+    let (ast, errors) = block(&mut Lexer::borrowed(&blocks, true), &mut Arena { bump });
+    Ok((
+        ast.into_bump_slice(),
+        errors.iter().map(ToString::to_string).collect(),
+    ))
+}
+
+/// Tree of nodes allocated in a bump arena
+struct Arena<'a> {
+    bump: &'a Bump,
+}
+
+impl<'a> Tree<'_> for Arena<'a> {
+    type Node = ArenaAST<'a>;
+    type Block = BumpVec<'a, ArenaAST<'a>>;
+
+    fn block(&mut self) -> Self::Block {
+        BumpVec::new_in(self.bump)
+    }
+
+    fn push(&mut self, block: &mut Self::Block, node: Self::Node) {
+        block.push(node);
+    }
+
+    fn node(&mut self, stmt: Stmt<'_, Self::Block>) -> Self::Node {
+        let c = self;
+        statements!(convert!(stmt, Stmt => ArenaAST, c))
+    }
+}
+
+/// Fields of a node in the arena out of the ones of a [`Stmt`], copying text
+/// into the arena, see [`convert`]
+impl<'a> Arena<'a> {
+    fn text(&mut self, text: Cow<str>) -> &'a str {
+        self.bump.alloc_str(&text)
+    }
+
+    fn opt_text(&mut self, text: Option<Cow<str>>) -> Option<&'a str> {
+        text.map(|text| self.text(text))
+    }
+
+    fn name(&mut self, name: Cow<str>) -> &'a str {
+        self.text(name)
+    }
+
+    fn opt_name(&mut self, name: Option<Cow<str>>) -> Option<&'a str> {
+        self.opt_text(name)
+    }
+
+    fn message(&mut self, message: String) -> &'a str {
+        self.bump.alloc_str(&message)
+    }
+
+    fn copy<T>(&mut self, value: T) -> T {
+        value
+    }
+
+    fn nodes(&mut self, nodes: BumpVec<'a, ArenaAST<'a>>) -> &'a [ArenaAST<'a>] {
+        nodes.into_bump_slice()
+    }
+}
@@ -0,0 +1,189 @@
+use crate::parsers::{walk, NodeRef, AST};
+#[cfg(feature = "fs")]
+use anyhow::Result;
+#[cfg(feature = "fs")]
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "fs")]
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "avif"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetRef {
+    pub name: String,
+    pub node: NodeRef,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetReport {
+    /// Image names from show and scene statements
+    pub images: Vec<AssetRef>,
+    /// Image tags from hide statements
+    pub tags: Vec<AssetRef>,
+    /// Audio files from play statements
+    pub audio: Vec<AssetRef>,
+}
+
+/// Lists every image and audio reference with its location
+pub fn assets(ast: &[AST]) -> AssetReport {
+    let mut report = AssetReport::default();
+
+    walk(ast, &mut |path, node| {
+        let asset = |name: &str| AssetRef {
+            name: name.to_string(),
+            node: NodeRef::new(path, node),
+        };
+
+        match node {
+            AST::Show(_, image, _) | AST::Scene(_, Some(image), ..) => {
+                report.images.push(asset(image))
+            }
+            AST::Hide(_, image, _) => report.tags.push(asset(image)),
+            AST::Play(_, _, filename) => report.audio.push(asset(filename)),
+            _ => {}
+        }
+    });
+
+    report
+}
+
+impl AssetReport {
+    /// References without a matching file in the game directory.
+    ///
+    /// Images follow Ren'Py's automatic naming: any image file under `images/`
+    /// whose lowercased stem matches the image name, or else the only one with
+    /// its tag and at least its attributes. Hide statements only need a file
+    /// with the tag. Audio files are looked up relative to the game directory
+    /// and its `audio/` subdirectory.
+    #[cfg(feature = "fs")]
+    pub fn missing(&self, game_dir: &Path) -> Result<AssetReport> {
+        let files = GameAssets::load(game_dir)?;
+        let images = self
+            .images
+            .iter()
+            .filter(|asset| !files.has_image(&asset.name))
+            .cloned()
+            .collect();
+
+        let tags = self
+            .tags
+            .iter()
+            .filter(|asset| !files.has_tag(&asset.name))
+            .cloned()
+            .collect();
+
+        let audio = self
+            .audio
+            .iter()
+            .filter(|asset| !files.has_audio(&asset.name))
+            .cloned()
+            .collect();
+
+        Ok(AssetReport {
+            images,
+            tags,
+            audio,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty() && self.tags.is_empty() && self.audio.is_empty()
+    }
+}
+
+/// Files of a game directory, to look up the assets scripts refer to
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub(crate) struct GameAssets {
+    dir: PathBuf,
+    images: HashSet<String>,
+    tags: HashSet<String>,
+}
+
+#[cfg(feature = "fs")]
+impl GameAssets {
+    pub(crate) fn load(dir: &Path) -> Result<Self> {
+        let mut images = HashSet::new();
+        let images_dir = dir.join("images");
+        if images_dir.is_dir() {
+            collect_image_names(&images_dir, &mut images)?;
+        }
+        let tags = images
+            .iter()
+            .filter_map(|name| name.split(' ').next())
+            .map(str::to_string)
+            .collect();
+
+        Ok(GameAssets {
+            dir: dir.to_path_buf(),
+            images,
+            tags,
+        })
+    }
+
+    /// Whether an image file is named after the image, or is the only one with
+    /// its tag and at least its attributes, as Ren'Py picks `eileen happy smile`
+    /// for `show eileen happy` unless `eileen happy frown` exists too. `black`
+    /// is defined by Ren'Py itself.
+    pub(crate) fn has_image(&self, name: &str) -> bool {
+        let key = image_key(name);
+        if key == "black" || self.images.contains(&key) {
+            return true;
+        }
+
+        let mut words = key.split(' ');
+        let tag = words.next().unwrap_or_default();
+        let attributes: Vec<&str> = words.collect();
+        let candidates = self.images.iter().filter(|image| {
+            let mut words = image.split(' ');
+            let image_tag = words.next();
+            let words: Vec<&str> = words.collect();
+            image_tag == Some(tag) && attributes.iter().all(|a| words.contains(a))
+        });
+        candidates.count() == 1
+    }
+
+    /// Whether any image file has the tag, for hide statements
+    pub(crate) fn has_tag(&self, tag: &str) -> bool {
+        let key = image_key(tag);
+        key == "black" || self.tags.contains(&key)
+    }
+
+    pub(crate) fn has_audio(&self, filename: &str) -> bool {
+        self.dir.join(filename).is_file() || self.dir.join("audio").join(filename).is_file()
+    }
+}
+
+#[cfg(feature = "fs")]
+fn collect_image_names(dir: &Path, names: &mut HashSet<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_image_names(&path, names)?;
+            continue;
+        }
+
+        let is_image = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+
+        if let (true, Some(stem)) = (is_image, path.file_stem().and_then(|s| s.to_str())) {
+            names.insert(image_key(stem));
+        }
+    }
+    Ok(())
+}
+
+/// Lowercased image name with words separated by single spaces
+#[cfg(feature = "fs")]
+fn image_key(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
@@ -0,0 +1,165 @@
+use crate::diagnostics::{Code, Diagnostics};
+use crate::include::{self, Origins};
+use crate::options::ParseOptions;
+use crate::parse_script_from_bytes;
+use crate::parsers::{walk, ParseError, AST};
+use anyhow::Result;
+use bevy_app::{App, Plugin};
+use bevy_asset::io::{AssetReaderError, Reader};
+use bevy_asset::{Asset, AssetApp, AssetLoader, AssetPath, LoadContext, ReadAssetBytesError};
+use bevy_reflect::TypePath;
+use std::collections::HashMap;
+
+/// A `.rpy` script loaded by the [`AssetServer`](bevy_asset::AssetServer),
+/// with the statements of the scripts it includes in place of its include
+/// statements
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct RenpyScript {
+    pub ast: Vec<AST>,
+    /// Scripts the included statements come from, which their line numbers
+    /// refer to
+    pub origins: Origins<AssetPath<'static>>,
+    /// Problems of the script and of the scripts it includes, which keep
+    /// their own file names
+    pub diagnostics: Diagnostics,
+}
+
+/// Parses `.rpy` files past statements with errors.
+///
+/// Included scripts are read as dependencies of the script, so with the
+/// asset server watching for changes (Bevy's `file_watcher` feature) editing
+/// either one reloads the script.
+#[derive(Default, TypePath)]
+pub struct RenpyScriptLoader;
+
+impl AssetLoader for RenpyScriptLoader {
+    type Asset = RenpyScript;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _: &(),
+        context: &mut LoadContext<'_>,
+    ) -> Result<RenpyScript> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let path = context.path().clone();
+        let script = parse_script_from_bytes(bytes, &path.to_string(), &ParseOptions::hardened())?;
+        let mut diagnostics = script.diagnostics;
+        let mut included = load_included(context, &path, &script.ast, &mut diagnostics).await;
+        included.insert(path.clone(), Ok(script.ast.clone()));
+
+        // Includes are replaced as in projects, see crate::project
+
+        let mut lookup = |script: &AssetPath<'static>, target: &str| {
+            let target_path = script.resolve_embed_str(target).map_err(|err| {
+                let message = format!("bad include path '{}': {}", target, err);
+                (Code::IncludeNotFound, message)
+            })?;
+            match included.get(&target_path) {
+                Some(Ok(ast)) => Ok((target_path, ast.clone())),
+                Some(Err((Code::IncludeNotFound, _))) | None => Err((
+                    Code::IncludeNotFound,
+                    format!("included file '{}' not found", target),
+                )),
+                Some(Err((code, problem))) => {
+                    Err((*code, format!("included file '{}' {}", target, problem)))
+                }
+            }
+        };
+        let expanded = include::expand(path.clone(), script.ast, &mut lookup);
+
+        for problem in expanded.problems {
+            let error = ParseError::new(
+                problem.file.to_string(),
+                problem.line_number,
+                problem.message,
+                None,
+                None,
+            );
+            diagnostics.error(error.with_code(problem.code));
+        }
+
+        Ok(RenpyScript {
+            ast: expanded.ast,
+            origins: expanded.origins,
+            diagnostics,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rpy"]
+    }
+}
+
+/// Registers [`RenpyScript`] assets and their loader
+pub struct RenpyPlugin;
+
+impl Plugin for RenpyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<RenpyScript>()
+            .init_asset_loader::<RenpyScriptLoader>();
+    }
+}
+
+/// Included script, or the code of the problem including it and why
+type Loaded = std::result::Result<Vec<AST>, (Code, String)>;
+
+/// Reads the scripts the script includes, directly or not, as dependencies of
+/// the asset, with their problems
+async fn load_included(
+    context: &mut LoadContext<'_>,
+    path: &AssetPath<'static>,
+    ast: &[AST],
+    diagnostics: &mut Diagnostics,
+) -> HashMap<AssetPath<'static>, Loaded> {
+    let mut rv = HashMap::new();
+    let mut pending = vec![(path.clone(), includes(ast))];
+
+    while let Some((script, targets)) = pending.pop() {
+        for target in targets {
+            let Ok(target_path) = script.resolve_embed_str(&target) else {
+                continue;
+            };
+            if target_path == *path || rv.contains_key(&target_path) {
+                continue;
+            }
+
+            let loaded = match context.read_asset_bytes(&target_path).await {
+                Ok(bytes) => {
+                    let filename = target_path.to_string();
+                    match parse_script_from_bytes(bytes, &filename, &ParseOptions::hardened()) {
+                        Ok(script) => {
+                            diagnostics
+                                .extend(script.diagnostics.iter().map(|(s, e)| (s, e.clone())));
+                            pending.push((target_path.clone(), includes(&script.ast)));
+                            Ok(script.ast)
+                        }
+                        Err(err) => Err((Code::IncludeUnreadable, format!("can't parse: {}", err))),
+                    }
+                }
+                Err(ReadAssetBytesError::AssetReaderError(AssetReaderError::NotFound(_))) => {
+                    Err((Code::IncludeNotFound, String::new()))
+                }
+                Err(err) => Err((Code::IncludeUnreadable, format!("can't read: {}", err))),
+            };
+            rv.insert(target_path, loaded);
+        }
+    }
+
+    rv
+}
+
+/// Targets of the include statements of a script
+fn includes(ast: &[AST]) -> Vec<String> {
+    let mut rv = Vec::new();
+    walk(ast, &mut |_, node| {
+        if let AST::Include(_, target) = node {
+            rv.push(target.clone());
+        }
+    });
+    rv
+}
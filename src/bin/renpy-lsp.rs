@@ -0,0 +1,3 @@
+fn main() -> anyhow::Result<()> {
+    renpy_parser::lsp::serve_stdio()
+}
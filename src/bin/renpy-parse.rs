@@ -0,0 +1,372 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand, ValueEnum};
+use renpy_parser::analytics::{self, speaker_stats};
+use renpy_parser::diagnostics::{Code, Severity};
+use renpy_parser::export::json::to_json;
+use renpy_parser::export::ron::to_ron;
+use renpy_parser::export::Node;
+use renpy_parser::lint::rules::MissingAsset;
+use renpy_parser::lint::{LintConfig, Linter};
+use renpy_parser::options::{CommentHandling, ParseOptions};
+use renpy_parser::parsers::{walk, ParseError, AST};
+use renpy_parser::search::{search_script, MatchKind, Pattern};
+use renpy_parser::writer::format_source;
+use renpy_parser::{parse_script_from_bytes, read_script, ParsedScript};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(
+    name = "renpy-parse",
+    version,
+    about = "Checks and dumps Ren'Py scripts"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints errors and warnings, exiting with 1 when there are errors
+    Check {
+        /// Scripts, or directories to check every .rpy file under
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Game directory to look for the images and audio files the scripts use in
+        #[arg(long)]
+        game_dir: Option<PathBuf>,
+        /// TOML file setting the levels of lint rules, under `[rules]`
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Prints the AST of a script
+    Dump {
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Rewrites scripts in the canonical style, keeping comments and blank lines
+    Fmt {
+        /// Scripts, or directories to format every .rpy file under
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// List the scripts that aren't formatted instead of rewriting them,
+        /// exiting with 1 if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Prints the dialogue and comments containing a pattern with their labels
+    /// and speakers, exiting with 1 when nothing matches
+    Search {
+        pattern: String,
+        /// Scripts, or directories to search every .rpy file under
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Read the pattern as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Prints lines, words, labels and the longest monologue of every speaker
+    Speakers {
+        /// Scripts, or directories to count every .rpy file under
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = TableFormat::Csv)]
+        format: TableFormat,
+    },
+    /// Prints statement counts
+    Stats {
+        /// Scripts, or directories to count every .rpy file under
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Ron,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TableFormat {
+    Csv,
+    Json,
+}
+
+fn main() -> Result<ExitCode> {
+    match Cli::parse().command {
+        Command::Check {
+            paths,
+            game_dir,
+            config,
+        } => {
+            let mut linter = Linter::default();
+            if let Some(game_dir) = game_dir {
+                linter.push(MissingAsset::new(&game_dir)?);
+            }
+            if let Some(config) = config {
+                linter =
+                    linter.with_config(&LintConfig::from_toml(&fs::read_to_string(config)?)?)?;
+            }
+            check(&scripts(&paths)?, &linter)
+        }
+        Command::Dump { path, format } => {
+            let script = parse(&path)?;
+            match format {
+                Format::Json => println!("{}", to_json(&script.ast)),
+                Format::Ron => println!("{}", to_ron(&script.ast)),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Fmt { paths, check } => fmt(&scripts(&paths)?, check),
+        Command::Search {
+            pattern,
+            paths,
+            regex,
+        } => {
+            let pattern = match regex {
+                true => Pattern::regex(&pattern)?,
+                false => Pattern::from(pattern.as_str()),
+            };
+            search(&scripts(&paths)?, &pattern)
+        }
+        Command::Speakers { paths, format } => {
+            let mut ast = Vec::new();
+            for path in scripts(&paths)? {
+                ast.extend(parse(&path)?.ast);
+            }
+            let stats = speaker_stats(&ast);
+            match format {
+                TableFormat::Csv => print!("{}", analytics::to_csv(&stats)),
+                TableFormat::Json => println!("{}", analytics::to_json(&stats)),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Stats { paths } => stats(&scripts(&paths)?),
+    }
+}
+
+/// The paths, with directories replaced by the scripts under them in path order
+fn scripts(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    fn collect(dir: &Path, rv: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                collect(&path, rv)?;
+            } else if path.extension().is_some_and(|ext| ext == "rpy") {
+                rv.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut rv = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect(path, &mut rv)?;
+        } else {
+            rv.push(path.clone());
+        }
+    }
+    Ok(rv)
+}
+
+/// Parses a script past statements with errors, keeping its comments for
+/// suppressing lint rules
+fn parse(path: &Path) -> Result<ParsedScript> {
+    let options = ParseOptions {
+        recover: true,
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    parse_script_from_bytes(fs::read(path)?, &path.to_string_lossy(), &options)
+}
+
+fn check(paths: &[PathBuf], linter: &Linter) -> Result<ExitCode> {
+    let (mut errors, mut warnings) = (0, 0);
+
+    for path in paths {
+        // (line, severity, code, message, notes)
+        let mut found = Vec::new();
+        match parse(path) {
+            Ok(script) => {
+                for (severity, error) in script.diagnostics.iter() {
+                    let message = error.message.clone();
+                    let line = error.line_number;
+                    found.push((line, severity, error.code, message, Vec::new()));
+                }
+                for diagnostic in linter.check_script(&script) {
+                    found.push((
+                        diagnostic.node.index,
+                        diagnostic.severity,
+                        diagnostic.code,
+                        diagnostic.message,
+                        diagnostic.related,
+                    ));
+                }
+            }
+            Err(err) => found.push(match err.downcast::<ParseError>() {
+                Ok(error) => (
+                    error.line_number,
+                    Severity::Error,
+                    error.code,
+                    error.message,
+                    Vec::new(),
+                ),
+                Err(err) => (1, Severity::Error, Code::Other, err.to_string(), Vec::new()),
+            }),
+        }
+        found.sort_by_key(|(line, ..)| *line);
+
+        for (line, severity, code, message, notes) in found {
+            match severity {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            println!(
+                "{}:{}: {}[{}]: {}",
+                path.display(),
+                line,
+                severity,
+                code,
+                message
+            );
+            for note in notes {
+                println!(
+                    "{}:{}: note: {}",
+                    path.display(),
+                    note.node.index,
+                    note.message
+                );
+            }
+        }
+    }
+
+    eprintln!(
+        "{} files checked: {} errors, {} warnings",
+        paths.len(),
+        errors,
+        warnings
+    );
+    Ok(if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn fmt(paths: &[PathBuf], check: bool) -> Result<ExitCode> {
+    let mut failed = false;
+    for path in paths {
+        let formatted = read_script(&path.to_string_lossy())
+            .and_then(|source| Ok((format_source(&source)?, source)));
+        let (formatted, source) = match formatted {
+            Ok(rv) => rv,
+            Err(err) => {
+                match err.downcast_ref::<ParseError>() {
+                    Some(error) => {
+                        eprintln!(
+                            "{}:{}: {}",
+                            path.display(),
+                            error.line_number,
+                            error.message
+                        )
+                    }
+                    None => eprintln!("{}: {}", path.display(), err),
+                }
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", path.display());
+            failed = true;
+        } else {
+            fs::write(path, formatted)?;
+        }
+    }
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn search(paths: &[PathBuf], pattern: &Pattern) -> Result<ExitCode> {
+    let mut found = false;
+    for path in paths {
+        let mut last = None;
+        for hit in search_script(&parse(path)?, pattern) {
+            // Once per line, however many times the pattern occurs in it
+            if last.replace((hit.line_number, hit.kind)) == Some((hit.line_number, hit.kind)) {
+                continue;
+            }
+            found = true;
+            let mut place = format!("{}:{}:", path.display(), hit.line_number);
+            if !hit.labels.is_empty() {
+                place = format!("{} {}", place, hit.labels.join("/"));
+            }
+            if let Some(speaker) = hit.speaker {
+                place = format!("{} {}", place, speaker);
+            }
+            match hit.kind {
+                MatchKind::Dialogue => println!("{} {:?}", place, hit.text),
+                MatchKind::Comment => println!("{} #{}", place, hit.text),
+            }
+        }
+    }
+
+    Ok(if found {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn stats(paths: &[PathBuf]) -> Result<ExitCode> {
+    let mut labels = 0;
+    let mut characters = 0;
+    let mut dialogue = 0;
+    let mut words = 0;
+    let mut statements: BTreeMap<String, usize> = BTreeMap::new();
+
+    for path in paths {
+        let script = parse(path)?;
+        walk(&script.ast, &mut |_, node| {
+            *statements.entry(Node::from(node).kind).or_default() += 1;
+            match node {
+                AST::Label(..) => labels += 1,
+                AST::Say(_, _, what, _) => {
+                    dialogue += 1;
+                    words += what.split_whitespace().count();
+                }
+                _ if node.defined_character().is_some() => characters += 1,
+                _ => {}
+            }
+        });
+    }
+
+    println!("files: {}", paths.len());
+    println!("labels: {}", labels);
+    println!("characters: {}", characters);
+    println!("dialogue lines: {}", dialogue);
+    println!("dialogue words: {}", words);
+    println!("statements: {}", statements.values().sum::<usize>());
+    for (kind, count) in statements {
+        println!("  {}: {}", kind, count);
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,121 @@
+use crate::lexer::Lexer;
+use crate::parsers::{block, convert, statements, Owned, Quoting, Stmt, Tree, AST};
+use crate::prelude::*;
+use crate::{group_logical_lines, parse_logical_lines};
+use alloc::borrow::Cow;
+use anyhow::Result;
+
+/// AST whose strings borrow from the script source wherever the parsed text
+/// is written there verbatim. Text changed by parsing, such as unescaped
+/// strings or lines joined by a backslash, is owned.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BorrowedAST<'src> {
+    Define(usize, Cow<'src, str>),
+    Hide(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Init(usize, Vec<BorrowedAST<'src>>, i32),
+    Jump(usize, Cow<'src, str>, bool),
+    Label(
+        usize,
+        Cow<'src, str>,
+        Vec<BorrowedAST<'src>>,
+        Option<Cow<'src, str>>,
+    ),
+    Play(usize, Cow<'src, str>, Cow<'src, str>),
+    Return(usize, Option<Cow<'src, str>>),
+    Say(usize, Option<Cow<'src, str>>, Cow<'src, str>, Quoting),
+    Scene(
+        usize,
+        Option<Cow<'src, str>>,
+        Cow<'src, str>,
+        Option<Cow<'src, str>>,
+    ),
+    Show(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Stop(usize, Cow<'src, str>, Option<Cow<'src, str>>, Option<f32>),
+    GameMechanic(usize, Cow<'src, str>),
+    LLMGenerate(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Include(usize, Cow<'src, str>),
+    Custom(usize, Cow<'src, str>, Cow<'src, str>),
+    Error {
+        loc: usize,
+        message: Cow<'src, str>,
+        raw_text: Cow<'src, str>,
+    },
+}
+
+impl BorrowedAST<'_> {
+    /// Copies borrowed strings, detaching the node from the source
+    pub fn into_owned(self) -> AST {
+        AST::from(self)
+    }
+}
+
+impl From<BorrowedAST<'_>> for AST {
+    fn from(node: BorrowedAST<'_>) -> Self {
+        let c = &mut Owned;
+        statements!(convert!(node, BorrowedAST => AST, c))
+    }
+}
+
+/// Parses a script into an AST borrowing from `source`
+pub fn parse_borrowed<'src>(
+    source: &'src str,
+    filename: &str,
+) -> Result<(Vec<BorrowedAST<'src>>, Vec<String>)> {
+    let lines = parse_logical_lines(source, filename)?;
+    let blocks = group_logical_lines(lines)?;
+    let (ast, errors) = block(&mut Lexer::borrowed(&blocks, true), &mut Borrowing);
+    Ok((ast, errors.iter().map(ToString::to_string).collect()))
+}
+
+/// Tree of nodes borrowing the text the lexer matched
+struct Borrowing;
+
+impl<'src> Tree<'src> for Borrowing {
+    type Node = BorrowedAST<'src>;
+    type Block = Vec<BorrowedAST<'src>>;
+
+    fn block(&mut self) -> Self::Block {
+        Vec::new()
+    }
+
+    fn push(&mut self, block: &mut Self::Block, node: Self::Node) {
+        block.push(node);
+    }
+
+    fn node(&mut self, stmt: Stmt<'src, Self::Block>) -> Self::Node {
+        let c = self;
+        statements!(convert!(stmt, Stmt => BorrowedAST, c))
+    }
+}
+
+/// Fields of a borrowing node out of the ones of a [`Stmt`], which are the same
+/// but for the error message, see [`convert`]
+impl Borrowing {
+    fn text<'src>(&mut self, text: Cow<'src, str>) -> Cow<'src, str> {
+        text
+    }
+
+    fn opt_text<'src>(&mut self, text: Option<Cow<'src, str>>) -> Option<Cow<'src, str>> {
+        text
+    }
+
+    fn name<'src>(&mut self, name: Cow<'src, str>) -> Cow<'src, str> {
+        name
+    }
+
+    fn opt_name<'src>(&mut self, name: Option<Cow<'src, str>>) -> Option<Cow<'src, str>> {
+        name
+    }
+
+    fn message(&mut self, message: String) -> Cow<'static, str> {
+        Cow::Owned(message)
+    }
+
+    fn copy<T>(&mut self, value: T) -> T {
+        value
+    }
+
+    fn nodes<T>(&mut self, nodes: T) -> T {
+        nodes
+    }
+}
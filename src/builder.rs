@@ -0,0 +1,106 @@
+use crate::parsers::{renumber, Quoting, AST};
+
+/// Fluent builder for scripts; indices are assigned in document order by `build`
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    nodes: Vec<AST>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Script::default()
+    }
+
+    fn push(mut self, node: AST) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    pub fn define(self, definition: &str) -> Self {
+        self.push(AST::Define(0, definition.to_string()))
+    }
+
+    pub fn label(self, name: &str, body: impl FnOnce(Script) -> Script) -> Self {
+        let block = body(Script::new()).nodes;
+        self.push(AST::Label(0, name.to_string(), block, None))
+    }
+
+    pub fn init(self, priority: i32, body: impl FnOnce(Script) -> Script) -> Self {
+        let block = body(Script::new()).nodes;
+        self.push(AST::Init(0, block, priority))
+    }
+
+    pub fn say(self, who: &str, what: &str) -> Self {
+        self.push(AST::Say(
+            0,
+            Some(who.to_string()),
+            what.to_string(),
+            Quoting::default(),
+        ))
+    }
+
+    pub fn narrate(self, what: &str) -> Self {
+        self.push(AST::Say(0, None, what.to_string(), Quoting::default()))
+    }
+
+    pub fn jump(self, target: &str) -> Self {
+        self.push(AST::Jump(0, target.to_string(), false))
+    }
+
+    pub fn return_(self) -> Self {
+        self.push(AST::Return(0, Some(String::new())))
+    }
+
+    pub fn scene(self, image: &str) -> Self {
+        self.push(AST::Scene(
+            0,
+            Some(image.to_string()),
+            "master".to_string(),
+            None,
+        ))
+    }
+
+    pub fn show(self, image: &str) -> Self {
+        self.push(AST::Show(0, image.to_string(), None))
+    }
+
+    pub fn hide(self, image: &str) -> Self {
+        self.push(AST::Hide(0, image.to_string(), None))
+    }
+
+    pub fn play(self, channel: &str, filename: &str) -> Self {
+        self.push(AST::Play(0, channel.to_string(), filename.to_string()))
+    }
+
+    pub fn stop(self, channel: &str, fadeout: Option<f32>) -> Self {
+        let effect = fadeout.map(|_| "fadeout".to_string());
+        self.push(AST::Stop(0, channel.to_string(), effect, fadeout))
+    }
+
+    pub fn game_mechanic(self, mechanic: &str) -> Self {
+        self.push(AST::GameMechanic(0, mechanic.to_string()))
+    }
+
+    pub fn llm_generate(self, who: &str, prompt: Option<&str>) -> Self {
+        self.push(AST::LLMGenerate(
+            0,
+            who.to_string(),
+            prompt.map(|p| p.to_string()),
+        ))
+    }
+
+    pub fn include(self, path: &str) -> Self {
+        self.push(AST::Include(0, path.to_string()))
+    }
+
+    pub fn custom(self, keyword: &str, text: &str) -> Self {
+        self.push(AST::Custom(0, keyword.to_string(), text.to_string()))
+    }
+
+    /// Assigns indices in document order and returns the finished AST
+    pub fn build(self) -> Vec<AST> {
+        let mut nodes = self.nodes;
+        renumber(&mut nodes);
+        nodes
+    }
+}
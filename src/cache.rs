@@ -0,0 +1,69 @@
+use crate::parsers::AST;
+use crate::{parse_scenario_from_string, read_script};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Bumped whenever the cached layout or the AST changes shape
+const CACHE_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    source_hash: [u8; 16],
+    ast: Vec<AST>,
+}
+
+fn source_hash(source: &str) -> [u8; 16] {
+    md5::compute(source).0
+}
+
+/// Writes the AST in a compact binary encoding, tagged with a hash of its source
+pub fn save(path: &Path, source: &str, ast: &[AST]) -> Result<()> {
+    let file = CacheFile {
+        version: CACHE_VERSION,
+        source_hash: source_hash(source),
+        ast: ast.to_vec(),
+    };
+
+    let bytes = bincode::serde::encode_to_vec(&file, bincode::config::standard())?;
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Reads a cached AST, or None when it was produced from a different source or cache version
+pub fn load(path: &Path, source: &str) -> Result<Option<Vec<AST>>> {
+    let bytes = fs::read(path)?;
+    let Ok((file, _)) =
+        bincode::serde::decode_from_slice::<CacheFile, _>(&bytes, bincode::config::standard())
+    else {
+        return Ok(None);
+    };
+
+    if file.version != CACHE_VERSION || file.source_hash != source_hash(source) {
+        return Ok(None);
+    }
+
+    Ok(Some(file.ast))
+}
+
+/// Parses a script, reusing the cache when the script is unchanged.
+///
+/// Only scripts that parsed without errors are cached, so a cache hit has no errors to report.
+pub fn parse_cached(filename: &str, cache_path: &Path) -> Result<(Vec<AST>, Vec<String>)> {
+    let source = read_script(filename)?;
+
+    if cache_path.is_file() {
+        if let Some(ast) = load(cache_path, &source)? {
+            return Ok((ast, Vec::new()));
+        }
+    }
+
+    let (ast, errors) = parse_scenario_from_string(&source, filename)?;
+    if errors.is_empty() {
+        save(cache_path, &source, &ast)?;
+    }
+
+    Ok((ast, errors))
+}
@@ -0,0 +1,60 @@
+use crate::parsers::{walk_labeled, NodeRef, AST};
+use std::collections::BTreeMap;
+
+/// Speakers Ren'Py defines itself, which scripts use without defining them
+pub const BUILTIN_CHARACTERS: [&str; 6] =
+    ["narrator", "centered", "vcentered", "extend", "adv", "nvl"];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CharacterUsage {
+    /// Number of say statements spoken by the character
+    pub lines: usize,
+    /// Labels the character speaks in, in order of first appearance
+    pub labels: Vec<String>,
+    /// The `define name = Character(...)` statement, if any
+    pub definition: Option<NodeRef>,
+}
+
+impl CharacterUsage {
+    pub fn is_defined(&self) -> bool {
+        self.definition.is_some()
+    }
+
+    /// Adds the usage of the same character in another script. The
+    /// definition found first is kept.
+    pub fn merge(&mut self, other: CharacterUsage) {
+        self.lines += other.lines;
+        for label in other.labels {
+            if !self.labels.contains(&label) {
+                self.labels.push(label);
+            }
+        }
+        self.definition = self.definition.take().or(other.definition);
+    }
+}
+
+/// Aggregates say statements per speaker, including defined characters that never speak
+pub fn character_usage(ast: &[AST]) -> BTreeMap<String, CharacterUsage> {
+    let mut rv: BTreeMap<String, CharacterUsage> = BTreeMap::new();
+
+    walk_labeled(ast, &mut |path, node, label| {
+        if let Some(name) = node.defined_character() {
+            let usage = rv.entry(name.to_string()).or_default();
+            usage
+                .definition
+                .get_or_insert_with(|| NodeRef::new(path, node));
+        }
+
+        if let AST::Say(_, Some(who), ..) = node {
+            let usage = rv.entry(who.clone()).or_default();
+            usage.lines += 1;
+            if let Some(label) = label {
+                if !usage.labels.iter().any(|l| l == label) {
+                    usage.labels.push(label.to_string());
+                }
+            }
+        }
+    });
+
+    rv
+}
@@ -0,0 +1,334 @@
+use crate::parsers::{NodeRef, ParseError};
+use crate::prelude::*;
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Stable identifier of a kind of problem, like `RPY0201`, for tools to
+/// filter and document them by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Code {
+    // Scanning a script into lines
+    /// Problem without a more specific code
+    Other,
+    /// Tab character in the indentation of a script
+    TabCharacter,
+    /// Tab characters expanded to spaces
+    TabExpanded,
+    /// Line swallowed by an unclosed string or parenthesis
+    UnterminatedLine,
+    /// Script file that isn't valid UTF-8 or UTF-16
+    InvalidEncoding,
+
+    // Indentation and blocks
+    /// Block under a statement that doesn't take one
+    UnexpectedBlock,
+    /// Line indented differently from the lines of its block
+    IndentMismatch,
+    /// Statement without the block it needs
+    MissingBlock,
+    /// Blocks nested deeper than the options allow
+    NestingTooDeep,
+    /// Block indented by a different number of spaces than earlier ones
+    SuspiciousIndentation,
+
+    // Statements
+    /// Speaker without a line of dialogue
+    MissingSayText,
+    /// Line that isn't any known statement
+    UnknownStatement,
+    /// Text left over after a statement
+    ExpectedEndOfLine,
+    /// Statement missing a name, string or other argument
+    MissingArgument,
+    /// Audio channel other than music or sound
+    InvalidAudioChannel,
+    /// Audio file that isn't mp3, ogg or wav
+    InvalidAudioFile,
+    /// Init priority that doesn't fit in 32 bits
+    InitPriorityOutOfRange,
+    /// Image clause kept as written but not interpreted, like `at` or `with`
+    UnsupportedClause,
+    /// Form Ren'Py still accepts but deprecates, like image manipulators
+    DeprecatedForm,
+
+    // Expressions
+    /// String without its closing quote
+    UnterminatedString,
+    /// Closing bracket without a matching opening one, or the other way around
+    UnbalancedBracket,
+    /// Number that can't be read or is out of range
+    InvalidNumber,
+    /// Required character or word missing
+    ExpectedToken,
+
+    // Checks of parsed scripts
+    /// Jump to a label that isn't defined
+    UndefinedLabel,
+    /// Speaker that isn't defined as a Character
+    UndefinedCharacter,
+    /// Hide of an image that was never shown
+    HideWithoutShow,
+    /// Stop of an audio channel that isn't playing
+    StopWithoutPlay,
+    /// Character defined but never speaking
+    UnusedCharacter,
+    /// Label running into the next one without a return or jump
+    FallThrough,
+    /// Line of dialogue without any text to show
+    EmptyDialogue,
+    /// Image or audio file not found in the game directory
+    MissingAsset,
+    /// Statement after a return or jump, which can never run
+    DeadCode,
+    /// Finding of a user-provided [`TextChecker`](crate::lint::text::TextChecker)
+    TextCheck,
+
+    // Projects
+    /// Label defined more than once
+    DuplicateLabel,
+    /// Script that ends up including itself
+    IncludeCycle,
+    /// Included file that doesn't exist
+    IncludeNotFound,
+    /// Included file that can't be read
+    IncludeUnreadable,
+    /// Script or directory of a project that can't be read
+    Unreadable,
+}
+
+impl Code {
+    /// Every code, in order
+    pub const ALL: [Code; 38] = [
+        Code::Other,
+        Code::TabCharacter,
+        Code::TabExpanded,
+        Code::UnterminatedLine,
+        Code::InvalidEncoding,
+        Code::UnexpectedBlock,
+        Code::IndentMismatch,
+        Code::MissingBlock,
+        Code::NestingTooDeep,
+        Code::SuspiciousIndentation,
+        Code::MissingSayText,
+        Code::UnknownStatement,
+        Code::ExpectedEndOfLine,
+        Code::MissingArgument,
+        Code::InvalidAudioChannel,
+        Code::InvalidAudioFile,
+        Code::InitPriorityOutOfRange,
+        Code::UnsupportedClause,
+        Code::DeprecatedForm,
+        Code::UnterminatedString,
+        Code::UnbalancedBracket,
+        Code::InvalidNumber,
+        Code::ExpectedToken,
+        Code::UndefinedLabel,
+        Code::UndefinedCharacter,
+        Code::HideWithoutShow,
+        Code::StopWithoutPlay,
+        Code::UnusedCharacter,
+        Code::FallThrough,
+        Code::EmptyDialogue,
+        Code::MissingAsset,
+        Code::DeadCode,
+        Code::TextCheck,
+        Code::DuplicateLabel,
+        Code::IncludeCycle,
+        Code::IncludeNotFound,
+        Code::IncludeUnreadable,
+        Code::Unreadable,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::Other => "RPY0000",
+            Code::TabCharacter => "RPY0001",
+            Code::TabExpanded => "RPY0002",
+            Code::UnterminatedLine => "RPY0003",
+            Code::InvalidEncoding => "RPY0004",
+            Code::UnexpectedBlock => "RPY0101",
+            Code::IndentMismatch => "RPY0102",
+            Code::MissingBlock => "RPY0103",
+            Code::NestingTooDeep => "RPY0104",
+            Code::SuspiciousIndentation => "RPY0105",
+            Code::MissingSayText => "RPY0201",
+            Code::UnknownStatement => "RPY0202",
+            Code::ExpectedEndOfLine => "RPY0203",
+            Code::MissingArgument => "RPY0204",
+            Code::InvalidAudioChannel => "RPY0205",
+            Code::InvalidAudioFile => "RPY0206",
+            Code::InitPriorityOutOfRange => "RPY0207",
+            Code::UnsupportedClause => "RPY0208",
+            Code::DeprecatedForm => "RPY0209",
+            Code::UnterminatedString => "RPY0301",
+            Code::UnbalancedBracket => "RPY0302",
+            Code::InvalidNumber => "RPY0303",
+            Code::ExpectedToken => "RPY0304",
+            Code::UndefinedLabel => "RPY1001",
+            Code::UndefinedCharacter => "RPY1002",
+            Code::HideWithoutShow => "RPY1003",
+            Code::StopWithoutPlay => "RPY1004",
+            Code::UnusedCharacter => "RPY1005",
+            Code::FallThrough => "RPY1006",
+            Code::EmptyDialogue => "RPY1007",
+            Code::MissingAsset => "RPY1008",
+            Code::DeadCode => "RPY1009",
+            Code::TextCheck => "RPY1010",
+            Code::DuplicateLabel => "RPY1101",
+            Code::IncludeCycle => "RPY1102",
+            Code::IncludeNotFound => "RPY1103",
+            Code::IncludeUnreadable => "RPY1104",
+            Code::Unreadable => "RPY1105",
+        }
+    }
+
+    /// The code of a string like `RPY0201`
+    pub fn parse(code: &str) -> Option<Code> {
+        Code::ALL.into_iter().find(|c| c.as_str() == code)
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Finding about a specific node of an already parsed script
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    pub node: NodeRef,
+    /// Other statements the finding is about, like the first definition of a
+    /// label defined twice
+    pub related: Vec<Related>,
+}
+
+/// Note pointing at another statement of a [`Diagnostic`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Related {
+    pub node: NodeRef,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] on line {}: {}",
+            self.severity, self.code, self.node.index, self.message
+        )?;
+        for related in &self.related {
+            write!(
+                f,
+                "\n  note on line {}: {}",
+                related.node.index, related.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostic {
+    pub fn error(node: NodeRef, code: Code, message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            node,
+            related: Vec::new(),
+        }
+    }
+
+    pub fn warning(node: NodeRef, code: Code, message: String) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            node,
+            related: Vec::new(),
+        }
+    }
+
+    /// Adds a note pointing at another statement
+    pub fn with_related(mut self, node: NodeRef, message: String) -> Self {
+        self.related.push(Related { node, message });
+        self
+    }
+}
+
+/// Problems found while parsing a script, in the order they were found
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    items: Vec<(Severity, ParseError)>,
+}
+
+impl Diagnostics {
+    pub fn warning(&mut self, error: ParseError) {
+        self.items.push((Severity::Warning, error));
+    }
+
+    pub fn error(&mut self, error: ParseError) {
+        self.items.push((Severity::Error, error));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Severity, &ParseError)> {
+        self.items
+            .iter()
+            .map(|(severity, error)| (*severity, error))
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ParseError> {
+        self.of(Severity::Warning)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ParseError> {
+        self.of(Severity::Error)
+    }
+
+    fn of(&self, severity: Severity) -> impl Iterator<Item = &ParseError> {
+        self.items
+            .iter()
+            .filter(move |(s, _)| *s == severity)
+            .map(|(_, error)| error)
+    }
+
+    /// Orders the problems by line, keeping the order of the ones on the same line
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|(_, error)| error.line_number);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+impl Extend<(Severity, ParseError)> for Diagnostics {
+    fn extend<T: IntoIterator<Item = (Severity, ParseError)>>(&mut self, iter: T) {
+        self.items.extend(iter);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::diagnostics::Code;
+use crate::parsers::ParseError;
+use crate::prelude::*;
+use anyhow::Result;
+
+/// How script files that aren't valid in their encoding are read
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncodingFallback {
+    /// Fail, as Ren'Py does
+    #[default]
+    Reject,
+    /// Replace invalid bytes by U+FFFD, with a warning
+    Lossy,
+    /// Read invalid UTF-8 as Latin-1, with a warning. Windows tools often
+    /// save scripts this way.
+    Latin1,
+}
+
+/// Encoding a script was read in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    /// Detected by its byte order mark
+    Utf16Le,
+    /// Detected by its byte order mark
+    Utf16Be,
+    /// UTF-8 with invalid bytes replaced
+    LossyUtf8,
+    Latin1,
+}
+
+/// Text of a script file along with how it was read
+#[derive(Clone, Debug)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Why the text may differ from what the file holds, when a fallback was used
+    pub warning: Option<ParseError>,
+}
+
+/// Reads the bytes of a script as UTF-8, or as UTF-16 when they start with its
+/// byte order mark. A UTF-8 byte order mark is kept, the scanner skips it.
+pub fn decode(bytes: Vec<u8>, filename: &str, fallback: EncodingFallback) -> Result<Decoded> {
+    let utf16 = match bytes.get(..2) {
+        Some([0xff, 0xfe]) => Some(Encoding::Utf16Le),
+        Some([0xfe, 0xff]) => Some(Encoding::Utf16Be),
+        _ => None,
+    };
+    if let Some(encoding) = utf16 {
+        return decode_utf16(&bytes[2..], encoding, filename, fallback);
+    }
+
+    let (bytes, valid_up_to) = match String::from_utf8(bytes) {
+        Ok(text) => {
+            return Ok(Decoded {
+                text,
+                encoding: Encoding::Utf8,
+                warning: None,
+            })
+        }
+        Err(err) => {
+            let valid_up_to = err.utf8_error().valid_up_to();
+            (err.into_bytes(), valid_up_to)
+        }
+    };
+    let line_number = line_number(&bytes[..valid_up_to]);
+    let error = |message: String| error(filename, line_number, message);
+
+    let (text, encoding, message) = match fallback {
+        EncodingFallback::Reject => {
+            let message = format!(
+                "byte {:#04x} is not valid UTF-8, save the script as UTF-8",
+                bytes[valid_up_to]
+            );
+            return Err(error(message).into());
+        }
+        EncodingFallback::Lossy => (
+            String::from_utf8_lossy(&bytes).into_owned(),
+            Encoding::LossyUtf8,
+            "script is not valid UTF-8, invalid bytes were replaced",
+        ),
+        EncodingFallback::Latin1 => (
+            bytes.iter().map(|&b| b as char).collect(),
+            Encoding::Latin1,
+            "script is not valid UTF-8 and was read as Latin-1",
+        ),
+    };
+
+    Ok(Decoded {
+        text,
+        encoding,
+        warning: Some(error(message.to_string())),
+    })
+}
+
+fn decode_utf16(
+    bytes: &[u8],
+    encoding: Encoding,
+    filename: &str,
+    fallback: EncodingFallback,
+) -> Result<Decoded> {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| match (encoding, pair) {
+            (Encoding::Utf16Le, [lo, hi]) => u16::from_le_bytes([*lo, *hi]),
+            (_, [hi, lo]) => u16::from_be_bytes([*hi, *lo]),
+            // A trailing odd byte can't be a whole code unit
+            _ => 0xdc00,
+        })
+        .collect();
+
+    let text = String::from_utf16_lossy(&units);
+    let Some(invalid) = text
+        .find('\u{fffd}')
+        .filter(|_| String::from_utf16(&units).is_err())
+    else {
+        return Ok(Decoded {
+            text,
+            encoding,
+            warning: None,
+        });
+    };
+
+    let line_number = line_number(&text.as_bytes()[..invalid]);
+    if fallback == EncodingFallback::Reject {
+        let message = "script is not valid UTF-16".to_string();
+        return Err(error(filename, line_number, message).into());
+    }
+
+    let message = "script is not valid UTF-16, invalid code units were replaced";
+    Ok(Decoded {
+        text,
+        encoding,
+        warning: Some(error(filename, line_number, message.to_string())),
+    })
+}
+
+fn line_number(before: &[u8]) -> usize {
+    before.iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+fn error(filename: &str, line_number: usize, message: String) -> ParseError {
+    ParseError::new(filename.to_string(), line_number, message, None, None)
+        .with_code(Code::InvalidEncoding)
+}
@@ -0,0 +1,57 @@
+use crate::parsers::{walk_labeled, AST};
+
+const HEADER: [&str; 5] = ["file", "line", "label", "speaker", "text"];
+
+/// One row per say statement; fields are quoted as in RFC 4180 when needed
+pub fn to_csv(ast: &[AST], filename: &str) -> String {
+    rows(ast, filename, |fields| {
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        fields.join(",")
+    })
+}
+
+/// One row per say statement; tabs, newlines and backslashes are backslash-escaped
+pub fn to_tsv(ast: &[AST], filename: &str) -> String {
+    rows(ast, filename, |fields| {
+        let fields: Vec<String> = fields.iter().map(|field| tsv_field(field)).collect();
+        fields.join("\t")
+    })
+}
+
+fn rows(ast: &[AST], filename: &str, format_row: impl Fn(&[&str]) -> String) -> String {
+    let mut out = format_row(&HEADER);
+    out.push('\n');
+
+    walk_labeled(ast, &mut |_, node, label| {
+        if let AST::Say(index, who, what, _) = node {
+            let line = index.to_string();
+            let row = [
+                filename,
+                &line,
+                label.unwrap_or_default(),
+                who.as_deref().unwrap_or_default(),
+                what,
+            ];
+            out.push_str(&format_row(&row));
+            out.push('\n');
+        }
+    });
+
+    out
+}
+
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
@@ -0,0 +1,259 @@
+use crate::parsers::{statements, Quoting, AST};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Version of the exported document layout, bumped on incompatible changes
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Exported script: `{ "version": 1, "nodes": [...] }`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub nodes: Vec<Node>,
+}
+
+/// A statement in the exported layout.
+///
+/// Every node carries `type` and `index`; the other fields depend on the type
+/// and are omitted when absent:
+///
+/// | type            | fields                          |
+/// |-----------------|---------------------------------|
+/// | `define`        | `definition`                    |
+/// | `hide`, `show`  | `image`, `clauses`              |
+/// | `init`          | `priority`, `block`             |
+/// | `jump`          | `target`, `expression`          |
+/// | `label`         | `name`, `parameters`, `block`   |
+/// | `play`          | `channel`, `filename`           |
+/// | `return`        | `value`                         |
+/// | `say`           | `who`, `what`, `quoting`        |
+/// | `scene`         | `image`, `layer`, `clauses`     |
+/// | `stop`          | `channel`, `effect`, `length`   |
+/// | `game_mechanic` | `mechanic`                      |
+/// | `llm_generate`  | `who`, `prompt`                 |
+/// | `include`       | `filename`                      |
+/// | `custom`        | `name`, `value`                 |
+/// | `error`         | `message`, `raw_text`           |
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clauses: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expression: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub who: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub what: Option<String>,
+    /// How `what` was quoted, when not in plain double quotes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoting: Option<Quoting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mechanic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub block: Vec<Node>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Document {
+    pub fn new(ast: &[AST]) -> Self {
+        Document {
+            version: FORMAT_VERSION,
+            nodes: ast.iter().map(Node::from).collect(),
+        }
+    }
+
+    pub fn into_ast(self) -> Result<Vec<AST>> {
+        if self.version != FORMAT_VERSION {
+            bail!("unsupported document version {}", self.version);
+        }
+
+        self.nodes.into_iter().map(AST::try_from).collect()
+    }
+}
+
+/// Converts between an [`AST`] node and a [`Node`], moving each field to the
+/// one of the same name, see [`statements`]
+macro_rules! document {
+    (
+        ()
+        $($kind:literal $variant:ident
+            $(($($field:ident: $field_kind:ident $(= $default:literal)?),*))?
+            $({$($named:ident: $named_kind:ident $(= $named_default:literal)?),*})?
+        ),* $(,)?
+    ) => {
+        impl From<&AST> for Node {
+            fn from(ast: &AST) -> Self {
+                match ast {
+                    $(AST::$variant $((_, $($field),*))? $({ $($named,)* .. })? => Node {
+                        kind: $kind.to_string(),
+                        index: ast.index(),
+                        $($($field: Field::export($field),)*)?
+                        $($($named: Field::export($named),)*)?
+                        ..Node::default()
+                    },)*
+                }
+            }
+        }
+
+        impl TryFrom<Node> for AST {
+            type Error = anyhow::Error;
+
+            fn try_from(node: Node) -> Result<Self> {
+                let i = node.index;
+                let missing = |name: &str| {
+                    anyhow!("{} node at index {} is missing '{}'", node.kind, i, name)
+                };
+
+                let ast = match node.kind.as_str() {
+                    $($kind => AST::$variant
+                        $((i, $(Field::import(node.$field)?
+                            $(.or_else(|| Some($default.into())))?
+                            .ok_or_else(|| missing(stringify!($field)))?),*))?
+                        $({ loc: i, $($named: Field::import(node.$named)?
+                            $(.or_else(|| Some($named_default.into())))?
+                            .ok_or_else(|| missing(stringify!($named)))?),* })?,)*
+                    _ => bail!("unknown node type '{}' at index {}", node.kind, i),
+                };
+
+                Ok(ast)
+            }
+        }
+    };
+}
+
+statements!(document!());
+
+/// A field of an [`AST`] node as it's written in a [`Node`]
+trait Field: Sized {
+    type Exported;
+
+    fn export(&self) -> Self::Exported;
+
+    /// None when the field is required and the node leaves it out
+    fn import(exported: Self::Exported) -> Result<Option<Self>>;
+}
+
+impl Field for String {
+    type Exported = Option<String>;
+
+    fn export(&self) -> Option<String> {
+        Some(self.clone())
+    }
+
+    fn import(exported: Option<String>) -> Result<Option<Self>> {
+        Ok(exported)
+    }
+}
+
+impl Field for Option<String> {
+    type Exported = Option<String>;
+
+    fn export(&self) -> Option<String> {
+        self.clone()
+    }
+
+    fn import(exported: Option<String>) -> Result<Option<Self>> {
+        Ok(Some(exported))
+    }
+}
+
+impl Field for Vec<AST> {
+    type Exported = Vec<Node>;
+
+    fn export(&self) -> Vec<Node> {
+        self.iter().map(Node::from).collect()
+    }
+
+    fn import(exported: Vec<Node>) -> Result<Option<Self>> {
+        exported
+            .into_iter()
+            .map(AST::try_from)
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+}
+
+impl Field for i32 {
+    type Exported = Option<i32>;
+
+    fn export(&self) -> Option<i32> {
+        Some(*self)
+    }
+
+    fn import(exported: Option<i32>) -> Result<Option<Self>> {
+        Ok(Some(exported.unwrap_or_default()))
+    }
+}
+
+impl Field for bool {
+    type Exported = bool;
+
+    fn export(&self) -> bool {
+        *self
+    }
+
+    fn import(exported: bool) -> Result<Option<Self>> {
+        Ok(Some(exported))
+    }
+}
+
+/// Left out when in plain double quotes
+impl Field for Quoting {
+    type Exported = Option<Quoting>;
+
+    fn export(&self) -> Option<Quoting> {
+        Some(*self).filter(|quoting| *quoting != Quoting::default())
+    }
+
+    fn import(exported: Option<Quoting>) -> Result<Option<Self>> {
+        Ok(Some(exported.unwrap_or_default()))
+    }
+}
+
+impl Field for Option<f32> {
+    type Exported = Option<f32>;
+
+    fn export(&self) -> Option<f32> {
+        *self
+    }
+
+    fn import(exported: Option<f32>) -> Result<Option<Self>> {
+        Ok(Some(exported))
+    }
+}
@@ -0,0 +1,34 @@
+use crate::flow::{EdgeKind, FlowGraph};
+use crate::parsers::{walk, AST};
+
+/// Renders the label graph as GraphViz DOT; fall-through edges are dashed
+pub fn to_dot(ast: &[AST]) -> String {
+    let mut out = String::from("digraph script {\n");
+
+    walk(ast, &mut |_, node| {
+        if let AST::Label(_, name, _, _) = node {
+            out.push_str(&format!("    {};\n", quote(name)));
+        }
+    });
+
+    for edge in FlowGraph::new(ast).label_edges() {
+        let style = match edge.kind {
+            EdgeKind::Jump => "",
+            EdgeKind::FallThrough => ", style=dashed",
+        };
+        out.push_str(&format!(
+            "    {} -> {} [label=\"line {}\"{}];\n",
+            quote(&edge.from),
+            quote(&edge.to),
+            edge.line_number,
+            style
+        ));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
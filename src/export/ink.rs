@@ -0,0 +1,105 @@
+use super::{label_segments, node_name};
+use crate::parsers::AST;
+use crate::symbols::qualified_name;
+use crate::writer::statement;
+
+/// Ink source along with the statements that had no Ink equivalent
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InkExport {
+    pub source: String,
+    pub unconverted: Vec<Unconverted>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unconverted {
+    pub line_number: usize,
+    /// Ren'Py source of the statement
+    pub statement: String,
+}
+
+/// Converts labels into knots, dialogue into text lines and jumps into diverts.
+///
+/// Staging and audio statements become tags; statements Ink can't express are
+/// kept as comments and listed in the report. Local labels are named by their
+/// qualified names, with `_` for the dot.
+pub fn to_ink(ast: &[AST]) -> InkExport {
+    let segments = label_segments(ast);
+    let mut export = InkExport::default();
+
+    let entry = segments
+        .iter()
+        .find(|segment| segment.name == "start")
+        .or(segments.first());
+    if let Some(entry) = entry {
+        export
+            .source
+            .push_str(&format!("-> {}\n", node_name(&entry.name)));
+    }
+
+    for segment in &segments {
+        export
+            .source
+            .push_str(&format!("\n=== {} ===\n", node_name(&segment.name)));
+
+        for node in &segment.statements {
+            match line(node, segment.global) {
+                Some(line) => export.source.push_str(&line),
+                None => {
+                    let Some(text) = statement(node) else {
+                        continue;
+                    };
+                    export.source.push_str(&format!("// {}", text));
+                    export.unconverted.push(Unconverted {
+                        line_number: node.index(),
+                        statement: text,
+                    });
+                }
+            }
+            export.source.push('\n');
+        }
+        if let Some(next) = &segment.falls_into {
+            export.source.push_str(&format!("-> {}\n", node_name(next)));
+        }
+    }
+
+    export
+}
+
+/// Ink line of a statement, resolving local jump targets against `global`
+fn line(node: &AST, global: Option<&str>) -> Option<String> {
+    let line = match node {
+        AST::Say(_, Some(who), what, _) => {
+            format!("{}: {}", escape(who, true), escape(what, false))
+        }
+        AST::Say(_, None, what, _) => escape(what, true),
+        AST::Jump(_, target, false) => {
+            format!("-> {}", node_name(&qualified_name(target, global)))
+        }
+        AST::Return(..) => "-> END".to_string(),
+        AST::Scene(_, Some(image), ..) => format!("# scene {}", image),
+        AST::Scene(_, None, ..) => "# scene".to_string(),
+        AST::Show(_, image, _) => format!("# show {}", image),
+        AST::Hide(_, image, _) => format!("# hide {}", image),
+        AST::Play(_, channel, filename) => format!("# play {} {}", channel, filename),
+        AST::Stop(_, channel, _, _) => format!("# stop {}", channel),
+        _ => return None,
+    };
+
+    Some(line)
+}
+
+/// Escapes characters Ink reads as markup, and choice, gather or logic marks at the start of a line
+fn escape(text: &str, line_start: bool) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        let special = matches!(
+            c,
+            '\\' | '{' | '}' | '[' | ']' | '|' | '#' | '/' | '<' | '>'
+        ) || (line_start && i == 0 && matches!(c, '*' | '+' | '-' | '~' | '='));
+        if special {
+            rv.push('\\');
+        }
+        rv.push(if c == '\n' { ' ' } else { c });
+    }
+    rv
+}
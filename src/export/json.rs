@@ -0,0 +1,13 @@
+use super::Document;
+use crate::parsers::AST;
+use anyhow::Result;
+
+/// Serializes the AST into the versioned layout described on `Node`
+pub fn to_json(ast: &[AST]) -> String {
+    serde_json::to_string_pretty(&Document::new(ast)).expect("document is always serializable")
+}
+
+pub fn from_json(json: &str) -> Result<Vec<AST>> {
+    let document: Document = serde_json::from_str(json)?;
+    document.into_ast()
+}
@@ -0,0 +1,82 @@
+use crate::parsers::AST;
+use crate::symbols::qualified_name;
+
+pub mod csv;
+#[cfg(feature = "serde")]
+mod document;
+pub mod dot;
+pub mod ink;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "ron")]
+pub mod ron;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+pub mod yarn;
+
+#[cfg(feature = "serde")]
+pub use document::{Document, Node, FORMAT_VERSION};
+
+/// Statements run by a label until the next label starts, in document order
+pub(crate) struct Segment<'a> {
+    /// Qualified name of the label, see [`qualified_name`]
+    pub name: String,
+    /// Global label the local jump targets of the segment belong to
+    pub global: Option<&'a str>,
+    pub statements: Vec<&'a AST>,
+    /// Label execution falls into when the segment doesn't end in a jump or return
+    pub falls_into: Option<String>,
+}
+
+/// Flattens labels into segments the way Ren'Py executes them: a nested label
+/// starts a new segment, and the parent's remaining statements run after it.
+/// Statements outside of labels and init blocks are not part of the story flow.
+pub(crate) fn label_segments(ast: &[AST]) -> Vec<Segment<'_>> {
+    fn collect<'a>(
+        block: &'a [AST],
+        global: &mut Option<&'a str>,
+        segments: &mut Vec<Segment<'a>>,
+    ) {
+        for node in block {
+            match node {
+                AST::Label(_, name, body, _) => {
+                    if !name.contains('.') {
+                        *global = Some(name);
+                    }
+                    let name = qualified_name(name, *global);
+                    if let Some(previous) = segments.last_mut() {
+                        if !matches!(
+                            previous.statements.last(),
+                            Some(AST::Jump(..) | AST::Return(..))
+                        ) {
+                            previous.falls_into = Some(name.clone());
+                        }
+                    }
+                    segments.push(Segment {
+                        name,
+                        global: *global,
+                        statements: Vec::new(),
+                        falls_into: None,
+                    });
+                    collect(body, global, segments);
+                }
+                AST::Init(..) | AST::Define(..) => {}
+                _ => {
+                    if let Some(segment) = segments.last_mut() {
+                        segment.statements.push(node);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut segments = Vec::new();
+    collect(ast, &mut None, &mut segments);
+    segments
+}
+
+/// Name of a label usable as a node or knot title, which can't hold the dots
+/// of qualified names
+pub(crate) fn node_name(label: &str) -> String {
+    label.replace('.', "_")
+}
@@ -0,0 +1,17 @@
+use super::Document;
+use crate::parsers::AST;
+use anyhow::Result;
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+
+/// Serializes the AST into the same layout as the JSON export
+pub fn to_ron(ast: &[AST]) -> String {
+    let config = PrettyConfig::default().extensions(Extensions::IMPLICIT_SOME);
+    ron::ser::to_string_pretty(&Document::new(ast), config)
+        .expect("document is always serializable")
+}
+
+pub fn from_ron(ron: &str) -> Result<Vec<AST>> {
+    let document: Document = ron::from_str(ron)?;
+    document.into_ast()
+}
@@ -0,0 +1,13 @@
+use super::Document;
+use crate::parsers::AST;
+use anyhow::Result;
+
+/// Serializes the AST into the same layout as the JSON export
+pub fn to_yaml(ast: &[AST]) -> String {
+    serde_norway::to_string(&Document::new(ast)).expect("document is always serializable")
+}
+
+pub fn from_yaml(yaml: &str) -> Result<Vec<AST>> {
+    let document: Document = serde_norway::from_str(yaml)?;
+    document.into_ast()
+}
@@ -0,0 +1,77 @@
+use super::{label_segments, node_name};
+use crate::parsers::AST;
+use crate::symbols::qualified_name;
+
+/// Converts labels into Yarn Spinner nodes; other statements become commands.
+/// Local labels are titled by their qualified names, with `_` for the dot.
+pub fn to_yarn(ast: &[AST]) -> String {
+    let mut out = String::new();
+
+    for segment in label_segments(ast) {
+        out.push_str(&format!("title: {}\n---\n", node_name(&segment.name)));
+
+        for node in &segment.statements {
+            if let Some(line) = line(node, segment.global) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        if let Some(next) = segment.falls_into {
+            out.push_str(&format!("<<jump {}>>\n", node_name(&next)));
+        }
+
+        out.push_str("===\n");
+    }
+
+    out
+}
+
+/// Yarn line of a statement, resolving local jump targets against `global`
+fn line(node: &AST, global: Option<&str>) -> Option<String> {
+    let line = match node {
+        AST::Say(_, Some(who), what, _) => format!("{}: {}", who, escape(what)),
+        AST::Say(_, None, what, _) => escape(what),
+        AST::Jump(_, target, false) => {
+            format!("<<jump {}>>", node_name(&qualified_name(target, global)))
+        }
+        AST::Jump(_, target, true) => format!("<<jump {}>>", target),
+        AST::Return(..) => "<<stop>>".to_string(),
+        AST::Scene(_, Some(image), ..) => format!("<<scene {}>>", image),
+        AST::Scene(_, None, ..) => "<<scene>>".to_string(),
+        AST::Show(_, image, _) => format!("<<show {}>>", image),
+        AST::Hide(_, image, _) => format!("<<hide {}>>", image),
+        AST::Play(_, channel, filename) => format!("<<play {} \"{}\">>", channel, filename),
+        // `stop` is Yarn's built-in for ending the dialogue
+        AST::Stop(_, channel, Some(effect), Some(length)) => {
+            format!("<<stop_audio {} {} {}>>", channel, effect, length)
+        }
+        AST::Stop(_, channel, _, _) => format!("<<stop_audio {}>>", channel),
+        AST::GameMechanic(_, mechanic) => format!("<<game_mechanic \"{}\">>", mechanic),
+        AST::LLMGenerate(_, who, Some(prompt)) => {
+            format!("<<llm_generate {} \"{}\">>", who, prompt)
+        }
+        AST::LLMGenerate(_, who, None) => format!("<<llm_generate {}>>", who),
+        AST::Include(_, path) => format!("<<include \"{}\">>", path),
+        AST::Custom(_, keyword, text) if text.is_empty() => format!("<<{}>>", keyword),
+        AST::Custom(_, keyword, text) => format!("<<{} {}>>", keyword, text),
+        AST::Define(..) | AST::Init(..) | AST::Label(..) | AST::Error { .. } => return None,
+    };
+
+    Some(line)
+}
+
+/// Escapes characters Yarn reads as markup, expressions, hashtags or commands
+fn escape(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '[' | ']' | '{' | '}' | '<' | '>' | '#' | '/' => {
+                rv.push('\\');
+                rv.push(c);
+            }
+            '\n' => rv.push(' '),
+            _ => rv.push(c),
+        }
+    }
+    rv
+}
@@ -0,0 +1,313 @@
+use crate::diagnostics::{Code, Severity};
+use crate::export::json::to_json;
+use crate::export::Node;
+use crate::options::ParseOptions;
+use crate::parse_script;
+use crate::parsers::{walk, ParseError, AST};
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// A parsed script handed to C, with its nodes in document order.
+///
+/// Build the C library with `cargo rustc --release --features ffi --crate-type cdylib`
+/// and include `include/renpy_parser.h`. Strings passed in must be
+/// nul-terminated UTF-8; strings returned for a handle live as long as it does.
+pub struct RenpyScript {
+    ast: Vec<AST>,
+    nodes: Vec<FfiNode>,
+    diagnostics: Vec<FfiDiagnostic>,
+}
+
+struct FfiNode {
+    path: Vec<usize>,
+    kind: CString,
+    line: usize,
+}
+
+struct FfiDiagnostic {
+    is_error: bool,
+    code: CString,
+    line: usize,
+    message: CString,
+}
+
+impl RenpyScript {
+    fn ast_node(&self, index: usize) -> Option<&AST> {
+        let (first, rest) = self.nodes.get(index)?.path.split_first()?;
+        rest.iter()
+            .try_fold(self.ast.get(*first)?, |ast, &i| ast.block()?.get(i))
+    }
+}
+
+/// Text that C can hold, dropping interior nul bytes
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+/// # Safety
+///
+/// `text` must be null or point to a nul-terminated string.
+unsafe fn str_arg<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    CStr::from_ptr(text).to_str().ok()
+}
+
+/// Parses a script, recovering from statements that fail to parse. Returns
+/// null when `source` is null or not UTF-8; `filename` may be null. Scripts
+/// that can't be read into statements at all, like ones indented with tabs,
+/// give a handle without nodes whose only diagnostic is the error.
+///
+/// # Safety
+///
+/// `source` and `filename` must be null or point to nul-terminated strings.
+/// The handle must be freed with [`renpy_parser_free`].
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_parse(
+    source: *const c_char,
+    filename: *const c_char,
+) -> *mut RenpyScript {
+    let Some(source) = str_arg(source) else {
+        return ptr::null_mut();
+    };
+    let filename = str_arg(filename).unwrap_or("<script>");
+
+    let parsed = match parse_script(source, filename, &ParseOptions::hardened()) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            let (code, line, message) = match err.downcast::<ParseError>() {
+                Ok(error) => (error.code, error.line_number, error.message),
+                Err(err) => (Code::Other, 1, err.to_string()),
+            };
+            return Box::into_raw(Box::new(RenpyScript {
+                ast: Vec::new(),
+                nodes: Vec::new(),
+                diagnostics: vec![FfiDiagnostic {
+                    is_error: true,
+                    code: c_string(code.as_str()),
+                    line,
+                    message: c_string(&message),
+                }],
+            }));
+        }
+    };
+
+    let mut nodes = Vec::new();
+    walk(&parsed.ast, &mut |path, node| {
+        nodes.push(FfiNode {
+            path: path.to_vec(),
+            kind: c_string(&Node::from(node).kind),
+            line: node.index(),
+        })
+    });
+
+    let diagnostics = parsed
+        .diagnostics
+        .iter()
+        .map(|(severity, error)| FfiDiagnostic {
+            is_error: severity == Severity::Error,
+            code: c_string(error.code.as_str()),
+            line: error.line_number,
+            message: c_string(&error.message),
+        })
+        .collect();
+
+    Box::into_raw(Box::new(RenpyScript {
+        ast: parsed.ast,
+        nodes,
+        diagnostics,
+    }))
+}
+
+/// Frees a script handle; null is ignored.
+///
+/// # Safety
+///
+/// `script` must be null or a handle from [`renpy_parser_parse`] that hasn't
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_free(script: *mut RenpyScript) {
+    if !script.is_null() {
+        drop(Box::from_raw(script));
+    }
+}
+
+/// Number of nodes, nested ones included
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_node_count(script: *const RenpyScript) -> usize {
+    script.as_ref().map_or(0, |script| script.nodes.len())
+}
+
+/// Statement type of a node, as named in the JSON export, or null when out of range
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_node_kind(
+    script: *const RenpyScript,
+    index: usize,
+) -> *const c_char {
+    match script.as_ref().and_then(|script| script.nodes.get(index)) {
+        Some(node) => node.kind.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Source line of a node, or 0 when out of range
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_node_line(script: *const RenpyScript, index: usize) -> usize {
+    script
+        .as_ref()
+        .and_then(|script| script.nodes.get(index))
+        .map_or(0, |node| node.line)
+}
+
+/// How many blocks a node is nested in, 0 for top-level nodes
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_node_depth(
+    script: *const RenpyScript,
+    index: usize,
+) -> usize {
+    script
+        .as_ref()
+        .and_then(|script| script.nodes.get(index))
+        .map_or(0, |node| node.path.len() - 1)
+}
+
+/// A node and the nodes nested in it as JSON, or null when out of range.
+///
+/// # Safety
+///
+/// `script` must be null or a live handle. The string must be freed with
+/// [`renpy_parser_string_free`].
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_node_json(
+    script: *const RenpyScript,
+    index: usize,
+) -> *mut c_char {
+    match script.as_ref().and_then(|script| script.ast_node(index)) {
+        Some(ast) => c_string(&to_json(std::slice::from_ref(ast))).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The whole script in the JSON export layout, or null for a null handle.
+///
+/// # Safety
+///
+/// `script` must be null or a live handle. The string must be freed with
+/// [`renpy_parser_string_free`].
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_to_json(script: *const RenpyScript) -> *mut c_char {
+    match script.as_ref() {
+        Some(script) => c_string(&to_json(&script.ast)).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Frees a string returned by the parser; null is ignored.
+///
+/// # Safety
+///
+/// `text` must be null or a string from [`renpy_parser_to_json`] or
+/// [`renpy_parser_node_json`] that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+/// Number of errors and warnings, sorted by line
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_diagnostic_count(script: *const RenpyScript) -> usize {
+    script.as_ref().map_or(0, |script| script.diagnostics.len())
+}
+
+/// Whether a diagnostic is an error rather than a warning
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_diagnostic_is_error(
+    script: *const RenpyScript,
+    index: usize,
+) -> bool {
+    script
+        .as_ref()
+        .and_then(|script| script.diagnostics.get(index))
+        .is_some_and(|diagnostic| diagnostic.is_error)
+}
+
+/// Code of a diagnostic, like `RPY0201`, or null when out of range
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_diagnostic_code(
+    script: *const RenpyScript,
+    index: usize,
+) -> *const c_char {
+    match script
+        .as_ref()
+        .and_then(|script| script.diagnostics.get(index))
+    {
+        Some(diagnostic) => diagnostic.code.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Line of a diagnostic, or 0 when out of range
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_diagnostic_line(
+    script: *const RenpyScript,
+    index: usize,
+) -> usize {
+    script
+        .as_ref()
+        .and_then(|script| script.diagnostics.get(index))
+        .map_or(0, |diagnostic| diagnostic.line)
+}
+
+/// Message of a diagnostic, or null when out of range
+///
+/// # Safety
+///
+/// `script` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn renpy_parser_diagnostic_message(
+    script: *const RenpyScript,
+    index: usize,
+) -> *const c_char {
+    match script
+        .as_ref()
+        .and_then(|script| script.diagnostics.get(index))
+    {
+        Some(diagnostic) => diagnostic.message.as_ptr(),
+        None => ptr::null(),
+    }
+}
@@ -0,0 +1,465 @@
+use crate::parsers::{walk, NodeRef, AST};
+use crate::symbols::{qualified_name, walk_scoped};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next statement in document order
+    FallThrough,
+    Jump,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowNode {
+    pub node: NodeRef,
+    /// Qualified name of the innermost label containing the statement, see
+    /// [`qualified_name`]; labels belong to themselves
+    pub label: Option<String>,
+}
+
+/// Edge between two labels, annotated with the line of the statement it leaves from
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LabelEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+    pub line_number: usize,
+}
+
+/// Code that can never execute when the story starts from a given label
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Unreachable {
+    /// Statements no other statement leads to, e.g. ones after a jump or return
+    pub statements: Vec<FlowNode>,
+    /// Labels not reachable from the entry label
+    pub orphan_labels: Vec<FlowNode>,
+}
+
+/// Run through the story from the entry label to a return, or to the end of
+/// the script
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StoryPath {
+    /// Labels entered, in order
+    pub labels: Vec<String>,
+    /// Statements run, labels included
+    pub statements: Vec<NodeRef>,
+}
+
+/// Size and shape of the story, to keep chapters within scope
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Complexity {
+    /// Statements leading to more than one other statement
+    pub branches: usize,
+    /// Most labels on a path from the entry label, with the labels of a loop
+    /// on it counted once each
+    pub max_depth: usize,
+    /// Labels ending the story: they return and lead to no other label
+    pub endings: Vec<String>,
+    /// Branching statements of each label plus one, as in cyclomatic complexity
+    pub labels: BTreeMap<String, usize>,
+}
+
+/// Control-flow graph over statements, with node ids in document order
+#[derive(Clone, Debug, Default)]
+pub struct FlowGraph {
+    pub nodes: Vec<FlowNode>,
+    pub edges: Vec<Edge>,
+    /// Label nodes by qualified name
+    labels: HashMap<String, usize>,
+}
+
+impl FlowGraph {
+    pub fn new(ast: &[AST]) -> Self {
+        let mut graph = FlowGraph::default();
+        let mut ids: HashMap<Vec<usize>, usize> = HashMap::new();
+        // Global label of each node, which local jump targets resolve against
+        let mut scopes: Vec<Option<&str>> = Vec::new();
+        // Innermost enclosing labels, as (depth, qualified name)
+        let mut enclosing: Vec<(usize, String)> = Vec::new();
+
+        walk_scoped(ast, &mut |path, node, global| {
+            let depth = path.len();
+            while enclosing.last().is_some_and(|(d, _)| *d >= depth) {
+                enclosing.pop();
+            }
+
+            let id = graph.nodes.len();
+            if let AST::Label(_, name, _, _) = node {
+                let name = qualified_name(name, global);
+                graph.labels.entry(name.clone()).or_insert(id);
+                enclosing.push((depth, name));
+            }
+
+            ids.insert(path.to_vec(), id);
+            scopes.push(global);
+            graph.nodes.push(FlowNode {
+                node: NodeRef::new(path, node),
+                label: enclosing.last().map(|(_, name)| name.clone()),
+            });
+        });
+
+        graph.link(ast, &ids, &scopes, &mut Vec::new(), None);
+        graph
+    }
+
+    fn link(
+        &mut self,
+        block: &[AST],
+        ids: &HashMap<Vec<usize>, usize>,
+        scopes: &[Option<&str>],
+        path: &mut Vec<usize>,
+        follow: Option<usize>,
+    ) {
+        for (position, item) in block.iter().enumerate() {
+            path.push(position + 1);
+            let next = ids.get(path.as_slice()).copied().or(follow);
+            path.pop();
+
+            path.push(position);
+            let id = ids[path.as_slice()];
+
+            match item {
+                AST::Jump(_, target, _) => {
+                    let target = qualified_name(target, scopes[id]);
+                    if let Some(&to) = self.labels.get(&target) {
+                        self.add_edge(id, to, EdgeKind::Jump);
+                    }
+                }
+                AST::Return(..) => {}
+                AST::Label(_, _, body, _) => {
+                    path.push(0);
+                    let first = ids.get(path.as_slice()).copied();
+                    path.pop();
+
+                    if let Some(to) = first.or(next) {
+                        self.add_edge(id, to, EdgeKind::FallThrough);
+                    }
+                    self.link(body, ids, scopes, path, next);
+                }
+                AST::Init(_, body, _) => {
+                    // Init blocks run at startup, outside of the story flow
+                    if let Some(to) = next {
+                        self.add_edge(id, to, EdgeKind::FallThrough);
+                    }
+                    self.link(body, ids, scopes, path, None);
+                }
+                _ => {
+                    if let Some(to) = next {
+                        self.add_edge(id, to, EdgeKind::FallThrough);
+                    }
+                }
+            }
+
+            path.pop();
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, kind: EdgeKind) {
+        self.edges.push(Edge { from, to, kind });
+    }
+
+    /// Node id of the first label with the given qualified name
+    pub fn label_node(&self, name: &str) -> Option<usize> {
+        self.labels.get(name).copied()
+    }
+
+    pub fn successors(&self, id: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == id)
+    }
+
+    pub fn predecessors(&self, id: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == id)
+    }
+
+    /// Edges crossing from one label into another, e.g. jumps and falling into the next label
+    pub fn label_edges(&self) -> Vec<LabelEdge> {
+        self.edges
+            .iter()
+            .filter_map(|edge| {
+                let from = self.nodes[edge.from].label.clone()?;
+                let to = self.nodes[edge.to].label.clone()?;
+
+                // Only edges entering a label at its header
+                if self.label_node(&to) != Some(edge.to) {
+                    return None;
+                }
+
+                Some(LabelEdge {
+                    from,
+                    to,
+                    kind: edge.kind,
+                    line_number: self.nodes[edge.from].node.index,
+                })
+            })
+            .collect()
+    }
+
+    /// Ids of the nodes reachable from the named label
+    pub fn reachable(&self, entry: &str) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<usize> = self.label_node(entry).into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(self.successors(id).map(|edge| edge.to));
+            }
+        }
+
+        seen
+    }
+}
+
+/// Reports dead statements and labels unreachable from `start`
+pub fn unreachable(ast: &[AST]) -> Unreachable {
+    unreachable_from(ast, "start")
+}
+
+pub fn unreachable_from(ast: &[AST], entry: &str) -> Unreachable {
+    let graph = FlowGraph::new(ast);
+    let reachable = graph.reachable(entry);
+    let has_predecessor: HashSet<usize> = graph.edges.iter().map(|edge| edge.to).collect();
+
+    let mut rv = Unreachable::default();
+    let mut id = 0;
+    let mut init_depth: Option<usize> = None;
+
+    walk(ast, &mut |path, node| {
+        let current = id;
+        id += 1;
+
+        // Init blocks and defines run at startup rather than through the story flow
+        if init_depth.is_some_and(|depth| path.len() <= depth) {
+            init_depth = None;
+        }
+        if init_depth.is_some() {
+            return;
+        }
+
+        match node {
+            AST::Init(..) => init_depth = Some(path.len()),
+            AST::Define(..) => {}
+            AST::Label(..) => {
+                if !reachable.contains(&current) {
+                    rv.orphan_labels.push(graph.nodes[current].clone());
+                }
+            }
+            _ => {
+                if !has_predecessor.contains(&current) {
+                    rv.statements.push(graph.nodes[current].clone());
+                }
+            }
+        }
+    });
+
+    rv
+}
+
+/// Measures the branching of the story starting from `start`
+pub fn complexity(ast: &[AST]) -> Complexity {
+    complexity_from(ast, "start")
+}
+
+pub fn complexity_from(ast: &[AST], entry: &str) -> Complexity {
+    let graph = FlowGraph::new(ast);
+    let mut rv = Complexity::default();
+
+    let mut returns: HashSet<&str> = HashSet::new();
+    for (id, flow) in graph.nodes.iter().enumerate() {
+        let label = flow.label.as_deref();
+        if let Some(label) = label {
+            rv.labels.entry(label.to_string()).or_insert(1);
+        }
+
+        if graph.successors(id).count() > 1 {
+            rv.branches += 1;
+            if let Some(label) = label {
+                *rv.labels.entry(label.to_string()).or_insert(1) += 1;
+            }
+        }
+
+        let node = flow.node.resolve(ast);
+        if let (Some(label), Some(AST::Return(..))) = (label, node) {
+            returns.insert(label);
+        }
+    }
+
+    let mut next: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for edge in graph.label_edges() {
+        if edge.from != edge.to {
+            next.entry(edge.from).or_default().insert(edge.to);
+        }
+    }
+
+    rv.endings = rv
+        .labels
+        .keys()
+        .filter(|label| returns.contains(label.as_str()) && !next.contains_key(*label))
+        .cloned()
+        .collect();
+
+    if graph.label_node(entry).is_some() {
+        rv.max_depth = depth(entry, &next);
+    }
+    rv
+}
+
+/// Labels on the longest path from `entry`, with the labels of each loop
+/// counted once: loops are collapsed into single steps weighing as many
+/// labels as they have, so the paths left can't revisit a label
+fn depth(entry: &str, next: &BTreeMap<String, HashSet<String>>) -> usize {
+    let mut components = Components::default();
+    components.visit(entry, next);
+
+    // Components are found successors first, so every step between them
+    // leads to one already measured
+    let mut deepest = vec![0; components.members.len()];
+    for (component, members) in components.members.iter().enumerate() {
+        let following = members
+            .iter()
+            .flat_map(|label| next.get(*label).into_iter().flatten())
+            .map(|to| components.component[to.as_str()])
+            .filter(|&to| to != component)
+            .map(|to| deepest[to])
+            .max()
+            .unwrap_or(0);
+        deepest[component] = members.len() + following;
+    }
+
+    deepest[components.component[entry]]
+}
+
+/// Strongly connected components of the labels reachable from an entry,
+/// found by Tarjan's algorithm
+#[derive(Default)]
+struct Components<'a> {
+    index: HashMap<&'a str, usize>,
+    lowlink: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    /// Component of each label, numbered in the order they are completed
+    component: HashMap<&'a str, usize>,
+    members: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Components<'a> {
+    fn visit(&mut self, label: &'a str, next: &'a BTreeMap<String, HashSet<String>>) {
+        let index = self.index.len();
+        self.index.insert(label, index);
+        self.lowlink.insert(label, index);
+        self.stack.push(label);
+        self.on_stack.insert(label);
+
+        for to in next.get(label).into_iter().flatten() {
+            let to = to.as_str();
+            if !self.index.contains_key(to) {
+                self.visit(to, next);
+                let low = self.lowlink[label].min(self.lowlink[to]);
+                self.lowlink.insert(label, low);
+            } else if self.on_stack.contains(to) {
+                let low = self.lowlink[label].min(self.index[to]);
+                self.lowlink.insert(label, low);
+            }
+        }
+
+        if self.lowlink[label] == index {
+            let component = self.members.len();
+            let mut members = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                self.component.insert(member, component);
+                members.push(member);
+                if member == label {
+                    break;
+                }
+            }
+            self.members.push(members);
+        }
+    }
+}
+
+/// Steps the search for paths takes before giving up on finding more, far
+/// more than a script with a manageable number of paths needs
+pub const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+/// Distinct paths from `start` to a return, at most `limit` of them
+pub fn paths(ast: &[AST], limit: usize) -> Vec<StoryPath> {
+    paths_from(ast, "start", limit)
+}
+
+/// Distinct paths from the entry label, in the order the branches appear.
+/// Paths visit each statement at most once, so loops are left out.
+///
+/// Scripts with many branches have more paths than can be enumerated, so the
+/// search stops after [`DEFAULT_MAX_STEPS`] steps even with fewer than
+/// `limit` paths found.
+pub fn paths_from(ast: &[AST], entry: &str, limit: usize) -> Vec<StoryPath> {
+    paths_within(ast, entry, limit, DEFAULT_MAX_STEPS)
+}
+
+/// Like [`paths_from`], stopping after `max_steps` steps through the graph
+pub fn paths_within(ast: &[AST], entry: &str, limit: usize, max_steps: usize) -> Vec<StoryPath> {
+    let graph = FlowGraph::new(ast);
+    let (Some(start), true) = (graph.label_node(entry), limit > 0) else {
+        return Vec::new();
+    };
+
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); graph.nodes.len()];
+    for edge in &graph.edges {
+        successors[edge.from].push(edge.to);
+    }
+
+    let mut rv = Vec::new();
+    // Statements of the path so far, each with the next successor to try
+    let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+    let mut on_path: HashSet<usize> = HashSet::from([start]);
+
+    let mut steps = 0;
+    while let Some(&(id, tried)) = stack.last() {
+        steps += 1;
+        if steps > max_steps {
+            break;
+        }
+
+        if successors[id].is_empty() {
+            rv.push(story_path(&graph, &stack));
+            if rv.len() >= limit {
+                break;
+            }
+        }
+
+        let top = stack.len() - 1;
+        match successors[id].get(tried) {
+            Some(&to) => {
+                stack[top].1 += 1;
+                if on_path.insert(to) {
+                    stack.push((to, 0));
+                }
+            }
+            None => {
+                on_path.remove(&id);
+                stack.pop();
+            }
+        }
+    }
+
+    rv
+}
+
+fn story_path(graph: &FlowGraph, stack: &[(usize, usize)]) -> StoryPath {
+    let mut rv = StoryPath::default();
+    for &(id, _) in stack {
+        let flow = &graph.nodes[id];
+        if graph.label_node(flow.label.as_deref().unwrap_or_default()) == Some(id) {
+            rv.labels.extend(flow.label.clone());
+        }
+        rv.statements.push(flow.node.clone());
+    }
+    rv
+}
@@ -0,0 +1,151 @@
+use crate::options::ParseOptions;
+use crate::parse_script;
+use crate::parsers::{walk, AST, IMAGE_CLAUSES};
+use crate::tokens::{TokenKind, Tokens};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Role of a token in the statement it belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SemanticKind {
+    Keyword,
+    /// Character saying a line, or being defined as one
+    Speaker,
+    /// Text of a say statement
+    Dialogue,
+    /// Words of the image name in show, hide and scene statements
+    Image,
+    /// File played by a play statement
+    Audio,
+    /// Name of a label being defined or jumped to
+    Label,
+    Comment,
+    /// Any other string
+    String,
+    Number,
+    /// Any other name
+    Name,
+    Punct,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SemanticToken<'a> {
+    pub kind: SemanticKind,
+    /// Source text of the token
+    pub text: &'a str,
+    /// Byte range in the source
+    pub span: Range<usize>,
+}
+
+/// Classifies the tokens of a script by parsing it, so names and strings are
+/// told apart the way the parser reads them.
+///
+/// Statements that fail to parse, and whole scripts that can't be scanned,
+/// get the plain kinds of [`crate::tokens`].
+pub fn highlight(source: &str) -> Vec<SemanticToken<'_>> {
+    highlight_with_options(source, &ParseOptions::default())
+}
+
+/// Like [`highlight`], parsing with the options, such as to know custom statements
+pub fn highlight_with_options<'a>(
+    source: &'a str,
+    options: &ParseOptions,
+) -> Vec<SemanticToken<'a>> {
+    let options = ParseOptions {
+        recover: true,
+        ..options.clone()
+    };
+    let ast = parse_script(source, "<source>", &options)
+        .map(|script| script.ast)
+        .unwrap_or_default();
+
+    // Statements by the line they start on
+    let mut statements: HashMap<usize, &AST> = HashMap::new();
+    walk(&ast, &mut |_, node| {
+        statements.entry(node.index()).or_insert(node);
+    });
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    let mut statement: Option<Statement> = None;
+    let mut rv = Vec::new();
+    for token in Tokens::new(source) {
+        let line_number = line_starts.partition_point(|&start| start <= token.span.start);
+        if let Some(node) = statements.remove(&line_number) {
+            statement = Some(Statement {
+                node,
+                position: 0,
+                after_clause: false,
+            });
+        }
+
+        let kind = match (&mut statement, token.kind) {
+            (_, TokenKind::Comment) => SemanticKind::Comment,
+            (Some(statement), kind) => statement.classify(kind, token.text),
+            (None, kind) => plain(kind),
+        };
+
+        rv.push(SemanticToken {
+            kind,
+            text: token.text,
+            span: token.span,
+        });
+    }
+
+    rv
+}
+
+/// Tokens of a statement being classified, which continue on the lines below
+/// its first one until the next statement starts
+struct Statement<'a> {
+    node: &'a AST,
+    /// Number of tokens seen so far
+    position: usize,
+    /// Whether an image clause was seen, after which nothing is part of the image name
+    after_clause: bool,
+}
+
+impl Statement<'_> {
+    fn classify(&mut self, kind: TokenKind, text: &str) -> SemanticKind {
+        let position = self.position;
+        self.position += 1;
+
+        let name = kind == TokenKind::Name || kind == TokenKind::Keyword;
+        match self.node {
+            AST::Say(_, Some(_), ..) if position == 0 => SemanticKind::Speaker,
+            AST::Say(..) if kind == TokenKind::String => SemanticKind::Dialogue,
+            AST::Define(..) if position == 1 && self.node.defined_character().is_some() => {
+                SemanticKind::Speaker
+            }
+            AST::LLMGenerate(..) if position == 1 => SemanticKind::Speaker,
+            AST::Play(..) if kind == TokenKind::String => SemanticKind::Audio,
+            AST::Label(..) if position == 1 => SemanticKind::Label,
+            AST::Jump(_, _, false) if position > 0 && (name || text == ".") => SemanticKind::Label,
+            AST::Show(..) | AST::Hide(..) | AST::Scene(..) if position > 0 => {
+                if IMAGE_CLAUSES.contains(&text) {
+                    self.after_clause = true;
+                    SemanticKind::Keyword
+                } else if !self.after_clause && (name || kind == TokenKind::Number) {
+                    SemanticKind::Image
+                } else {
+                    plain(kind)
+                }
+            }
+            AST::Custom(..) if position == 0 => SemanticKind::Keyword,
+            _ => plain(kind),
+        }
+    }
+}
+
+fn plain(kind: TokenKind) -> SemanticKind {
+    match kind {
+        TokenKind::Keyword => SemanticKind::Keyword,
+        TokenKind::Name => SemanticKind::Name,
+        TokenKind::String => SemanticKind::String,
+        TokenKind::Number => SemanticKind::Number,
+        TokenKind::Punct => SemanticKind::Punct,
+        TokenKind::Comment => SemanticKind::Comment,
+    }
+}
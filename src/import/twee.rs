@@ -0,0 +1,212 @@
+use crate::lexer::compiled;
+use crate::parsers::{Quoting, AST};
+use std::collections::{HashMap, HashSet};
+
+/// Passages that aren't part of the story text
+const SPECIAL_TAGS: [&str; 2] = ["script", "stylesheet"];
+
+struct Passage {
+    name: String,
+    line_number: usize,
+    lines: Vec<(usize, String)>,
+}
+
+enum Section {
+    /// Text before the first passage, or in script and style passages
+    Skipped,
+    StoryData,
+    Passage,
+}
+
+/// Converts Twee 3 source into labels, one per passage.
+///
+/// Text lines become narration, keeping the text of inline links, and links
+/// become jumps. The AST has no menus yet, so a passage offering several links
+/// jumps to the first one; passages without links return. The starting passage, taken from StoryData or named
+/// `Start`, becomes the `start` label; other passage names are turned into
+/// identifiers. Node indices are line numbers in the Twee source.
+pub fn from_twee(source: &str) -> Vec<AST> {
+    let patterns = (
+        compiled(r"^::\s*((?:\\.|[^\\\[{])*?)\s*(\[[^\]]*\])?\s*(\{.*\})?\s*$"),
+        compiled(r#""start"\s*:\s*"((?:\\.|[^"\\])*)""#),
+        compiled(r"\[\[(.*?)\]\]"),
+    );
+    let (Ok(header), Ok(start_field), Ok(link)) = patterns else {
+        return Vec::new();
+    };
+
+    let mut passages: Vec<Passage> = Vec::new();
+    let mut start = None;
+    let mut section = Section::Skipped;
+
+    for (i, line) in source.lines().enumerate() {
+        if let Some(captures) = header.captures(line) {
+            let name = unescape(&captures[1]);
+            let special = captures.get(2).is_some_and(|tags| {
+                tags.as_str()
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split_whitespace()
+                    .any(|tag| SPECIAL_TAGS.contains(&tag))
+            });
+
+            section = match name.as_str() {
+                "StoryData" => Section::StoryData,
+                "StoryTitle" => Section::Skipped,
+                _ if special => Section::Skipped,
+                _ => {
+                    passages.push(Passage {
+                        name,
+                        line_number: i + 1,
+                        lines: Vec::new(),
+                    });
+                    Section::Passage
+                }
+            };
+            continue;
+        }
+
+        match section {
+            Section::Skipped => {}
+            Section::StoryData => {
+                if let Some(captures) = start_field.captures(line) {
+                    start = Some(unescape(&captures[1]));
+                }
+            }
+            Section::Passage => {
+                if let Some(passage) = passages.last_mut() {
+                    passage.lines.push((i + 1, line.to_string()));
+                }
+            }
+        }
+    }
+
+    let start = start.unwrap_or_else(|| "Start".to_string());
+    let labels = label_names(&passages, &start);
+
+    passages
+        .iter()
+        .map(|passage| {
+            let mut body = Vec::new();
+            let mut targets = Vec::new();
+
+            for (line_number, line) in &passage.lines {
+                let text = link.replace_all(line, |captures: &regex::Captures| {
+                    let (text, target) = split_link(&captures[1]);
+                    targets.push((*line_number, target.to_string()));
+                    text.to_string()
+                });
+                let text = text.trim();
+
+                // Lines holding nothing but links are choices rather than text
+                if !text.is_empty() && !link.replace_all(line, "").trim().is_empty() {
+                    body.push(AST::Say(
+                        *line_number,
+                        None,
+                        text.to_string(),
+                        Quoting::default(),
+                    ));
+                }
+            }
+
+            match targets.first() {
+                Some((line_number, target)) => {
+                    let target = labels
+                        .get(target)
+                        .cloned()
+                        .unwrap_or_else(|| identifier(target));
+                    body.push(AST::Jump(*line_number, target, false));
+                }
+                None => {
+                    let line_number = passage
+                        .lines
+                        .iter()
+                        .rev()
+                        .find(|(_, line)| !line.trim().is_empty())
+                        .map_or(passage.line_number, |(line_number, _)| *line_number);
+                    body.push(AST::Return(line_number, None));
+                }
+            }
+
+            AST::Label(
+                passage.line_number,
+                labels[&passage.name].clone(),
+                body,
+                None,
+            )
+        })
+        .collect()
+}
+
+/// Maps passage names to unique label names, reserving `start` for the starting passage
+fn label_names(passages: &[Passage], start: &str) -> HashMap<String, String> {
+    let mut labels = HashMap::new();
+    let mut taken = HashSet::new();
+
+    if passages.iter().any(|passage| passage.name == start) {
+        labels.insert(start.to_string(), "start".to_string());
+        taken.insert("start".to_string());
+    }
+
+    for passage in passages {
+        if labels.contains_key(&passage.name) {
+            continue;
+        }
+
+        let base = identifier(&passage.name);
+        let mut name = base.clone();
+        let mut n = 1;
+        while taken.contains(&name) {
+            n += 1;
+            name = format!("{}_{}", base, n);
+        }
+
+        taken.insert(name.clone());
+        labels.insert(passage.name.clone(), name);
+    }
+
+    labels
+}
+
+/// Lowercased name with runs of other characters replaced by underscores
+fn identifier(name: &str) -> String {
+    let mut rv = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            rv.extend(c.to_lowercase());
+        } else if !rv.is_empty() && !rv.ends_with('_') {
+            rv.push('_');
+        }
+    }
+
+    let rv = rv.trim_end_matches('_');
+    match rv.chars().next() {
+        None => "passage".to_string(),
+        Some(c) if c.is_numeric() => format!("passage_{}", rv),
+        Some(_) => rv.to_string(),
+    }
+}
+
+/// Splits `text->target`, `target<-text`, `text|target` or `target` into text and target
+fn split_link(link: &str) -> (&str, &str) {
+    if let Some((text, target)) = link.rsplit_once("->") {
+        (text, target)
+    } else if let Some((target, text)) = link.split_once("<-") {
+        (text, target)
+    } else if let Some((text, target)) = link.rsplit_once('|') {
+        (text, target)
+    } else {
+        (link, link)
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => rv.extend(chars.next()),
+            _ => rv.push(c),
+        }
+    }
+    rv
+}
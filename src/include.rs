@@ -0,0 +1,181 @@
+use crate::diagnostics::Code;
+use crate::parsers::AST;
+use std::collections::BTreeMap;
+
+/// Files the statements of a script come from once its include statements
+/// are replaced by the statements of the scripts they name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Origins<K> {
+    /// File of each included statement, by its path in the script; the
+    /// statements of its block come from the same file unless listed too
+    files: BTreeMap<Vec<usize>, K>,
+}
+
+impl<K> Default for Origins<K> {
+    fn default() -> Self {
+        Origins {
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K> Origins<K> {
+    /// File of the statement at the path, `None` when it's from the script itself
+    pub fn get(&self, path: &[usize]) -> Option<&K> {
+        (1..=path.len())
+            .rev()
+            .find_map(|len| self.files.get(&path[..len]))
+    }
+
+    /// Paths of the first statements of each run of included statements,
+    /// with the file they come from
+    pub fn iter(&self) -> impl Iterator<Item = (&[usize], &K)> {
+        self.files
+            .iter()
+            .map(|(path, file)| (path.as_slice(), file))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub(crate) fn map<L>(self, mut f: impl FnMut(K) -> L) -> Origins<L> {
+        Origins {
+            files: self
+                .files
+                .into_iter()
+                .map(|(path, file)| (path, f(file)))
+                .collect(),
+        }
+    }
+}
+
+/// Include statement that was left in place
+#[derive(Clone, Debug)]
+pub(crate) struct IncludeProblem<K> {
+    /// Script with the include statement
+    pub file: K,
+    pub line_number: usize,
+    pub code: Code,
+    pub message: String,
+}
+
+/// A script with the statements of the scripts it includes in place of its
+/// include statements
+#[derive(Clone, Debug)]
+pub(crate) struct Expanded<K> {
+    pub ast: Vec<AST>,
+    pub origins: Origins<K>,
+    /// Scripts whose statements were included, directly or not
+    pub included: Vec<K>,
+    pub problems: Vec<IncludeProblem<K>>,
+}
+
+/// Finds the script an include statement names, given the script it's in:
+/// the script's key and statements, or the code and message of the problem
+pub(crate) type Lookup<'a, K> = dyn FnMut(&K, &str) -> Result<(K, Vec<AST>), (Code, String)> + 'a;
+
+/// Replaces include statements by the statements of the scripts they name,
+/// which keep their own line numbers. Includes that can't be looked up, or
+/// that would include a script into itself, are kept and reported.
+pub(crate) fn expand<K: Clone + PartialEq>(
+    root: K,
+    ast: Vec<AST>,
+    lookup: &mut Lookup<K>,
+) -> Expanded<K> {
+    let mut expansion = Expansion {
+        lookup,
+        stack: vec![root.clone()],
+        origins: Origins::default(),
+        included: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    let mut rv = Vec::with_capacity(ast.len());
+    expansion.block(&root, &root, ast, &mut Vec::new(), &mut rv);
+
+    Expanded {
+        ast: rv,
+        origins: expansion.origins,
+        included: expansion.included,
+        problems: expansion.problems,
+    }
+}
+
+struct Expansion<'a, 'l, K> {
+    lookup: &'a mut Lookup<'l, K>,
+    /// Scripts being expanded, to catch cycles
+    stack: Vec<K>,
+    origins: Origins<K>,
+    included: Vec<K>,
+    problems: Vec<IncludeProblem<K>>,
+}
+
+impl<K: Clone + PartialEq> Expansion<'_, '_, K> {
+    /// Appends the statements of the block from `file` to `out`, which is the
+    /// block at `path` holding statements from `parent`
+    fn block(
+        &mut self,
+        file: &K,
+        parent: &K,
+        block: Vec<AST>,
+        path: &mut Vec<usize>,
+        out: &mut Vec<AST>,
+    ) {
+        for node in block {
+            let AST::Include(line_number, target) = &node else {
+                self.push(file, parent, node, path, out);
+                continue;
+            };
+
+            let problem = match (self.lookup)(file, target) {
+                Ok((key, _)) if self.stack.contains(&key) => Some((
+                    Code::IncludeCycle,
+                    format!("include of '{}' forms a cycle", target),
+                )),
+                Ok((key, ast)) => {
+                    self.included.push(key.clone());
+                    self.stack.push(key.clone());
+                    self.block(&key, parent, ast, path, out);
+                    self.stack.pop();
+                    None
+                }
+                Err(problem) => Some(problem),
+            };
+
+            if let Some((code, message)) = problem {
+                self.problems.push(IncludeProblem {
+                    file: file.clone(),
+                    line_number: *line_number,
+                    code,
+                    message,
+                });
+                self.push(file, parent, node, path, out);
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        file: &K,
+        parent: &K,
+        mut node: AST,
+        path: &mut Vec<usize>,
+        out: &mut Vec<AST>,
+    ) {
+        path.push(out.len());
+        if file != parent {
+            self.origins.files.insert(path.clone(), file.clone());
+        }
+        if let Some(block) = node.block_mut() {
+            let nested = std::mem::take(block);
+            let mut expanded = Vec::with_capacity(nested.len());
+            self.block(file, file, nested, path, &mut expanded);
+            if let Some(block) = node.block_mut() {
+                *block = expanded;
+            }
+        }
+        path.pop();
+        out.push(node);
+    }
+}
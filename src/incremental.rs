@@ -0,0 +1,232 @@
+use crate::lexer::Lexer;
+use crate::options::ParseOptions;
+use crate::parsers::{parse_block, shift_indices, ParseError, AST};
+use crate::{group_logical_lines_with_options, scan_script};
+use anyhow::{bail, Result};
+use std::ops::Range;
+
+/// Replacement of a byte range of the source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// A top-level statement along with the source lines it owns, up to the next one
+#[derive(Clone, Debug)]
+struct Region {
+    bytes: Range<usize>,
+    first_line: usize,
+    ast: Vec<AST>,
+    errors: Vec<ParseError>,
+}
+
+/// A parsed script that is kept up to date with edits, re-parsing only the
+/// top-level statements an edit touches.
+///
+/// Statements after an edit are reused with their line numbers shifted.
+/// When an edit changes how the rest of the script is read, like an opened
+/// string, the whole script is parsed again.
+#[derive(Clone, Debug)]
+pub struct IncrementalParse {
+    source: String,
+    filename: String,
+    options: ParseOptions,
+    regions: Vec<Region>,
+    /// Why the script couldn't be divided into statements at all
+    failure: Option<String>,
+}
+
+impl IncrementalParse {
+    pub fn new(source: &str, filename: &str, options: &ParseOptions) -> Self {
+        let mut rv = IncrementalParse {
+            source: source.to_string(),
+            filename: filename.to_string(),
+            options: options.clone(),
+            regions: Vec::new(),
+            failure: None,
+        };
+        rv.parse_all();
+        rv
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn statements(&self) -> impl Iterator<Item = &AST> {
+        self.regions.iter().flat_map(|region| &region.ast)
+    }
+
+    pub fn ast(&self) -> Vec<AST> {
+        self.statements().cloned().collect()
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        self.failure
+            .iter()
+            .cloned()
+            .chain(
+                self.regions
+                    .iter()
+                    .flat_map(|region| region.errors.iter().map(ToString::to_string)),
+            )
+            .collect()
+    }
+
+    /// Applies the edit to the source and brings the AST up to date
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<()> {
+        let range = edit.range.clone();
+        if range.start > range.end
+            || range.end > self.source.len()
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            bail!("edit range {:?} is outside of the source", range);
+        }
+
+        let old_source = std::mem::take(&mut self.source);
+        self.source = format!(
+            "{}{}{}",
+            &old_source[..range.start],
+            edit.replacement,
+            &old_source[range.end..]
+        );
+
+        // Regions the edit touches, including the ones it borders since it
+        // may indent their first line or join them
+        let first = self
+            .regions
+            .iter()
+            .position(|region| region.bytes.end >= range.start);
+        let last = self
+            .regions
+            .iter()
+            .rposition(|region| region.bytes.start <= range.end);
+        let (Some(first), Some(last)) = (first, last) else {
+            self.parse_all();
+            return Ok(());
+        };
+
+        let byte_delta = edit.replacement.len() as isize - range.len() as isize;
+        let line_delta =
+            newlines(&edit.replacement) as isize - newlines(&old_source[range]) as isize;
+
+        let start = self.regions[first].bytes.start;
+        let end = self.regions[last]
+            .bytes
+            .end
+            .saturating_add_signed(byte_delta);
+        let first_line = self.regions[first].first_line;
+
+        let reparsed = match self.parse_chunk(start..end, first_line) {
+            Ok(regions) if !regions.is_empty() => regions,
+            _ => {
+                self.parse_all();
+                return Ok(());
+            }
+        };
+
+        let mut following = self.regions.split_off(last + 1);
+        self.regions.truncate(first);
+        self.regions.extend(reparsed);
+
+        for mut region in following.drain(..) {
+            region.bytes = region.bytes.start.saturating_add_signed(byte_delta)
+                ..region.bytes.end.saturating_add_signed(byte_delta);
+            region.first_line = region.first_line.saturating_add_signed(line_delta);
+
+            // Error messages mention line numbers, so they are produced again
+            if !region.errors.is_empty() {
+                if let Ok(regions) = self.parse_chunk(region.bytes.clone(), region.first_line) {
+                    self.regions.extend(regions);
+                    continue;
+                }
+            }
+
+            shift_indices(&mut region.ast, 0, line_delta);
+            self.regions.push(region);
+        }
+
+        Ok(())
+    }
+
+    fn parse_all(&mut self) {
+        match self.parse_chunk(0..self.source.len(), 1) {
+            Ok(regions) => {
+                self.regions = regions;
+                self.failure = None;
+            }
+            Err(err) => {
+                self.regions = Vec::new();
+                self.failure = Some(err.to_string());
+            }
+        }
+    }
+
+    /// Parses part of the source starting at a top-level statement into regions
+    fn parse_chunk(&self, bytes: Range<usize>, first_line: usize) -> Result<Vec<Region>> {
+        let chunk = &self.source[bytes.clone()];
+        // A byte order mark is only skipped at the very start of the source
+        if bytes.start > 0 && chunk.starts_with('\u{feff}') {
+            bail!("byte order mark in the middle of the source");
+        }
+
+        let mut lines = scan_script(chunk, &self.filename, &self.options)?.lines;
+        for line in &mut lines {
+            line.line_number += first_line - 1;
+            line.span = line.span.start + bytes.start..line.span.end + bytes.start;
+        }
+        let blocks = group_logical_lines_with_options(lines, &self.options)?;
+
+        // The indentation of top-level statements has to match the rest of the script
+        let partial = bytes != (0..self.source.len());
+        if partial && blocks.iter().any(|block| block.indent != 0) {
+            bail!("indented top-level statement");
+        }
+
+        let starts: Vec<usize> = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| match i {
+                0 => bytes.start,
+                _ => line_start(&self.source, block.span.start),
+            })
+            .collect();
+
+        let regions = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let end = starts.get(i + 1).copied().unwrap_or(bytes.end);
+                let mut lexer =
+                    Lexer::borrowed(std::slice::from_ref(block), true).with_options(&self.options);
+                let (ast, mut errors) = parse_block(&mut lexer);
+                // Errors recovered from inside of labels and init blocks
+                errors.extend(lexer.diagnostics().errors().cloned());
+
+                Region {
+                    bytes: starts[i]..end,
+                    first_line: if i == 0 {
+                        first_line
+                    } else {
+                        block.line_number
+                    },
+                    ast,
+                    errors,
+                }
+            })
+            .collect();
+
+        Ok(regions)
+    }
+}
+
+/// Start of the physical line holding the byte offset
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn newlines(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
@@ -0,0 +1,183 @@
+use crate::lexer::Lexer;
+use crate::parsers::{block, convert, from_ast, statements, Owned, Quoting, Stmt, Tree, AST};
+use crate::{group_logical_lines, parse_logical_lines};
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// AST whose speaker and image names are shared between nodes, see [`Interner`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum InternedAST {
+    Define(usize, String),
+    Hide(usize, Arc<str>, Option<String>),
+    Init(usize, Vec<InternedAST>, i32),
+    Jump(usize, String, bool),
+    Label(usize, String, Vec<InternedAST>, Option<String>),
+    Play(usize, String, String),
+    Return(usize, Option<String>),
+    Say(usize, Option<Arc<str>>, String, Quoting),
+    Scene(usize, Option<Arc<str>>, String, Option<String>),
+    Show(usize, Arc<str>, Option<String>),
+    Stop(usize, String, Option<String>, Option<f32>),
+    GameMechanic(usize, String),
+    LLMGenerate(usize, Arc<str>, Option<String>),
+    Include(usize, String),
+    Custom(usize, String, String),
+    Error {
+        loc: usize,
+        message: String,
+        raw_text: String,
+    },
+}
+
+impl InternedAST {
+    pub fn index(&self) -> usize {
+        match self {
+            InternedAST::Define(i, ..)
+            | InternedAST::Hide(i, ..)
+            | InternedAST::Init(i, ..)
+            | InternedAST::Jump(i, ..)
+            | InternedAST::Label(i, ..)
+            | InternedAST::Play(i, ..)
+            | InternedAST::Return(i, ..)
+            | InternedAST::Say(i, ..)
+            | InternedAST::Scene(i, ..)
+            | InternedAST::Show(i, ..)
+            | InternedAST::Stop(i, ..)
+            | InternedAST::GameMechanic(i, ..)
+            | InternedAST::LLMGenerate(i, ..)
+            | InternedAST::Include(i, ..)
+            | InternedAST::Custom(i, ..)
+            | InternedAST::Error { loc: i, .. } => *i,
+        }
+    }
+
+    /// Statements nested under this node, for labels and init blocks
+    pub fn block(&self) -> Option<&[InternedAST]> {
+        match self {
+            InternedAST::Label(_, _, block, _) | InternedAST::Init(_, block, _) => Some(block),
+            _ => None,
+        }
+    }
+
+    /// Copies the node with owned names
+    pub fn to_owned_ast(&self) -> AST {
+        AST::from(self.clone())
+    }
+}
+
+impl From<InternedAST> for AST {
+    fn from(node: InternedAST) -> Self {
+        let c = &mut Owned;
+        statements!(convert!(node, InternedAST => AST, c))
+    }
+}
+
+/// Pool of names, handing out one shared allocation per distinct name
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    names: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(name) = self.names.get(name) {
+            return name.clone();
+        }
+
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(name.clone());
+        name
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl<'src> Tree<'src> for Interner {
+    type Node = InternedAST;
+    type Block = Vec<InternedAST>;
+
+    fn block(&mut self) -> Self::Block {
+        Vec::new()
+    }
+
+    fn push(&mut self, block: &mut Self::Block, node: Self::Node) {
+        block.push(node);
+    }
+
+    fn node(&mut self, stmt: Stmt<'src, Self::Block>) -> Self::Node {
+        let c = self;
+        statements!(convert!(stmt, Stmt => InternedAST, c))
+    }
+}
+
+/// Fields of an interned node out of the ones of a [`Stmt`], with names taken
+/// from the pool, see [`convert`]
+impl Interner {
+    fn text(&mut self, text: Cow<str>) -> String {
+        text.into_owned()
+    }
+
+    fn opt_text(&mut self, text: Option<Cow<str>>) -> Option<String> {
+        text.map(Cow::into_owned)
+    }
+
+    fn name(&mut self, name: Cow<str>) -> Arc<str> {
+        self.intern(&name)
+    }
+
+    fn opt_name(&mut self, name: Option<Cow<str>>) -> Option<Arc<str>> {
+        name.map(|name| self.intern(&name))
+    }
+
+    fn message(&mut self, message: String) -> String {
+        message
+    }
+
+    fn copy<T>(&mut self, value: T) -> T {
+        value
+    }
+
+    fn nodes(&mut self, nodes: Vec<InternedAST>) -> Vec<InternedAST> {
+        nodes
+    }
+}
+
+/// Parses a script with its speaker and image names taken from the interner
+/// as they're parsed.
+///
+/// Reusing one interner across files shares names between their ASTs as well.
+pub fn parse_interned(
+    content: &str,
+    filename: &str,
+    interner: &mut Interner,
+) -> Result<(Vec<InternedAST>, Vec<String>)> {
+    let lines = parse_logical_lines(content, filename)?;
+    let blocks = group_logical_lines(lines)?;
+    let (ast, errors) = block(&mut Lexer::borrowed(&blocks, true), interner);
+    Ok((ast, errors.iter().map(ToString::to_string).collect()))
+}
+
+/// Replaces speaker and image names of an already parsed AST with shared ones
+/// from the interner
+pub fn intern(ast: Vec<AST>, interner: &mut Interner) -> Vec<InternedAST> {
+    ast.into_iter()
+        .map(|node| from_ast(interner, node))
+        .collect()
+}
+
+/// Converts an interned AST back to one with owned names
+pub fn unintern(ast: Vec<InternedAST>) -> Vec<AST> {
+    ast.iter().map(InternedAST::to_owned_ast).collect()
+}
@@ -0,0 +1,430 @@
+use crate::parsers::{node_at, walk, walk_labeled, NodeRef, AST};
+use crate::writer::statement;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// What running a statement shows or plays, for the game to present
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    Say {
+        /// Character saying the line, `None` for the narrator
+        who: Option<&'a str>,
+        what: &'a str,
+        /// Whether the line was shown before, see [`Seen`]
+        seen: bool,
+    },
+    Scene {
+        image: Option<&'a str>,
+        layer: &'a str,
+    },
+    Show {
+        image: &'a str,
+    },
+    Hide {
+        image: &'a str,
+    },
+    Play {
+        channel: &'a str,
+        filename: &'a str,
+    },
+    Stop {
+        channel: &'a str,
+        fadeout: Option<f32>,
+    },
+    /// The story continues at another label
+    JumpTaken {
+        from: Option<&'a str>,
+        to: &'a str,
+    },
+    GameMechanic(&'a str),
+    LlmGenerate {
+        who: &'a str,
+        prompt: Option<&'a str>,
+    },
+    /// Statement of a [`StatementParser`](crate::statements::StatementParser)
+    Custom {
+        keyword: &'a str,
+        rest: &'a str,
+    },
+    /// Return from a label entered by [`VM::call`]
+    Return,
+    /// The story is over: it returned from its entry label, or ran off the end of the script
+    End,
+}
+
+/// Decisions the script leaves to the game
+pub trait Resolver {
+    /// Label a `jump expression` goes to, given the source of the expression
+    fn jump_expression(&mut self, expression: &str) -> Option<String>;
+}
+
+/// Resolves nothing, so `jump expression` fails
+impl Resolver for () {
+    fn jump_expression(&mut self, _expression: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Identity of a line of dialogue that survives edits elsewhere in the
+/// script: a hash of its label, speaker and text, and how many times the
+/// same line came before it in the label
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatementId(pub u64);
+
+/// Ids of every say statement, in document order
+pub fn statement_ids(ast: &[AST]) -> Vec<(NodeRef, StatementId)> {
+    let mut repeats: HashMap<(Option<&str>, Option<&str>, &str), u64> = HashMap::new();
+    let mut rv = Vec::new();
+
+    walk_labeled(ast, &mut |path, node, label| {
+        if let AST::Say(_, who, what, _) = node {
+            let repeat = repeats.entry((label, who.as_deref(), what)).or_default();
+            let mut hash = Fnv::default();
+            hash.write(label.unwrap_or_default());
+            hash.write(who.as_deref().unwrap_or_default());
+            hash.write(what);
+            hash.write(&repeat.to_string());
+            *repeat += 1;
+            rv.push((NodeRef::new(path, node), StatementId(hash.0)));
+        }
+    });
+
+    rv
+}
+
+/// 64-bit FNV-1a, which unlike the std hashers is the same across builds
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    /// Hashes the text followed by a byte UTF-8 never has, to separate fields
+    fn write(&mut self, text: &str) {
+        for &byte in text.as_bytes().iter().chain(&[0xff]) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Lines of dialogue shown so far, kept across playthroughs like Ren'Py's
+/// persistent data
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Seen(pub BTreeSet<StatementId>);
+
+impl Seen {
+    pub fn contains(&self, id: StatementId) -> bool {
+        self.0.contains(&id)
+    }
+
+    /// Marks the line seen, returning whether it was seen before
+    pub fn mark_seen(&mut self, id: StatementId) -> bool {
+        !self.0.insert(id)
+    }
+}
+
+/// Where a story is, apart from the script it runs, for games to save and load
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    /// Innermost label of the position, to restart at should the script
+    /// change so the position no longer exists
+    pub label: Option<String>,
+    /// Path of the next statement to run, as in [`NodeRef`](crate::parsers::NodeRef),
+    /// or `None` once the story ended
+    pub position: Option<Vec<usize>>,
+    /// Source text of the statement at `position`, to tell whether the
+    /// script changed under it, see [`crate::writer::statement`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub statement: Option<String>,
+    /// Positions to return to, innermost last
+    pub call_stack: Vec<Option<Vec<usize>>>,
+}
+
+/// Runs a parsed script one event at a time
+pub struct VM<'a> {
+    ast: &'a [AST],
+    /// Paths of the labels, the first definition of a name winning
+    labels: HashMap<&'a str, Vec<usize>>,
+    /// Ids of the say statements by path
+    ids: HashMap<Vec<usize>, StatementId>,
+    resolver: Box<dyn Resolver + 'a>,
+    state: State,
+    seen: Seen,
+    /// Whether steps pass over lines already seen
+    skipping: bool,
+}
+
+impl<'a> VM<'a> {
+    /// A machine that hasn't started; see [`VM::start`]
+    pub fn new(ast: &'a [AST]) -> Self {
+        let mut labels = HashMap::new();
+        walk(ast, &mut |path, node| {
+            if let AST::Label(_, name, _, _) = node {
+                labels.entry(name.as_str()).or_insert_with(|| path.to_vec());
+            }
+        });
+
+        let ids = statement_ids(ast)
+            .into_iter()
+            .map(|(node, id)| (node.path, id))
+            .collect();
+
+        VM {
+            ast,
+            labels,
+            ids,
+            resolver: Box::new(()),
+            state: State::default(),
+            seen: Seen::default(),
+            skipping: false,
+        }
+    }
+
+    /// Continues with the lines seen in earlier playthroughs
+    pub fn with_seen(mut self, seen: Seen) -> Self {
+        self.seen = seen;
+        self
+    }
+
+    pub fn seen(&self) -> &Seen {
+        &self.seen
+    }
+
+    /// Whether the line at the path was shown before
+    pub fn is_seen(&self, path: &[usize]) -> bool {
+        self.ids.get(path).is_some_and(|&id| self.seen.contains(id))
+    }
+
+    /// Makes steps pass over lines already seen, like Ren'Py's skip mode.
+    /// Other events are still returned, so the game can keep up the scene.
+    pub fn set_skipping(&mut self, skipping: bool) {
+        self.skipping = skipping;
+    }
+
+    pub fn is_skipping(&self) -> bool {
+        self.skipping
+    }
+
+    pub fn with_resolver(mut self, resolver: impl Resolver + 'a) -> Self {
+        self.resolver = Box::new(resolver);
+        self
+    }
+
+    /// Starts the story at the label, failing if it isn't defined
+    pub fn start(mut self, label: &str) -> Result<Self> {
+        self.jump(label)?;
+        Ok(self)
+    }
+
+    /// Continues the story at the label
+    pub fn jump(&mut self, label: &str) -> Result<()> {
+        let (_, path) = self.label_path(label)?;
+        self.goto(Some(path));
+        Ok(())
+    }
+
+    /// Continues the story at the label, coming back to the current position
+    /// when it returns
+    pub fn call(&mut self, label: &str) -> Result<()> {
+        let (_, path) = self.label_path(label)?;
+        let back = self.state.position.clone();
+        self.goto(Some(path));
+        self.state.call_stack.push(back);
+        Ok(())
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Continues a saved story. A position the script no longer has, or that
+    /// holds another statement since lines were added or removed before it,
+    /// restarts its label, failing if the label is gone too.
+    pub fn restore(&mut self, mut state: State) -> Result<()> {
+        let exists = |position: &Option<Vec<usize>>| {
+            position
+                .as_deref()
+                .is_none_or(|path| node_at(self.ast, path).is_some())
+        };
+
+        // Saves without the statement text can only be checked for the position
+        let unchanged = match (&state.position, &state.statement) {
+            (Some(path), Some(text)) => self.statement(path).as_ref() == Some(text),
+            (position, _) => exists(position),
+        };
+        if !unchanged {
+            let label = state.label.as_deref().unwrap_or_default();
+            let path = self.label_path(label)?.1;
+            state.statement = self.statement(&path);
+            state.position = Some(path);
+        }
+        if !state.call_stack.iter().all(exists) {
+            return Err(anyhow!("saved call stack doesn't match the script"));
+        }
+
+        self.state = state;
+        Ok(())
+    }
+
+    /// Innermost label containing the next statement
+    pub fn label(&self) -> Option<&'a str> {
+        let mut block = self.ast;
+        let mut rv = None;
+        for &index in self.state.position.as_deref().unwrap_or_default() {
+            let node = block.get(index)?;
+            if let AST::Label(_, name, _, _) = node {
+                rv = Some(name.as_str());
+            }
+            block = node.block().map_or(&[], Vec::as_slice);
+        }
+        rv
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.state.position.is_none()
+    }
+
+    fn goto(&mut self, position: Option<Vec<usize>>) {
+        self.state.statement = position.as_deref().and_then(|path| self.statement(path));
+        self.state.position = position;
+        self.state.label = self.label().map(str::to_string);
+    }
+
+    /// Source text of the statement at the path
+    fn statement(&self, path: &[usize]) -> Option<String> {
+        node_at(self.ast, path).and_then(statement)
+    }
+
+    /// Runs statements up to the next one the game has to present. Statements
+    /// that failed to parse are errors, and are skipped by the next step.
+    pub fn step(&mut self) -> Result<Event<'a>> {
+        loop {
+            let Some(path) = self.state.position.clone() else {
+                return Ok(Event::End);
+            };
+            let Some(node) = node_at(self.ast, &path) else {
+                self.goto(None);
+                return Ok(Event::End);
+            };
+
+            let event = match node {
+                AST::Label(_, _, body, _) if !body.is_empty() => {
+                    let mut inside = path;
+                    inside.push(0);
+                    self.goto(Some(inside));
+                    continue;
+                }
+                AST::Label(..) | AST::Define(..) | AST::Init(..) | AST::Include(..) => {
+                    let next = self.next(path);
+                    self.goto(next);
+                    continue;
+                }
+                AST::Jump(_, target, expression) => {
+                    let target = match expression {
+                        false => target.clone(),
+                        true => self.resolver.jump_expression(target).ok_or_else(|| {
+                            anyhow!(
+                                "line {}: can't resolve jump expression {}",
+                                node.index(),
+                                target
+                            )
+                        })?,
+                    };
+                    let from = self.label();
+                    let (to, path) = self.label_path(&target)?;
+                    self.goto(Some(path));
+                    return Ok(Event::JumpTaken { from, to });
+                }
+                AST::Return(..) => {
+                    return Ok(match self.state.call_stack.pop() {
+                        Some(back) => {
+                            self.goto(back);
+                            Event::Return
+                        }
+                        None => {
+                            self.goto(None);
+                            Event::End
+                        }
+                    });
+                }
+                AST::Error { loc, message, .. } => {
+                    let next = self.next(path);
+                    self.goto(next);
+                    return Err(anyhow!("line {}: {}", loc, message));
+                }
+                AST::Say(_, who, what, _) => {
+                    let seen = match self.ids.get(&path) {
+                        Some(&id) => self.seen.mark_seen(id),
+                        None => false,
+                    };
+                    if seen && self.skipping {
+                        let next = self.next(path);
+                        self.goto(next);
+                        continue;
+                    }
+                    Event::Say {
+                        who: who.as_deref(),
+                        what,
+                        seen,
+                    }
+                }
+                AST::Scene(_, image, layer, _) => Event::Scene {
+                    image: image.as_deref(),
+                    layer,
+                },
+                AST::Show(_, image, _) => Event::Show { image },
+                AST::Hide(_, image, _) => Event::Hide { image },
+                AST::Play(_, channel, filename) => Event::Play { channel, filename },
+                AST::Stop(_, channel, _, fadeout) => Event::Stop {
+                    channel,
+                    fadeout: *fadeout,
+                },
+                AST::GameMechanic(_, mechanic) => Event::GameMechanic(mechanic),
+                AST::LLMGenerate(_, who, prompt) => Event::LlmGenerate {
+                    who,
+                    prompt: prompt.as_deref(),
+                },
+                AST::Custom(_, keyword, rest) => Event::Custom { keyword, rest },
+            };
+
+            let next = self.next(path);
+            self.goto(next);
+            return Ok(event);
+        }
+    }
+
+    fn label_path(&self, label: &str) -> Result<(&'a str, Vec<usize>)> {
+        self.labels
+            .get_key_value(label)
+            .map(|(name, path)| (*name, path.clone()))
+            .ok_or_else(|| anyhow!("jump to undefined label '{}'", label))
+    }
+
+    /// Statement after the one at the path, leaving the blocks that end
+    fn next(&self, mut path: Vec<usize>) -> Option<Vec<usize>> {
+        while let Some(last) = path.last_mut() {
+            *last += 1;
+            if node_at(self.ast, &path).is_some() {
+                return Some(path);
+            }
+            path.pop();
+        }
+        None
+    }
+}
+
+impl fmt::Debug for VM<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VM")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,251 +1,643 @@
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::rc::Rc;
+use alloc::sync::Arc;
 use anyhow::{Ok, Result};
-use std::collections::HashSet;
+use core::cell::{Cell, RefCell};
+use core::ops::Range;
+use regex_automata::meta::{BuildError, Regex};
 
-use crate::parsers::ParseError;
+use crate::diagnostics::{Code, Diagnostics};
+use crate::options::{IndentRule, ParseOptions, WhitespacePolicy};
+use crate::parsers::{ParseError, Quoting};
+use crate::progress::ParseEvent;
+
+const WORD: &str = r"^[a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*";
+const WORD_CHAR: &str = r"^[0-9a-zA-Z_\u00a0-\ufffd]";
+const DOTTED_PART: &str = r"^\.[a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*";
 
 #[derive(Clone)]
-pub struct Block {
+pub struct Block<'src> {
     pub filename: String,
     pub line_number: usize,
-    pub text: String,
-    pub subblocks: Vec<Block>,
+    /// Text of the logical line, borrowed from the source unless the scanner
+    /// had to change it
+    pub text: Cow<'src, str>,
+    /// Number of spaces stripped from the start of the line
+    pub indent: usize,
+    /// Byte range of the text in the source
+    pub span: Range<usize>,
+    pub subblocks: Vec<Block<'src>>,
 }
 
-pub struct Lexer {
-    block: Vec<Block>,
+/// Reads the statements of a block. What it matches is borrowed from the
+/// source of the blocks wherever their text is.
+pub struct Lexer<'a, 'src> {
+    block: Cow<'a, [Block<'src>]>,
     init: bool,
     eob: bool,
+    /// Index of the current line in `block`
     line: isize,
-    filename: String,
-    line_number: usize,
-    text: String,
-    subblock: Vec<Block>,
+    /// Byte offset into `text`, always on a char boundary
     pos: usize,
-    keywords: HashSet<&'static str>,
+    keywords: Rc<BTreeSet<String>>,
+    options: Rc<ParseOptions>,
+    /// Reported problems of this lexer and its sub-block lexers
+    diagnostics: Rc<RefCell<Diagnostics>>,
+    /// Set once the observer of the options cancels the parse
+    cancelled: Rc<Cell<bool>>,
+    /// Whether this lexer reads the block of another statement
+    nested: bool,
+    /// Indentation step of the first sub-block read, shared with sub-block lexers
+    indent_step: Rc<Cell<Option<usize>>>,
 }
 
-#[derive(Clone)]
+/// A string literal along with how it was written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StringLit<'src> {
+    /// Text with escapes resolved, unless raw, and whitespace treated as
+    /// [`ParseOptions::whitespace`] says
+    pub value: Cow<'src, str>,
+    /// Written with an `r` prefix, keeping escapes as they are
+    pub raw: bool,
+    pub quote_char: char,
+    /// Written in triple quotes
+    pub triple: bool,
+    /// Source text, including quotes and prefix
+    pub source: Cow<'src, str>,
+}
+
+impl StringLit<'_> {
+    pub fn quoting(&self) -> Quoting {
+        Quoting {
+            quote_char: self.quote_char,
+            raw: self.raw,
+            triple: self.triple,
+        }
+    }
+}
+
+/// Position of a lexer, cheap to take and to restore
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LexerState {
-    filename: String,
-    line_number: usize,
-    text: String,
-    subblock: Vec<Block>,
+    line: isize,
+    eob: bool,
     pos: usize,
 }
 
-impl Lexer {
-    pub fn new(block: Vec<Block>, init: bool) -> Self {
-        let keywords = HashSet::from([
-            "hide",
-            "init",
-            "jump",
-            "return",
-            "scene",
-            "show",
-            "play",
-            "define",
-            "game_mechanic",
-            "llm_generate",
-        ]);
+impl LexerState {
+    /// Byte offset into the text of the saved line
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+}
 
+impl<'a, 'src> Lexer<'a, 'src> {
+    pub fn new(block: Vec<Block<'src>>, init: bool) -> Self {
+        Self::with_blocks(Cow::Owned(block), init)
+    }
+
+    /// Lexer over blocks owned by the caller, which sub-block lexers borrow as well
+    pub fn borrowed(block: &'a [Block<'src>], init: bool) -> Self {
+        Self::with_blocks(Cow::Borrowed(block), init)
+    }
+
+    fn with_blocks(block: Cow<'a, [Block<'src>]>, init: bool) -> Self {
         Lexer {
             block,
             init,
             eob: false,
             line: -1,
-            filename: String::new(),
-            line_number: 0,
-            text: String::new(),
-            subblock: Vec::new(),
             pos: 0,
-            keywords,
+            keywords: Rc::new(reserved_words(&ParseOptions::default())),
+            options: Rc::default(),
+            diagnostics: Rc::default(),
+            cancelled: Rc::default(),
+            nested: false,
+            indent_step: Rc::default(),
         }
     }
 
+    /// Applies the string handling and reserved words of the options to this
+    /// lexer and its sub-block lexers
+    pub fn with_options(mut self, options: &ParseOptions) -> Self {
+        self.keywords = Rc::new(reserved_words(options));
+        self.options = Rc::new(options.clone());
+        self
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Whether `name` can't be used as a name because it is reserved
+    pub fn is_keyword(&self, name: &str) -> bool {
+        self.keywords.contains(name)
+    }
+
     pub fn eob(&self) -> bool {
-        return self.eob;
+        self.eob
+    }
+
+    pub fn text(&self) -> &str {
+        self.current().map_or("", |block| &block.text)
     }
 
-    pub fn text(&self) -> String {
-        return self.text.clone();
+    fn current(&self) -> Option<&Block<'src>> {
+        usize::try_from(self.line)
+            .ok()
+            .and_then(|line| self.block.get(line))
     }
 
+    /// Text of the current line in the range, borrowed from the source when
+    /// the line is
+    pub(crate) fn span(&self, range: Range<usize>) -> Cow<'src, str> {
+        self.current()
+            .map_or(Cow::Borrowed(""), |block| slice(&block.text, range))
+    }
+
+    /// The block nested under the current line, without consuming it
+    pub fn subblock(&self) -> &[Block<'src>] {
+        self.current().map_or(&[], |block| &block.subblocks)
+    }
+
+    pub fn has_block(&self) -> bool {
+        !self.subblock().is_empty()
+    }
+
+    /// Number of lines directly in the block under the current line
+    pub fn subblock_len(&self) -> usize {
+        self.subblock().len()
+    }
+
+    /// Byte offset into the current line's text
     pub fn pos(&self) -> usize {
-        return self.pos;
+        self.pos
     }
 
     pub fn advance(&mut self) -> bool {
-        self.line += 1;
-
-        if self.line as usize >= self.block.len() {
+        if (self.line + 1) as usize >= self.block.len() {
             self.eob = true;
             return false;
         }
 
-        let block = self.block[self.line as usize].clone();
-
-        self.filename = block.filename;
-        self.line_number = block.line_number;
-        self.text = block.text;
-        self.subblock = block.subblocks;
+        self.line += 1;
         self.pos = 0;
 
         true
     }
 
-    fn match_regexp(&mut self, regexp: &str) -> Option<String> {
+    /// Moves past a match of the pattern at the current position
+    fn skip(&mut self, regexp: &'static str) -> bool {
+        let Some(range) = self.find_at(self.pos, regexp) else {
+            return false;
+        };
+        self.pos = range.end;
+        true
+    }
+
+    /// Byte range of a match of one of the lexer's own patterns starting
+    /// exactly at `start`. Those are known to compile.
+    fn find_at(&self, start: usize, regexp: &'static str) -> Option<Range<usize>> {
+        self.find_at_with(start, regexp, false).ok().flatten()
+    }
+
+    /// Byte range of a match of the pattern starting exactly at `start`,
+    /// failing when the pattern doesn't compile
+    fn find_at_with(
+        &self,
+        start: usize,
+        regexp: &str,
+        ignore_case: bool,
+    ) -> Result<Option<Range<usize>>> {
+        let re = anchored(regexp, ignore_case).map_err(|err| {
+            let msg = format!("invalid pattern {:?}: {}", regexp, err);
+            self.parse_error(Code::Other, &msg)
+        })?;
+
         if self.eob {
-            return None;
+            return Ok(None);
         }
 
-        if self.pos == self.text.len() {
-            return None;
+        let text = self.text();
+        if start >= text.len() || !text.is_char_boundary(start) {
+            return Ok(None);
         }
 
-        let text_to_match = self.text[self.pos..].to_string();
-        let re = regex::Regex::new(regexp).unwrap();
-        let m = re.find(&text_to_match);
-
-        if let Some(m) = m {
-            self.pos += m.end();
-            let result = text_to_match[m.start()..m.end()].to_string();
+        let m = re.find(&text[start..]);
+        Ok(m.map(|m| start + m.start()..start + m.end()))
+    }
 
-            Some(result)
-        } else {
-            None
-        }
+    /// Position of the next non-whitespace character
+    pub(crate) fn after_whitespace(&self) -> usize {
+        self.find_at(self.pos, r"^\s+")
+            .map_or(self.pos, |range| range.end)
     }
 
     fn skip_whitespace(&mut self) {
-        self.match_regexp(r"^\s+");
+        self.skip(r"^\s+");
+    }
+
+    /// Matches the pattern after any whitespace. Patterns are always anchored
+    /// at the current position, whether or not they start with `^`. Fails
+    /// when the pattern doesn't compile.
+    pub fn match_(&mut self, regexp: &str) -> Result<Option<Cow<'src, str>>> {
+        self.match_with(regexp, false)
+    }
+
+    /// Like [`Lexer::match_`], ignoring case
+    pub fn match_ignore_case(&mut self, regexp: &str) -> Result<Option<Cow<'src, str>>> {
+        self.match_with(regexp, true)
     }
 
-    pub fn match_(&mut self, regexp: &str) -> Option<String> {
+    fn match_with(&mut self, regexp: &str, ignore_case: bool) -> Result<Option<Cow<'src, str>>> {
         self.skip_whitespace();
-        self.match_regexp(regexp)
+        let Some(range) = self.find_at_with(self.pos, regexp, ignore_case)? else {
+            return Ok(None);
+        };
+        self.pos = range.end;
+        Ok(Some(self.span(range)))
+    }
+
+    /// [`Lexer::match_`] for the lexer's own patterns, which are known to compile
+    fn match_known(&mut self, regexp: &'static str) -> Option<Cow<'src, str>> {
+        self.match_with(regexp, false).ok().flatten()
+    }
+
+    /// Matches a keyword, which has to end at a word boundary so that
+    /// `return` doesn't match the start of `returning`. Fails when the
+    /// pattern doesn't compile.
+    pub fn keyword(&mut self, regexp: &str) -> Result<Option<Cow<'src, str>>> {
+        self.keyword_with(regexp, false)
+    }
+
+    /// Like [`Lexer::keyword`], ignoring case
+    pub fn keyword_ignore_case(&mut self, regexp: &str) -> Result<Option<Cow<'src, str>>> {
+        self.keyword_with(regexp, true)
+    }
+
+    fn keyword_with(&mut self, regexp: &str, ignore_case: bool) -> Result<Option<Cow<'src, str>>> {
+        let Some(range) = self.find_keyword(self.after_whitespace(), regexp, ignore_case)? else {
+            return Ok(None);
+        };
+        self.pos = range.end;
+        Ok(Some(self.span(range)))
+    }
+
+    fn find_keyword(
+        &self,
+        start: usize,
+        regexp: &str,
+        ignore_case: bool,
+    ) -> Result<Option<Range<usize>>> {
+        let Some(range) = self.find_at_with(start, regexp, ignore_case)? else {
+            return Ok(None);
+        };
+        if self.find_at(range.end, WORD_CHAR).is_some() {
+            return Ok(None);
+        }
+        Ok(Some(range))
     }
 
-    pub fn keyword(&mut self, regexp: &str) -> Option<String> {
-        self.match_(&format!(r"{}", regexp))
+    pub fn error(&self, code: Code, msg: &str) -> Result<()> {
+        Err(self.parse_error(code, msg).into())
     }
 
-    pub fn error(&self, msg: &str) -> Result<()> {
-        let err = ParseError {
-            filename: self.filename.clone(),
-            line_number: self.line_number,
+    /// An error located at the current position
+    pub fn parse_error(&self, code: Code, msg: &str) -> ParseError {
+        ParseError {
+            code,
+            filename: self
+                .current()
+                .map_or_else(String::new, |block| block.filename.clone()),
+            line_number: self.get_location(),
             message: msg.to_string(),
-            line: Some(self.text.clone()),
+            line: Some(self.text().to_string()),
             pos: Some(self.pos),
+            column: Some(self.column()),
+        }
+    }
+
+    /// Reports a problem at the current position that doesn't stop parsing
+    pub fn warning(&self, code: Code, msg: &str) {
+        let warning = self.parse_error(code, msg);
+        self.diagnostics.borrow_mut().warning(warning);
+    }
+
+    /// Reports a problem at a byte offset into the current line that doesn't
+    /// stop parsing
+    pub fn warning_at(&self, pos: usize, code: Code, msg: &str) {
+        let indent = self.current().map_or(0, |block| block.indent);
+        let warning = ParseError {
+            pos: Some(pos),
+            column: Some(column(self.text(), pos, indent)),
+            ..self.parse_error(code, msg)
+        };
+        self.diagnostics.borrow_mut().warning(warning);
+    }
+
+    /// Reports an error that was recovered from
+    pub fn report_error(&self, error: ParseError) {
+        self.diagnostics.borrow_mut().error(error);
+    }
+
+    /// Problems reported so far by this lexer and its sub-block lexers
+    pub fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.borrow().clone()
+    }
+
+    /// Tells the observer of the options about the event, noting whether it
+    /// cancelled the parse. Nothing is told once the parse is cancelled.
+    pub fn notify(&self, event: ParseEvent) {
+        if !self.is_cancelled() && self.options.observer.notify(event).is_err() {
+            self.cancelled.set(true);
         }
-        .into();
-        Err(err)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+
+    pub fn is_nested(&self) -> bool {
+        self.nested
     }
 
     pub fn eol(&mut self) -> bool {
         self.skip_whitespace();
-        self.pos >= self.text.len()
+        self.pos >= self.text().len()
     }
 
     pub fn expect_eol(&mut self) -> Result<()> {
         if !self.eol() {
-            self.error("end of line expected")?;
+            self.error(Code::ExpectedEndOfLine, "end of line expected")?;
         }
         Ok(())
     }
 
     pub fn expect_noblock(&mut self, stmt: &str) -> Result<()> {
-        if !self.subblock.is_empty() {
-            self.error(&format!("{} does not expect a block. Please check the indentation of the line after this one.", stmt))?;
+        if self.has_block() {
+            self.error(Code::UnexpectedBlock, &format!("{} does not expect a block. Please check the indentation of the line after this one.", stmt))?;
         }
         Ok(())
     }
 
     pub fn expect_block(&mut self, stmt: &str) -> Result<()> {
-        if self.subblock.is_empty() {
-            self.error(&format!("{} expects a non-empty block.", stmt))?;
+        if !self.has_block() {
+            self.error(
+                Code::MissingBlock,
+                &format!("{} expects a non-empty block.", stmt),
+            )?;
         }
         Ok(())
     }
 
-    pub fn subblock_lexer(&mut self, init: bool) -> Lexer {
-        Lexer::new(self.subblock.clone(), self.init || init)
-    }
-
-    pub fn string(&mut self) -> Option<String> {
-        let s = self
-            .match_(r#"r?"([^\\"]|\\.)*""#)
-            .or_else(|| self.match_(r#"r?'([^\\']|\\.)*'"#))
-            .or_else(|| self.match_(r#"r?`([^\\`]|\\.)*`"#));
-
-        if let Some(s) = s {
-            let mut s: String = s[1..s.len() - 1].to_string();
-            if s.starts_with('r') {
-                s = s[1..].to_string();
-            } else {
-                s = s.replace("\\n", "\n");
-                s = regex::Regex::new(r"\\u([0-9a-fA-F]{1,4})")
-                    .unwrap()
-                    .replace_all(&s, |caps: &regex::Captures| {
-                        let hex = &caps[1];
-                        String::from_utf8(vec![u8::from_str_radix(hex, 16).unwrap()]).unwrap()
-                    })
-                    .to_string();
-
-                s = regex::Regex::new(r"\\.")
-                    .unwrap()
-                    .replace_all(&s, |caps: &regex::Captures| {
-                        caps.get(0).unwrap().as_str()[1..].to_string()
-                    })
-                    .to_string();
-
-                s = regex::Regex::new(r"\s+")
-                    .unwrap()
-                    .replace_all(&s, " ")
-                    .to_string();
+    pub fn subblock_lexer(&mut self, init: bool) -> Lexer<'a, 'src> {
+        let block = match self.block {
+            Cow::Borrowed(block) => Cow::Borrowed(
+                usize::try_from(self.line)
+                    .ok()
+                    .and_then(|line| block.get(line))
+                    .map_or(&[][..], |block| &block.subblocks),
+            ),
+            Cow::Owned(_) => Cow::Owned(self.subblock().to_vec()),
+        };
+
+        Lexer {
+            block,
+            init: self.init || init,
+            eob: false,
+            line: -1,
+            pos: 0,
+            keywords: self.keywords.clone(),
+            options: self.options.clone(),
+            diagnostics: self.diagnostics.clone(),
+            cancelled: self.cancelled.clone(),
+            nested: true,
+            indent_step: self.indent_step.clone(),
+        }
+    }
+
+    /// Lexer over the sub-block that borrows it from this lexer, never cloning it
+    pub fn child_lexer(&self, init: bool) -> Lexer<'_, 'src> {
+        self.check_indentation();
+        Lexer {
+            block: Cow::Borrowed(self.subblock()),
+            init: self.init || init,
+            eob: false,
+            line: -1,
+            pos: 0,
+            keywords: self.keywords.clone(),
+            options: self.options.clone(),
+            diagnostics: self.diagnostics.clone(),
+            cancelled: self.cancelled.clone(),
+            nested: true,
+            indent_step: self.indent_step.clone(),
+        }
+    }
+
+    /// Warns about a sub-block indented by a different number of spaces than
+    /// the first one read. Ren'Py accepts it, but it's likely a mistake.
+    fn check_indentation(&self) {
+        let (Some(parent), Some(first)) = (self.current(), self.subblock().first()) else {
+            return;
+        };
+        if self.options.indent != IndentRule::Any {
+            return;
+        }
+
+        let step = first.indent - parent.indent;
+        match self.indent_step.get() {
+            None => self.indent_step.set(Some(step)),
+            Some(expected) if expected != step => {
+                let warning = ParseError::new(
+                    first.filename.clone(),
+                    first.line_number,
+                    format!(
+                        "block indented by {} spaces where earlier blocks are indented by {}",
+                        step, expected
+                    ),
+                    Some(first.text.to_string()),
+                    Some(0),
+                )
+                .with_column(first.indent + 1)
+                .with_code(Code::SuspiciousIndentation);
+                self.diagnostics.borrow_mut().warning(warning);
             }
-            Some(s.to_string())
-        } else {
-            None
+            Some(_) => {}
         }
     }
 
-    pub fn integer(&mut self) -> Option<String> {
-        self.match_(r"(\+|\-)?[0-9]+")
+    pub fn string(&mut self) -> Option<StringLit<'src>> {
+        self.skip_whitespace();
+        let (rv, end) = self.scan_string(true).or_else(|| self.scan_string(false))?;
+        self.pos = end;
+        Some(rv)
     }
 
-    pub fn float(&mut self) -> Option<String> {
-        self.match_(r"(\+|\-)?([0-9]+\.[0-9]*|[0-9]*\.[0-9]+)([eE][-+]?[0-9]+)?")
+    /// Matches a string in triple quotes, which keeps its line breaks
+    pub fn triple_string(&mut self) -> Option<StringLit<'src>> {
+        self.skip_whitespace();
+        let (rv, end) = self.scan_string(true)?;
+        self.pos = end;
+        Some(rv)
     }
 
-    pub fn word(&mut self) -> Option<String> {
-        self.match_(r#"^[a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*"#)
+    /// The string [`Lexer::string`] would match, without advancing
+    pub fn peek_string(&self) -> Option<StringLit<'src>> {
+        self.scan_string(true)
+            .or_else(|| self.scan_string(false))
+            .map(|(rv, _)| rv)
     }
 
-    pub fn audio_filename(&mut self) -> Option<String> {
-        self.match_(r#""([a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*).+\.(\w)+\"$"#)
+    /// The keyword [`Lexer::keyword`] would match, without advancing
+    pub fn peek_keyword(&self, regexp: &str) -> Result<Option<Cow<'src, str>>> {
+        let range = self.find_keyword(self.after_whitespace(), regexp, false)?;
+        Ok(range.map(|range| self.span(range)))
     }
 
-    pub fn stop_arguments(&mut self) -> (Option<String>, Option<f32>) {
-        let rmatch = self.match_(r#"^[a&&b]|(fadeout \d+\.\d+)$"#);
-        if let Some(rmatch) = rmatch {
-            if rmatch == "" {
-                return (None, None);
-            }
+    /// The word [`Lexer::word`] would match, without advancing
+    pub fn peek_word(&self) -> Option<Cow<'src, str>> {
+        self.peek_keyword(WORD).ok().flatten()
+    }
 
-            let parts: Vec<&str> = rmatch.split(" ").collect();
-            let length = parts[1].parse::<f32>().unwrap();
+    /// Matches a string literal after any whitespace, returning it with its end position
+    fn scan_string(&self, triple: bool) -> Option<(StringLit<'src>, usize)> {
+        let patterns = if triple {
+            [
+                r#"^r?"""(?s:[^\\]|\\.)*?""""#,
+                r"^r?'''(?s:[^\\]|\\.)*?'''",
+                r"^r?```(?s:[^\\]|\\.)*?```",
+            ]
+        } else {
+            [
+                r#"^r?"([^\\"]|\\.)*""#,
+                r"^r?'([^\\']|\\.)*'",
+                r"^r?`([^\\`]|\\.)*`",
+            ]
+        };
+
+        let start = self.after_whitespace();
+        let range = patterns
+            .iter()
+            .find_map(|pattern| self.find_at(start, pattern))?;
+        let source = self.span(range.clone());
+
+        let raw = source.starts_with('r');
+        let quote_char = source[raw as usize..].chars().next()?;
+        let quotes = if triple { 3 } else { 1 };
+        let contents = range.start + raw as usize + quotes..range.end - quotes;
+        let s = &self.text()[contents.clone()];
+
+        let collapse = self.options.whitespace == WhitespacePolicy::Collapse;
+        let value = if raw || (!collapse && !s.contains('\\')) {
+            self.span(contents)
+        } else if !collapse {
+            Cow::Owned(unescape(s))
+        } else if triple {
+            Cow::Owned(unescape(collapse_whitespace(s, true).trim()))
+        } else {
+            Cow::Owned(unescape(&collapse_whitespace(s, false)))
+        };
+
+        let rv = StringLit {
+            value,
+            raw,
+            quote_char,
+            triple,
+            source,
+        };
+        Some((rv, range.end))
+    }
+
+    pub fn integer(&mut self) -> Option<Cow<'src, str>> {
+        self.match_known(r"(\+|\-)?[0-9]+")
+    }
+
+    pub fn float(&mut self) -> Option<Cow<'src, str>> {
+        self.match_known(r"(\+|\-)?([0-9]+\.[0-9]*|[0-9]*\.[0-9]+)([eE][-+]?[0-9]+)?")
+    }
 
-            return (Some(parts[0].to_string()), Some(length));
+    /// Matches an integer and parses it, failing when it doesn't fit an i64
+    pub fn int_value(&mut self) -> Result<Option<i64>> {
+        let start = self.pos;
+        let Some(text) = self.integer() else {
+            return Ok(None);
+        };
+
+        let value = text.trim().parse::<i64>().ok();
+        if value.is_none() {
+            self.pos = start;
+            self.error(
+                Code::InvalidNumber,
+                &format!("integer '{}' is out of range", text.trim()),
+            )?;
         }
+        Ok(value)
+    }
 
-        return (None, None);
+    /// Matches a floating point number and parses it
+    pub fn float_value(&mut self) -> Result<Option<f64>> {
+        let start = self.pos;
+        let Some(text) = self.float() else {
+            return Ok(None);
+        };
+
+        let value = text.trim().parse::<f64>().ok();
+        if value.is_none() {
+            self.pos = start;
+            self.error(
+                Code::InvalidNumber,
+                &format!("invalid number '{}'", text.trim()),
+            )?;
+        }
+        Ok(value)
     }
 
-    pub fn name(&mut self) -> Option<String> {
+    pub fn word(&mut self) -> Option<Cow<'src, str>> {
+        self.match_known(WORD)
+    }
+
+    /// Matches a name with dot-separated parts, like `store.variable.name`,
+    /// or a local label name starting with a dot, like `.sub`
+    pub fn dotted_name(&mut self) -> Option<Cow<'src, str>> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if !self.skip(DOTTED_PART) {
+            self.name()?;
+        }
+        while self.skip(DOTTED_PART) {}
+        Some(self.span(start..self.pos))
+    }
+
+    pub fn audio_filename(&mut self) -> Option<Cow<'src, str>> {
+        self.match_known(r#""([a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*).+\.(\w)+\"$"#)
+    }
+
+    pub fn stop_arguments(&mut self) -> Result<(Option<Cow<'src, str>>, Option<f32>)> {
+        let start = self.after_whitespace();
+        let rmatch = self.match_known(r#"^[a&&b]|(fadeout \d+\.\d+)$"#);
+        let Some((effect, length)) = rmatch.as_deref().and_then(|m| m.split_once(' ')) else {
+            return Ok((None, None));
+        };
+
+        let Some(length) = length.parse::<f32>().ok() else {
+            return Err(self
+                .parse_error(
+                    Code::InvalidNumber,
+                    &format!("invalid fadeout length {}", length),
+                )
+                .into());
+        };
+
+        Ok((Some(self.span(start..start + effect.len())), Some(length)))
+    }
+
+    pub fn name(&mut self) -> Option<Cow<'src, str>> {
         let oldpos = self.pos;
         let rv = self.word();
         if let Some(rv) = rv {
-            if self.keywords.contains(rv.as_str()) {
+            if self.is_keyword(&rv) {
                 self.pos = oldpos;
                 return None;
             }
@@ -255,62 +647,318 @@ impl Lexer {
         }
     }
 
-    pub fn simple_expression(&mut self) -> Result<Option<String>> {
+    pub fn simple_expression(&mut self) -> Result<Option<Cow<'src, str>>> {
         self.skip_whitespace();
         if self.eol() {
             return Ok(None);
         }
 
         let start = self.pos;
+        self.skip_whitespace();
 
-        while !self.eol() {
-            self.skip_whitespace();
-            if self.eol() {
+        Ok(Some(self.span(start..self.pos)))
+    }
+
+    /// Matches a Python expression up to a `:` outside of brackets, or the end of the line
+    pub fn python_expression(&mut self) -> Result<Option<Cow<'src, str>>> {
+        self.delimited_python(":")
+    }
+
+    /// Matches Python code up to one of `stop_chars` outside of brackets and
+    /// strings, or the end of the line. Brackets have to be balanced.
+    pub fn delimited_python(&mut self, stop_chars: &str) -> Result<Option<Cow<'src, str>>> {
+        let start = self.after_whitespace();
+        let mut closers = Vec::new();
+        let mut i = start;
+
+        while let Some(c) = self.text().get(i..).and_then(|rest| rest.chars().next()) {
+            if closers.is_empty() && stop_chars.contains(c) {
                 break;
             }
 
-            break;
+            match c {
+                '"' | '\'' | '`' => {
+                    let patterns = [
+                        r#"^"""(?s:[^\\]|\\.)*?""""#,
+                        r"^'''(?s:[^\\]|\\.)*?'''",
+                        r"^```(?s:[^\\]|\\.)*?```",
+                        r#"^"([^\\"]|\\.)*""#,
+                        r"^'([^\\']|\\.)*'",
+                        r"^`([^\\`]|\\.)*`",
+                    ];
+                    match patterns.iter().find_map(|pattern| self.find_at(i, pattern)) {
+                        Some(string) => i = string.end,
+                        None => {
+                            self.pos = i;
+                            self.error(Code::UnterminatedString, "unterminated string")?;
+                        }
+                    }
+                    continue;
+                }
+                '(' => closers.push(')'),
+                '[' => closers.push(']'),
+                '{' => closers.push('}'),
+                ')' | ']' | '}' => {
+                    let expected = closers.pop();
+                    if expected != Some(c) {
+                        self.pos = i;
+                        self.error(Code::UnbalancedBracket, &format!("unexpected '{}'", c))?;
+                    }
+                }
+                _ => {}
+            }
+
+            i += c.len_utf8();
+        }
+
+        if let Some(closer) = closers.last() {
+            self.pos = i;
+            self.error(
+                Code::UnbalancedBracket,
+                &format!("expected '{}' not found", closer),
+            )?;
+        }
+
+        let end = start + self.text()[start..i].trim_end().len();
+        if end == start {
+            return Ok(None);
         }
 
-        Ok(Some(self.text[start..self.pos].to_string()))
+        self.pos = end;
+        Ok(Some(self.span(start..end)))
     }
 
-    pub fn require(&mut self, thing: &str) -> Result<String> {
-        let regexp = &format!(r"{}", thing);
-        if let Some(rv) = self.match_(regexp) {
+    pub fn require(&mut self, thing: &str) -> Result<Cow<'src, str>> {
+        self.require_with(thing, false)
+    }
+
+    /// Like [`Lexer::require`], ignoring case
+    pub fn require_ignore_case(&mut self, thing: &str) -> Result<Cow<'src, str>> {
+        self.require_with(thing, true)
+    }
+
+    fn require_with(&mut self, thing: &str, ignore_case: bool) -> Result<Cow<'src, str>> {
+        if let Some(rv) = self.match_with(thing, ignore_case)? {
             Ok(rv)
         } else {
-            let err: Result<()> = self.error(&format!("expected '{}' not found", thing));
-            return Err(err.err().unwrap());
+            let msg = format!("expected '{}' not found", thing);
+            Err(self.parse_error(Code::ExpectedToken, &msg).into())
         }
     }
 
-    pub fn rest(&mut self) -> String {
+    pub fn rest(&mut self) -> Cow<'src, str> {
         self.skip_whitespace();
         let start = self.pos;
-        self.pos = self.text.len();
-        self.text[start..].to_string()
+        self.pos = self.text().len();
+        self.span(start..self.pos)
+    }
+
+    /// Consumes the rest of the line along with its block, if any, and moves
+    /// to the next line. For statements kept as written, like `python:`.
+    pub fn renpy_statement(&mut self) -> (Cow<'src, str>, Vec<Block<'src>>) {
+        let text = self.rest();
+        let block = self.subblock().to_vec();
+        self.advance();
+        (text, block)
     }
 
     pub fn get_location(&self) -> usize {
-        return self.line_number;
+        self.current().map_or(0, |block| block.line_number)
+    }
+
+    /// 1-based column of the current position, including the stripped
+    /// indentation. In statements spanning several lines it is counted from
+    /// the start of the physical line the position is on.
+    pub fn column(&self) -> usize {
+        let indent = self.current().map_or(0, |block| block.indent);
+        column(self.text(), self.pos, indent)
     }
 
     pub fn checkpoint(&self) -> LexerState {
         LexerState {
-            filename: self.filename.clone(),
-            line_number: self.line_number,
-            text: self.text.clone(),
-            subblock: self.subblock.clone(),
+            line: self.line,
+            eob: self.eob,
             pos: self.pos,
         }
     }
 
     pub fn revert(&mut self, state: LexerState) {
-        self.filename = state.filename;
-        self.line_number = state.line_number;
-        self.text = state.text;
-        self.subblock = state.subblock;
+        self.line = state.line;
+        self.eob = state.eob;
         self.pos = state.pos;
     }
 }
+
+/// Names the lexer reserves unless the options say otherwise
+pub const RESERVED_WORDS: [&str; 12] = [
+    "define",
+    "game_mechanic",
+    "hide",
+    "include",
+    "init",
+    "jump",
+    "llm_generate",
+    "onlayer",
+    "play",
+    "return",
+    "scene",
+    "show",
+];
+
+/// Keywords of the statements Ren'Py doesn't have
+pub const EXTENSION_WORDS: [&str; 3] = ["game_mechanic", "include", "llm_generate"];
+
+fn reserved_words(options: &ParseOptions) -> BTreeSet<String> {
+    RESERVED_WORDS
+        .iter()
+        .filter(|word| options.extended_statements || !EXTENSION_WORDS.contains(word))
+        .map(|word| word.to_string())
+        .chain(options.reserved_words.iter().cloned())
+        .chain(options.statements.keywords().map(str::to_string))
+        .filter(|word| !options.unreserved_words.contains(word))
+        .collect()
+}
+
+/// Unescapes string contents the way Ren'Py does. `\n` is a line break and
+/// `\uXXXX` a code point; `\{`, `\[` and `\%` stay escaped for the text engine
+/// as `{{`, `[[` and `%%`; any other escaped character stands for itself.
+pub fn unescape(s: &str) -> String {
+    let mut rv = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            rv.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => rv.push('\n'),
+            Some('u') => {
+                let mut hex = String::new();
+                while hex.len() < 4 && chars.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    hex.extend(chars.next());
+                }
+
+                match u32::from_str_radix(&hex, 16).ok() {
+                    Some(code) => {
+                        rv.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                    }
+                    None => rv.push('u'),
+                }
+            }
+            Some(c @ ('{' | '[' | '%')) => {
+                rv.push(c);
+                rv.push(c);
+            }
+            Some(c) => rv.push(c),
+            None => rv.push('\\'),
+        }
+    }
+
+    rv
+}
+
+/// Part of a text, borrowed from the source when the text is
+pub(crate) fn slice<'src>(text: &Cow<'src, str>, range: Range<usize>) -> Cow<'src, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+        Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+    }
+}
+
+fn column(text: &str, pos: usize, indent: usize) -> usize {
+    let before = &text[..pos];
+    match before.rfind('\n') {
+        Some(newline) => before[newline + 1..].chars().count() + 1,
+        None => indent + before.chars().count() + 1,
+    }
+}
+
+/// Replaces each run of whitespace with a space, the way Ren'Py reads
+/// strings. With `newlines`, runs holding line breaks become just the breaks.
+fn collapse_whitespace(s: &str, newlines: bool) -> String {
+    let mut rv = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() {
+            rv.push(c);
+            continue;
+        }
+
+        let mut breaks = usize::from(c == '\n');
+        while let Some(c) = chars.next_if(|c| c.is_whitespace()) {
+            breaks += usize::from(c == '\n');
+        }
+        match (newlines, breaks) {
+            (true, breaks) if breaks > 0 => rv.extend(core::iter::repeat_n('\n', breaks)),
+            _ => rv.push(' '),
+        }
+    }
+    rv
+}
+
+/// Compiled patterns, by whether they ignore case. The regexes are shared
+/// rather than cloned, as clones don't share match caches.
+type PatternCache = [BTreeMap<String, Arc<Regex>>; 2];
+
+/// Patterns kept compiled for each case setting. Patterns can come from
+/// callers, like keywords of custom statements, so the cache is emptied when
+/// it fills up rather than growing for as long as the program runs; the
+/// crate's own patterns are far fewer.
+const MAX_CACHED_PATTERNS: usize = 256;
+
+/// Compiles a pattern to match only at the start of the text, once per thread,
+/// or once per program without std
+fn anchored(pattern: &str, ignore_case: bool) -> Result<Arc<Regex>, Box<BuildError>> {
+    #[cfg(feature = "std")]
+    {
+        thread_local! {
+            static CACHE: RefCell<PatternCache> = RefCell::default();
+        }
+        CACHE.with(|cache| cached(&mut cache.borrow_mut(), pattern, ignore_case))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        static CACHE: spin::Mutex<PatternCache> =
+            spin::Mutex::new([BTreeMap::new(), BTreeMap::new()]);
+        cached(&mut CACHE.lock(), pattern, ignore_case)
+    }
+}
+
+fn cached(
+    cache: &mut PatternCache,
+    pattern: &str,
+    ignore_case: bool,
+) -> Result<Arc<Regex>, Box<BuildError>> {
+    let cache = &mut cache[ignore_case as usize];
+    if let Some(re) = cache.get(pattern) {
+        return core::result::Result::Ok(re.clone());
+    }
+
+    let flags = if ignore_case { "i" } else { "" };
+    let re = Arc::new(Regex::new(&format!("^(?{}:{})", flags, pattern)).map_err(Box::new)?);
+    if cache.len() >= MAX_CACHED_PATTERNS {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), re.clone());
+    core::result::Result::Ok(re)
+}
+
+/// Compiles a pattern once per thread, reusing it on later calls
+#[cfg(feature = "std")]
+pub(crate) fn compiled(pattern: &str) -> Result<Rc<regex::Regex>, regex::Error> {
+    thread_local! {
+        static CACHE: RefCell<BTreeMap<String, Rc<regex::Regex>>> = RefCell::default();
+    }
+
+    CACHE.with(|cache| {
+        if let Some(re) = cache.borrow().get(pattern) {
+            return core::result::Result::Ok(re.clone());
+        }
+
+        let re = Rc::new(regex::Regex::new(pattern)?);
+        cache.borrow_mut().insert(pattern.to_string(), re.clone());
+        core::result::Result::Ok(re)
+    })
+}
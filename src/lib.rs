@@ -1,79 +1,387 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analytics;
+#[cfg(feature = "ast_arena")]
+pub mod arena;
+#[cfg(feature = "std")]
+pub mod assets;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod borrowed;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod characters;
+pub mod diagnostics;
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod flow;
+#[cfg(feature = "std")]
+pub mod highlight;
+#[cfg(feature = "std")]
+pub mod import;
+#[cfg(feature = "fs")]
+pub mod include;
+#[cfg(feature = "std")]
+pub mod incremental;
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "std")]
+pub mod interpreter;
 pub mod lexer;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+#[cfg(feature = "std")]
+pub mod merge;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod options;
 pub mod parsers;
+#[cfg(feature = "std")]
+pub mod playtime;
+pub mod progress;
+#[cfg(feature = "fs")]
+pub mod project;
+#[cfg(feature = "miette")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod search;
+#[cfg(feature = "std")]
+pub mod source_map;
+pub mod statements;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod tokens;
+#[cfg(feature = "std")]
+pub mod translation;
+pub mod trivia;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod writer;
+
+#[cfg(feature = "macros")]
+mod macros;
+
+/// What the std prelude brings in, for the modules that build without std
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::format;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
 
+use alloc::borrow::Cow;
 use anyhow::Result;
+use core::ops::Range;
+use diagnostics::{Code, Diagnostics};
+use encoding::decode;
+#[cfg(feature = "std")]
+use encoding::EncodingFallback;
 use lexer::{Block, Lexer};
+use options::{CommentHandling, IndentRule, ParseOptions, TabPolicy};
 use parsers::{parse_block, ParseError, AST};
-use regex::Regex;
-use std::{fs::File, io::Read, path::Path};
+use prelude::*;
+use progress::{Cancelled, ParseEvent};
+#[cfg(feature = "std")]
+use std::io::Read;
+use trivia::{Comment, Comments, SourceFormat};
 
 #[derive(Debug, Clone)]
-pub struct LogicalLine {
+pub struct LogicalLine<'a> {
     filename: String,
     line_number: usize,
-    text: String,
+    /// Text as the scanner reads it, borrowed from the source unless it had
+    /// to change it, such as for line continuations
+    text: Cow<'a, str>,
+    /// Byte range of the line in the source, without indentation, comments and trailing whitespace
+    span: Range<usize>,
+}
+
+impl LogicalLine<'_> {
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Copies the text of the line, detaching it from the source
+    pub fn into_owned(self) -> LogicalLine<'static> {
+        LogicalLine {
+            text: Cow::Owned(self.text.into_owned()),
+            ..self
+        }
+    }
+}
+
+pub fn parse_logical_lines<'a>(data: &'a str, filename: &str) -> Result<Vec<LogicalLine<'a>>> {
+    LogicalLines::new(data, filename).collect()
+}
+
+/// A script divided into logical lines, along with what was stripped from it
+#[derive(Clone, Debug, Default)]
+pub struct ScannedScript<'a> {
+    pub lines: Vec<LogicalLine<'a>>,
+    pub comments: Vec<Comment>,
+    /// Numbers of the lines holding only whitespace
+    pub blank_lines: Vec<usize>,
+    /// Problems that didn't stop the script from being divided, such as expanded tabs
+    pub warnings: Vec<ParseError>,
+}
+
+/// Divides a script into logical lines, keeping its comments and blank lines
+pub fn scan_script<'a>(
+    source: &'a str,
+    filename: &str,
+    options: &ParseOptions,
+) -> Result<ScannedScript<'a>> {
+    let mut lines = LogicalLines::with_options(source, filename, options);
+    let rv = lines.by_ref().collect::<Result<Vec<_>>>()?;
+
+    Ok(ScannedScript {
+        lines: rv,
+        comments: lines.comments,
+        blank_lines: lines.blank_lines,
+        warnings: lines.warnings,
+    })
+}
+
+/// Reads the chars of a script the way the scanner sees them: Windows line
+/// endings as `\n`, tabs expanded when allowed, and two extra newlines at the
+/// end to fix lousy editors. Each char comes with the byte offset it is from.
+#[derive(Clone)]
+struct Cursor<'a> {
+    source: &'a str,
+    pos: usize,
+    tabs: TabPolicy,
+    /// Column on the physical line, for tab stops
+    column: usize,
+    /// Spaces left of an expanded tab
+    spaces: usize,
+    trailing_newlines: usize,
 }
 
-pub fn parse_logical_lines(data: &str, filename: &str) -> Result<Vec<LogicalLine>> {
-    // Replace Windows line endings
-    let mut data = data.to_string().replace("\r\n", "\n");
+impl Cursor<'_> {
+    fn next(&mut self) -> Option<(char, usize)> {
+        if self.spaces > 0 {
+            self.spaces -= 1;
+            return Some((' ', self.pos - 1));
+        }
 
-    // Add newlines to fix lousy editors
-    data.push_str("\n\n");
+        let Some(c) = self.source[self.pos..].chars().next() else {
+            if self.trailing_newlines == 0 {
+                return None;
+            }
+            self.trailing_newlines -= 1;
+            return Some(('\n', self.source.len()));
+        };
+
+        let offset = self.pos;
+        self.pos += c.len_utf8();
+        match (c, self.tabs) {
+            ('\r', _) if self.source[self.pos..].starts_with('\n') => return self.next(),
+            ('\t', TabPolicy::ExpandTo(width)) => {
+                let width = width.max(1);
+                let spaces = width - self.column % width;
+                self.column = self.column.saturating_add(spaces);
+                self.spaces = spaces - 1;
+                return Some((' ', offset));
+            }
+            ('\n', _) => self.column = 0,
+            _ => self.column += 1,
+        }
+        Some((c, offset))
+    }
 
-    let mut rv = Vec::new();
-    let mut number = 1;
-    let mut pos = 0;
-    let chars: Vec<char> = data.chars().collect();
+    fn peek(&self) -> Option<(char, usize)> {
+        self.clone().next()
+    }
 
-    // Skip BOM if present
-    if chars.first() == Some(&'\u{feff}') {
-        pos += 1;
+    /// The char after the next one
+    fn second(&self) -> Option<char> {
+        let mut cursor = self.clone();
+        cursor.next();
+        cursor.next().map(|(c, _)| c)
     }
 
-    while pos < chars.len() {
-        let start_number = number;
-        let mut line = String::new();
-        let mut parendepth = 0;
+    /// Whether the next `n` chars are all `c`
+    fn starts_with(&self, c: char, n: usize) -> bool {
+        let mut cursor = self.clone();
+        (0..n).all(|_| cursor.next().map(|(next, _)| next) == Some(c))
+    }
+}
+
+/// Iterator over the logical lines of a script, reading them from the source
+/// as it goes. Comments, blank lines and warnings are gathered along the way.
+pub struct LogicalLines<'a> {
+    source: &'a str,
+    filename: String,
+    cursor: Cursor<'a>,
+    /// Current physical line
+    number: usize,
+    done: bool,
+    pub comments: Vec<Comment>,
+    /// Numbers of the lines holding only whitespace
+    pub blank_lines: Vec<usize>,
+    pub warnings: Vec<ParseError>,
+}
 
-        while pos < chars.len() {
-            let c = chars[pos];
+impl<'a> LogicalLines<'a> {
+    pub fn new(source: &'a str, filename: &str) -> Self {
+        Self::with_options(source, filename, &ParseOptions::default())
+    }
+
+    pub fn with_options(source: &'a str, filename: &str, options: &ParseOptions) -> Self {
+        let mut cursor = Cursor {
+            source,
+            pos: 0,
+            tabs: options.tabs,
+            column: 0,
+            spaces: 0,
+            trailing_newlines: 2,
+        };
+
+        // Skip BOM if present
+        if source.starts_with('\u{feff}') {
+            cursor.pos = '\u{feff}'.len_utf8();
+            cursor.column = 1;
+        }
 
+        LogicalLines {
+            source,
+            filename: filename.to_string(),
+            cursor,
+            number: 1,
+            done: false,
+            comments: Vec::new(),
+            blank_lines: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Consumes the next char, warning about expanded tabs once per line
+    fn bump(&mut self) -> Option<(char, usize)> {
+        let (c, offset) = self.cursor.next()?;
+
+        let expanded = c == ' ' && self.source.as_bytes().get(offset) == Some(&b'\t');
+        let tabs = self.cursor.tabs;
+        let repeated = self.warnings.last().map(|w| w.line_number) == Some(self.number);
+        if let (true, false, TabPolicy::ExpandTo(width)) = (expanded, repeated, tabs) {
+            self.warnings.push(
+                ParseError::new(
+                    self.filename.clone(),
+                    self.number,
+                    format!("Tab characters expanded to {} spaces", width.max(1)),
+                    None,
+                    None,
+                )
+                .with_code(Code::TabExpanded),
+            );
+        }
+
+        Some((c, offset))
+    }
+
+    /// Consumes the next char into the line, noting where its text starts
+    fn push(&mut self, line: &mut LineText<'a>, start: &mut Option<usize>) {
+        if let Some((c, offset)) = self.bump() {
+            if start.is_none() && c != ' ' {
+                *start = Some(offset);
+            }
+            line.push(c, offset);
+        }
+    }
+
+    fn error(&self, code: Code, message: &str, line: &str, line_number: usize) -> anyhow::Error {
+        ParseError::new(
+            self.filename.clone(),
+            line_number,
+            message.to_string(),
+            Some(line.to_string()),
+            Some(line.len()),
+        )
+        .with_column(column(line))
+        .with_code(code)
+        .into()
+    }
+
+    /// Reads up to the end of the next logical line, which is None when blank
+    fn scan_line(&mut self) -> Result<Option<LogicalLine<'a>>> {
+        let blank = |line: &str| line.chars().all(char::is_whitespace);
+        let start_number = self.number;
+        // Byte offsets of the text and of a trailing comment
+        let mut start = None;
+        let mut comment_start = None;
+        let mut line = LineText::new(self.source);
+        let mut parendepth = 0;
+
+        while let Some((c, offset)) = self.cursor.peek() {
             if c == '\t' {
-                let parse_error = ParseError::new(
-                    filename.to_string(),
-                    number,
-                    "Tab characters are not allowed in Ren'Py scripts".to_string(),
-                    Some(line),
-                    Some(pos),
-                );
-
-                return Err(parse_error.into());
+                return Err(self.error(
+                    Code::TabCharacter,
+                    "Tab characters are not allowed in Ren'Py scripts",
+                    line.as_str(),
+                    self.number,
+                ));
             }
 
             if c == '\n' {
-                number += 1;
+                self.number += 1;
             }
 
             if c == '\n' && parendepth == 0 {
+                self.bump();
+
+                // Only the extra newlines were left to end the line, so a
+                // string or parenthesis swallowed the real one
+                if self.cursor.peek().is_none() && !line.as_str().is_empty() {
+                    self.done = true;
+                    return Err(self.error(
+                        Code::UnterminatedLine,
+                        "is not terminated with a newline (check quotes and parenthesis)",
+                        line.as_str(),
+                        start_number,
+                    ));
+                }
+
                 // Check if line is not blank
-                let re = Regex::new(r"^\s*$").unwrap();
-                if !re.is_match(&line) {
-                    rv.push(LogicalLine {
-                        filename: filename.to_string(),
+                if !blank(line.as_str()) {
+                    let start = start.unwrap_or(offset);
+                    let end = comment_start.unwrap_or(offset);
+                    return Ok(Some(LogicalLine {
+                        filename: self.filename.clone(),
                         line_number: start_number,
-                        text: line.clone(),
-                    });
+                        text: line.into_text(),
+                        span: start..start + self.source[start..end].trim_end().len(),
+                    }));
+                }
+
+                if self.comments.last().map(|c| c.line_number) != Some(start_number) {
+                    self.blank_lines.push(start_number);
                 }
-                pos += 1;
-                break;
+                return Ok(None);
             }
 
             // Handle backslash/newline
-            if c == '\\' && pos + 1 < chars.len() && chars[pos + 1] == '\n' {
-                pos += 2;
-                number += 1;
-                line.push('\n');
+            if c == '\\' && self.cursor.second() == Some('\n') {
+                start.get_or_insert(offset);
+                self.bump();
+                self.bump();
+                self.number += 1;
+                line.push('\n', offset);
                 continue;
             }
 
@@ -86,36 +394,53 @@ pub fn parse_logical_lines(data: &str, filename: &str) -> Result<Vec<LogicalLine
 
             // Handle comments
             if c == '#' {
-                while pos < chars.len() && chars[pos] != '\n' {
-                    pos += 1;
+                comment_start = Some(offset);
+                self.bump();
+                let mut text = String::new();
+                while let Some((c, _)) = self.cursor.peek().filter(|(c, _)| *c != '\n') {
+                    self.bump();
+                    text.push(c);
                 }
+                self.comments.push(Comment {
+                    line_number: self.number,
+                    text,
+                    trailing: !blank(line.as_str()),
+                    blank_lines_before: 0,
+                });
                 continue;
             }
 
             // Handle strings
             if c == '"' || c == '\'' || c == '`' {
+                comment_start = None;
                 let delim = c;
-                line.push(c);
-                pos += 1;
 
-                let mut escape = false;
-                while pos < chars.len() {
-                    let c = chars[pos];
+                // Triple-quoted strings end at three delimiters in a row
+                let quotes = if self.cursor.starts_with(delim, 3) {
+                    3
+                } else {
+                    1
+                };
+                for _ in 0..quotes {
+                    self.push(&mut line, &mut start);
+                }
 
+                let mut escape = false;
+                while let Some((c, _)) = self.cursor.peek() {
                     if c == '\n' {
-                        number += 1;
+                        self.number += 1;
                     }
 
                     if escape {
                         escape = false;
-                        line.push(c);
-                        pos += 1;
+                        self.push(&mut line, &mut start);
                         continue;
                     }
 
-                    if c == delim {
-                        line.push(c);
-                        pos += 1;
+                    if c == delim && self.cursor.starts_with(delim, quotes) {
+                        for _ in 0..quotes {
+                            self.push(&mut line, &mut start);
+                        }
                         break;
                     }
 
@@ -123,129 +448,372 @@ pub fn parse_logical_lines(data: &str, filename: &str) -> Result<Vec<LogicalLine
                         escape = true;
                     }
 
-                    line.push(c);
-                    pos += 1;
+                    self.push(&mut line, &mut start);
                 }
                 continue;
             }
 
-            line.push(c);
-            pos += 1;
+            if !c.is_whitespace() {
+                comment_start = None;
+            }
+            self.push(&mut line, &mut start);
         }
-        if pos >= chars.len() && !line.is_empty() {
-            let parse_error = ParseError::new(
-                filename.to_string(),
-                start_number,
-                "is not terminated with a newline (check quotes and parenthesis)".to_string(),
-                Some(line),
-                Some(pos),
-            );
 
-            return Err(parse_error.into());
+        self.done = true;
+        if !line.as_str().is_empty() {
+            return Err(self.error(
+                Code::UnterminatedLine,
+                "is not terminated with a newline (check quotes and parenthesis)",
+                line.as_str(),
+                start_number,
+            ));
         }
+        Ok(None)
     }
+}
 
-    Ok(rv)
+/// Text of a logical line, borrowed from the source for as long as the
+/// scanner reads it unchanged
+struct LineText<'a> {
+    source: &'a str,
+    /// Byte range of the text while it is borrowed
+    range: Option<Range<usize>>,
+    owned: Option<String>,
 }
 
-/// Reads the specified filename and divides it into logical lines
-pub fn list_logical_lines(filename: &str) -> Result<Vec<LogicalLine>> {
-    let mut file = File::open(Path::new(filename))?;
-    let mut data = String::new();
-    file.read_to_string(&mut data)?;
+impl<'a> LineText<'a> {
+    fn new(source: &'a str) -> Self {
+        LineText {
+            source,
+            range: None,
+            owned: None,
+        }
+    }
+
+    /// Appends the char read from the offset, copying the text once it
+    /// differs from the source there
+    fn push(&mut self, c: char, offset: usize) {
+        if let Some(owned) = &mut self.owned {
+            owned.push(c);
+            return;
+        }
+
+        let start = self.range.as_ref().map_or(offset, |range| range.start);
+        let end = self.range.as_ref().map_or(offset, |range| range.end);
+        if offset == end && self.source[offset..].starts_with(c) {
+            self.range = Some(start..end + c.len_utf8());
+        } else {
+            let mut owned = self.as_str().to_string();
+            owned.push(c);
+            self.owned = Some(owned);
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match (&self.owned, &self.range) {
+            (Some(owned), _) => owned,
+            (None, Some(range)) => &self.source[range.clone()],
+            (None, None) => "",
+        }
+    }
 
-    parse_logical_lines(&data, filename)
+    fn into_text(self) -> Cow<'a, str> {
+        match (self.owned, self.range) {
+            (Some(owned), _) => Cow::Owned(owned),
+            (None, Some(range)) => Cow::Borrowed(&self.source[range]),
+            (None, None) => Cow::Borrowed(""),
+        }
+    }
 }
 
-/// Groups logical lines into blocks based on indentation
-pub fn group_logical_lines(lines: Vec<LogicalLine>) -> Result<Vec<Block>> {
-    fn depth_split(line: &str) -> (usize, String) {
-        let mut depth = 0;
-        let chars = line.chars();
-        let mut chars_copy = chars.clone();
-
-        for c in chars {
-            if c == ' ' {
-                depth += 1;
-            } else {
-                break;
+impl<'a> Iterator for LogicalLines<'a> {
+    type Item = Result<LogicalLine<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.scan_line() {
+                Ok(Some(line)) => return Some(Ok(line)),
+                Ok(None) => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
             }
         }
+        None
+    }
+}
 
-        for _ in 0..depth {
-            chars_copy.next();
-        }
+/// 1-based column just past the end of a partial logical line
+fn column(line: &str) -> usize {
+    let last = line.rsplit('\n').next().unwrap_or_default();
+    last.chars().count() + 1
+}
+
+/// Reads the specified filename and divides it into logical lines
+#[cfg(feature = "fs")]
+pub fn list_logical_lines(filename: &str) -> Result<Vec<LogicalLine<'static>>> {
+    let data = read_script(filename)?;
+    let lines = parse_logical_lines(&data, filename)?;
+    Ok(lines.into_iter().map(LogicalLine::into_owned).collect())
+}
+
+/// Reads a script file as UTF-8, or as UTF-16 when it starts with a byte order mark
+#[cfg(feature = "fs")]
+pub fn read_script(filename: &str) -> Result<String> {
+    let bytes = std::fs::read(filename)?;
+    Ok(decode(bytes, filename, EncodingFallback::Reject)?.text)
+}
+
+/// Groups logical lines into blocks based on indentation
+pub fn group_logical_lines(lines: Vec<LogicalLine<'_>>) -> Result<Vec<Block<'_>>> {
+    group_logical_lines_with_options(lines, &ParseOptions::default())
+}
 
-        (depth, chars_copy.collect())
+/// Groups logical lines into blocks, enforcing the indentation rule of the options
+pub fn group_logical_lines_with_options<'a>(
+    lines: Vec<LogicalLine<'a>>,
+    options: &ParseOptions,
+) -> Result<Vec<Block<'a>>> {
+    fn depth_split<'a>(line: &Cow<'a, str>) -> (usize, Cow<'a, str>) {
+        let depth = line.len() - line.trim_start_matches(' ').len();
+        (depth, lexer::slice(line, depth..line.len()))
     }
 
-    fn gll_core(
-        lines: &[LogicalLine],
-        start_index: usize,
-        min_depth: usize,
-    ) -> Result<(Vec<Block>, usize)> {
-        let mut rv = Vec::new();
-        let mut i = start_index;
-        let mut depth: Option<usize> = None;
+    fn indentation_error(line: &LogicalLine, expected: usize, found: usize) -> anyhow::Error {
+        ParseError::new(
+            line.filename.clone(),
+            line.line_number,
+            format!(
+                "indentation mismatch: expected {} spaces, found {}",
+                expected, found
+            ),
+            Some(line.text.to_string()),
+            Some(found),
+        )
+        .with_column(found + 1)
+        .with_code(Code::IndentMismatch)
+        .into()
+    }
 
-        while i < lines.len() {
-            let line = &lines[i];
-            let (line_depth, rest) = depth_split(&line.text);
+    struct Grouper<'l, 'a> {
+        lines: &'l [LogicalLine<'a>],
+        rule: IndentRule,
+        max_depth: Option<usize>,
+        /// Indentation step of the first nested block, for the consistent rule
+        step: Option<usize>,
+    }
 
-            if line_depth < min_depth {
-                break;
-            }
+    impl<'a> Grouper<'_, 'a> {
+        fn group(
+            &mut self,
+            start_index: usize,
+            parent_depth: Option<usize>,
+            nesting: usize,
+        ) -> Result<(Vec<Block<'a>>, usize)> {
+            let mut rv = Vec::new();
+            let mut i = start_index;
+            let mut depth: Option<usize> = None;
+            let min_depth = parent_depth.map_or(0, |depth| depth + 1);
+
+            while i < self.lines.len() {
+                let line = &self.lines[i];
+                let (line_depth, rest) = depth_split(&line.text);
+
+                if line_depth < min_depth {
+                    break;
+                }
 
-            if depth.is_none() {
-                depth = Some(line_depth);
-            }
+                if depth.is_none() {
+                    let parent = parent_depth.unwrap_or(0);
+                    let expected = match (self.rule, parent_depth) {
+                        (IndentRule::Any, _) => line_depth,
+                        (_, None) => 0,
+                        (IndentRule::Width(width), Some(_)) => parent.saturating_add(width),
+                        (IndentRule::Consistent, Some(_)) => {
+                            parent + *self.step.get_or_insert(line_depth - parent)
+                        }
+                    };
+
+                    if line_depth != expected {
+                        return Err(indentation_error(line, expected, line_depth));
+                    }
+                    depth = Some(line_depth);
+                }
 
-            if depth != Some(line_depth) {
-                let err = ParseError::new(
-                    line.filename.clone(),
-                    line.line_number,
-                    "indentation mismatch".to_string(),
-                    None,
-                    None,
-                );
-                return Err(err.into());
-            }
+                if let Some(expected) = depth.filter(|&depth| depth != line_depth) {
+                    return Err(indentation_error(line, expected, line_depth));
+                }
 
-            i += 1;
+                i += 1;
+
+                let nested = self.lines.get(i).is_some_and(|next| {
+                    next.text.len() - next.text.trim_start_matches(' ').len() > line_depth
+                });
+                if nested && self.max_depth.is_some_and(|max| nesting >= max) {
+                    let next = &self.lines[i];
+                    return Err(ParseError::new(
+                        next.filename.clone(),
+                        next.line_number,
+                        format!("blocks nested more than {} deep", nesting),
+                        Some(next.text.to_string()),
+                        None,
+                    )
+                    .with_code(Code::NestingTooDeep)
+                    .into());
+                }
 
-            let (subblocks, new_i) = gll_core(lines, i, line_depth + 1)?;
-            i = new_i;
+                let (subblocks, new_i) = self.group(i, Some(line_depth), nesting + 1)?;
+                i = new_i;
+
+                rv.push(Block {
+                    filename: line.filename.clone(),
+                    line_number: line.line_number,
+                    text: rest,
+                    indent: line_depth,
+                    span: line.span.clone(),
+                    subblocks,
+                });
+            }
 
-            rv.push(Block {
-                filename: line.filename.clone(),
-                line_number: line.line_number,
-                text: rest,
-                subblocks,
-            });
+            Ok((rv, i))
         }
-
-        Ok((rv, i))
     }
 
-    let (blocks, _) = gll_core(&lines, 0, 0)?;
+    let mut grouper = Grouper {
+        lines: &lines,
+        rule: options.indent,
+        max_depth: options.max_depth,
+        step: None,
+    };
+    let (blocks, _) = grouper.group(0, None, 0)?;
     Ok(blocks)
 }
 
+/// Parses a script file, returning its statements along with the errors of
+/// statements that couldn't be parsed
+#[cfg(feature = "fs")]
 pub fn parse_scenario_from_file(filename: &str) -> Result<(Vec<AST>, Vec<String>)> {
-    let lines = list_logical_lines(filename).unwrap();
-    let blocks = group_logical_lines(lines).unwrap();
-    let l = &mut Lexer::new(blocks.clone(), true);
+    let content = read_script(filename)?;
+    parse_scenario_from_string(&content, filename)
+}
 
-    Ok(parse_block(l))
+/// Parses a script read to the end from any source, such as an archive entry
+#[cfg(feature = "std")]
+pub fn parse_scenario_from_reader<R: Read>(
+    mut reader: R,
+    filename: &str,
+) -> Result<(Vec<AST>, Vec<String>)> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let content = decode(bytes, filename, EncodingFallback::Reject)?.text;
+    parse_scenario_from_string(&content, filename)
 }
 
+/// Parses a script held in memory. `filename` is only used in error messages,
+/// so it doesn't have to exist.
 pub fn parse_scenario_from_string(
     content: &str,
     filename: &str,
 ) -> Result<(Vec<AST>, Vec<String>)> {
     let lines = parse_logical_lines(content, filename)?;
     let blocks = group_logical_lines(lines)?;
-    let l = &mut Lexer::new(blocks.clone(), true);
+    let l = &mut Lexer::borrowed(&blocks, true);
+
+    let (ast, errors) = parse_block(l);
+    Ok((ast, errors.iter().map(ToString::to_string).collect()))
+}
 
-    Ok(parse_block(l))
+/// Parses a script with non-default settings, see [`ParseOptions`]
+pub fn parse_scenario_with_options(
+    content: &str,
+    filename: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<AST>, Vec<String>)> {
+    let script = parse_script(content, filename, options)?;
+    let errors = script
+        .diagnostics
+        .errors()
+        .map(ToString::to_string)
+        .collect();
+    Ok((script.ast, errors))
+}
+
+/// Everything parsing a script produces
+#[derive(Clone, Debug, Default)]
+pub struct ParsedScript {
+    pub ast: Vec<AST>,
+    /// Statements that failed to parse, and problems that didn't stop a
+    /// statement from being parsed, by line
+    pub diagnostics: Diagnostics,
+    /// Comments attached to statements, when [`ParseOptions::comments`] asks for them
+    pub comments: Option<Comments>,
+    /// Line endings and byte order mark of the source
+    pub format: SourceFormat,
+}
+
+/// Parses a script as the options say, failing on the first statement error
+/// in strict mode, and with [`Cancelled`] when the observer stops the parse
+pub fn parse_script(content: &str, filename: &str, options: &ParseOptions) -> Result<ParsedScript> {
+    options.observer.notify(ParseEvent::FileStarted(filename))?;
+    let scanned = scan_script(content, filename, options)?;
+    let lines = scanned.lines.len();
+    options
+        .observer
+        .notify(ParseEvent::LinesScanned(filename, lines))?;
+
+    let blocks = group_logical_lines_with_options(scanned.lines, options)?;
+    let l = &mut Lexer::borrowed(&blocks, true).with_options(options);
+    let (ast, errors) = parse_block(l);
+    if l.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+
+    let mut diagnostics = Diagnostics::default();
+    for warning in scanned.warnings {
+        diagnostics.warning(warning);
+    }
+    diagnostics.extend(l.diagnostics().iter().map(|(s, e)| (s, e.clone())));
+    for error in errors {
+        diagnostics.error(error);
+    }
+    diagnostics.sort();
+
+    if let (true, Some(error)) = (options.strict, diagnostics.errors().next()) {
+        return Err(error.clone().into());
+    }
+
+    let comments = match options.comments {
+        CommentHandling::Discard => None,
+        CommentHandling::Attach => {
+            Some(Comments::new(&ast, scanned.comments, &scanned.blank_lines))
+        }
+    };
+
+    options
+        .observer
+        .notify(ParseEvent::FileFinished(filename))?;
+    Ok(ParsedScript {
+        ast,
+        diagnostics,
+        comments,
+        format: SourceFormat::detect(content),
+    })
+}
+
+/// Like [`parse_script`], reading the bytes of a script file as the options
+/// say, see [`encoding::decode`]
+pub fn parse_script_from_bytes(
+    bytes: Vec<u8>,
+    filename: &str,
+    options: &ParseOptions,
+) -> Result<ParsedScript> {
+    let decoded = decode(bytes, filename, options.encoding)?;
+    let mut script = parse_script(&decoded.text, filename, options)?;
+
+    if let Some(warning) = decoded.warning {
+        script.diagnostics.warning(warning);
+        script.diagnostics.sort();
+    }
+    Ok(script)
 }
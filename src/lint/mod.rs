@@ -0,0 +1,245 @@
+use crate::characters::{character_usage, CharacterUsage};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::parsers::AST;
+use crate::symbols::{labels, LabelInfo};
+use crate::trivia::Comments;
+use crate::ParsedScript;
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+pub mod rules;
+pub mod text;
+
+use rules::{
+    DeadCode, DuplicateLabel, EmptyDialogue, FallThrough, HideWithoutShow, StopWithoutPlay,
+    UndefinedCharacter, UndefinedLabel, UnusedCharacter,
+};
+
+/// What rules report at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Level {
+    /// Don't run the rule
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Levels of rules by name, like `dead-code = "allow"` under `[rules]` in TOML
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LintConfig {
+    pub rules: BTreeMap<String, Level>,
+}
+
+impl LintConfig {
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+/// The script being linted, along with lookups shared by the rules
+pub struct LintContext<'a> {
+    pub ast: &'a [AST],
+    pub labels: HashMap<String, LabelInfo>,
+    pub characters: BTreeMap<String, CharacterUsage>,
+    /// Comments of the script, for `# renpy-parser: disable=rule-name` to
+    /// silence rules on the statement they're attached to
+    pub comments: Option<&'a Comments>,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(ast: &'a [AST]) -> Self {
+        LintContext {
+            ast,
+            labels: labels(ast),
+            characters: character_usage(ast),
+            comments: None,
+        }
+    }
+
+    pub fn with_comments(mut self, comments: &'a Comments) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    /// Whether a comment of the statement on the line disables the rule
+    pub fn is_suppressed(&self, rule: &str, line_number: usize) -> bool {
+        self.comments
+            .is_some_and(|comments| is_suppressed(comments, rule, line_number))
+    }
+}
+
+/// Whether one of the comments of the statement on the line disables the rule
+pub(crate) fn is_suppressed(comments: &Comments, rule: &str, line_number: usize) -> bool {
+    let Some(trivia) = comments.get(line_number) else {
+        return false;
+    };
+    trivia
+        .leading
+        .iter()
+        .chain(&trivia.trailing)
+        .filter_map(|comment| comment.text.trim().strip_prefix(SUPPRESSION))
+        .flat_map(|rules| rules.split(','))
+        .any(|name| name.trim() == rule)
+}
+
+/// Comment text disabling the rules listed after it, separated by commas
+const SUPPRESSION: &str = "renpy-parser: disable=";
+
+/// A check over a parsed script
+pub trait Rule: Send + Sync {
+    /// Name the rule is configured by, like `undefined-label`
+    fn name(&self) -> &str;
+
+    /// Level the rule reports at unless configured otherwise
+    fn default_level(&self) -> Level {
+        Level::Warn
+    }
+
+    /// Problems found in the script. Their severity is replaced by the level
+    /// the rule runs at.
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic>;
+}
+
+/// Rules to run, each at its configured level
+#[derive(Clone)]
+pub struct Linter {
+    rules: Vec<Arc<dyn Rule>>,
+    levels: HashMap<String, Level>,
+}
+
+impl Default for Linter {
+    /// Every rule this crate ships, at their default levels
+    fn default() -> Self {
+        Linter::new()
+            .with(UndefinedLabel)
+            .with(DuplicateLabel)
+            .with(FallThrough)
+            .with(DeadCode)
+            .with(UndefinedCharacter)
+            .with(UnusedCharacter)
+            .with(EmptyDialogue)
+            .with(HideWithoutShow)
+            .with(StopWithoutPlay)
+    }
+}
+
+impl Linter {
+    /// A linter without any rules
+    pub fn new() -> Self {
+        Linter {
+            rules: Vec::new(),
+            levels: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, rule: impl Rule + 'static) -> Self {
+        self.push(rule);
+        self
+    }
+
+    pub fn push(&mut self, rule: impl Rule + 'static) {
+        self.rules.push(Arc::new(rule));
+    }
+
+    /// Runs the named rule at the level instead of its default one
+    pub fn with_level(mut self, name: &str, level: Level) -> Self {
+        self.set_level(name, level);
+        self
+    }
+
+    pub fn set_level(&mut self, name: &str, level: Level) {
+        self.levels.insert(name.to_string(), level);
+    }
+
+    /// Runs the rules at the levels of the config. Fails on rules the linter
+    /// doesn't have, which are likely misspelled.
+    pub fn with_config(mut self, config: &LintConfig) -> Result<Self> {
+        for (name, level) in &config.rules {
+            if !self.rules().any(|rule| rule.name() == name) {
+                bail!("unknown lint rule '{}'", name);
+            }
+            self.set_level(name, *level);
+        }
+        Ok(self)
+    }
+
+    pub fn level(&self, rule: &dyn Rule) -> Level {
+        self.levels
+            .get(rule.name())
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    /// Runs the rules over the script, returning their findings in document order
+    pub fn check(&self, ast: &[AST]) -> Vec<Diagnostic> {
+        self.check_context(&LintContext::new(ast))
+    }
+
+    /// Like [`Linter::check`], honoring suppression comments when the script
+    /// was parsed with its comments attached
+    pub fn check_script(&self, script: &ParsedScript) -> Vec<Diagnostic> {
+        let ctx = LintContext::new(&script.ast);
+        match &script.comments {
+            Some(comments) => self.check_context(&ctx.with_comments(comments)),
+            None => self.check_context(&ctx),
+        }
+    }
+
+    pub fn check_context(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv: Vec<Diagnostic> = self
+            .findings(ctx)
+            .into_iter()
+            .filter(|(rule, diagnostic)| !ctx.is_suppressed(rule, diagnostic.node.index))
+            .map(|(_, diagnostic)| diagnostic)
+            .collect();
+
+        // Paths order nodes the way the document does
+        rv.sort_by(|a, b| a.node.path.cmp(&b.node.path));
+        rv
+    }
+
+    /// Findings of the rules that run, at their levels, with the name of the
+    /// rule finding them and whether comments suppress them or not
+    pub(crate) fn findings(&self, ctx: &LintContext) -> Vec<(&str, Diagnostic)> {
+        let mut rv = Vec::new();
+        for rule in self.rules() {
+            let severity = match self.level(rule) {
+                Level::Allow => continue,
+                Level::Warn => Severity::Warning,
+                Level::Deny => Severity::Error,
+            };
+            rv.extend(rule.check(ctx).into_iter().map(|diagnostic| {
+                let diagnostic = Diagnostic {
+                    severity,
+                    ..diagnostic
+                };
+                (rule.name(), diagnostic)
+            }));
+        }
+        rv
+    }
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.rules().map(|rule| (rule.name(), self.level(rule))))
+            .finish()
+    }
+}
+
+/// Runs every rule this crate ships at its default level
+pub fn lint(ast: &[AST]) -> Vec<Diagnostic> {
+    Linter::default().check(ast)
+}
@@ -0,0 +1,453 @@
+use super::{Level, LintContext, Rule};
+#[cfg(feature = "fs")]
+use crate::assets::GameAssets;
+use crate::characters::BUILTIN_CHARACTERS;
+use crate::diagnostics::{Code, Diagnostic};
+use crate::parsers::{walk, NodeRef, AST};
+use crate::symbols::{qualified_name, walk_scoped};
+#[cfg(feature = "fs")]
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Jumps to labels that aren't defined, suggesting the closest defined name
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UndefinedLabel;
+
+impl Rule for UndefinedLabel {
+    fn name(&self) -> &str {
+        "undefined-label"
+    }
+
+    fn default_level(&self) -> Level {
+        Level::Deny
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk_scoped(ctx.ast, &mut |path, node, global| {
+            if let AST::Jump(_, target, false) = node {
+                if !ctx.labels.contains_key(&qualified_name(target, global)) {
+                    let mut message = format!("jump to undefined label '{}'", target);
+                    let names = ctx.labels.keys().map(String::as_str);
+                    if let Some(name) = closest(target, names) {
+                        message.push_str(&format!("; did you mean '{}'?", name));
+                    }
+                    rv.push(Diagnostic::error(
+                        NodeRef::new(path, node),
+                        Code::UndefinedLabel,
+                        message,
+                    ));
+                }
+            }
+        });
+        rv
+    }
+}
+
+/// Labels defined more than once, which Ren'Py resolves to one of the
+/// definitions without saying so
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DuplicateLabel;
+
+impl Rule for DuplicateLabel {
+    fn name(&self) -> &str {
+        "duplicate-label"
+    }
+
+    fn default_level(&self) -> Level {
+        Level::Deny
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        // First definition of each label, by qualified name
+        let mut first: HashMap<String, NodeRef> = HashMap::new();
+        walk_scoped(ctx.ast, &mut |path, node, global| {
+            if let AST::Label(_, name, _, _) = node {
+                let name = qualified_name(name, global);
+                match first.get(&name) {
+                    Some(first) => rv.push(
+                        Diagnostic::error(
+                            NodeRef::new(path, node),
+                            Code::DuplicateLabel,
+                            format!("label '{}' is defined more than once", name),
+                        )
+                        .with_related(first.clone(), format!("'{}' is first defined here", name)),
+                    ),
+                    None => {
+                        first.insert(name, NodeRef::new(path, node));
+                    }
+                }
+            }
+        });
+        rv
+    }
+}
+
+/// Labels whose body ends without a return or jump, so the story runs on
+/// into the label after them. Labels at the end of a script aren't reported,
+/// as the story returns there.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FallThrough;
+
+impl Rule for FallThrough {
+    fn name(&self) -> &str {
+        "fall-through"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        fall_through(ctx.ast, &mut Vec::new(), None, &mut rv);
+        rv
+    }
+}
+
+/// Reports the labels of the block falling through, where `follow` is the
+/// label the story continues with after the block
+fn fall_through(
+    block: &[AST],
+    path: &mut Vec<usize>,
+    follow: Option<&str>,
+    rv: &mut Vec<Diagnostic>,
+) {
+    for (position, node) in block.iter().enumerate() {
+        path.push(position);
+        match node {
+            AST::Label(_, name, body, _) => {
+                // Defines and init blocks run at startup, not when reached
+                let next = block[position + 1..]
+                    .iter()
+                    .find(|node| !matches!(node, AST::Define(..) | AST::Init(..)));
+                let next = match next {
+                    Some(AST::Label(_, next, _, _)) => Some(next.as_str()),
+                    Some(_) => None,
+                    None => follow,
+                };
+
+                let ends = matches!(
+                    body.last(),
+                    Some(AST::Return(..) | AST::Jump(..) | AST::Label(..) | AST::Error { .. })
+                );
+                if let (false, Some(next)) = (ends, next) {
+                    rv.push(Diagnostic::warning(
+                        NodeRef::new(path, node),
+                        Code::FallThrough,
+                        format!(
+                            "label '{}' ends without a return or jump, falling through into '{}'",
+                            name, next
+                        ),
+                    ));
+                }
+                fall_through(body, path, next, rv);
+            }
+            AST::Init(_, body, _) => fall_through(body, path, None, rv),
+            _ => {}
+        }
+        path.pop();
+    }
+}
+
+/// Statements after a return or jump in the same block, which can never run.
+/// Labels after them are entry points of their own, and defines and init
+/// blocks run at startup.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeadCode;
+
+impl Rule for DeadCode {
+    fn name(&self) -> &str {
+        "dead-code"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        dead_code(ctx.ast, &mut Vec::new(), &mut rv);
+        rv
+    }
+}
+
+fn dead_code(block: &[AST], path: &mut Vec<usize>, rv: &mut Vec<Diagnostic>) {
+    // Line of the return or jump ending the flow of the block, until a label
+    let mut ended: Option<(usize, &str)> = None;
+
+    for (position, node) in block.iter().enumerate() {
+        path.push(position);
+        match node {
+            AST::Label(..) => ended = None,
+            AST::Define(..) | AST::Init(..) => {}
+            _ => {
+                if let Some((line, statement)) = ended.take() {
+                    rv.push(Diagnostic::warning(
+                        NodeRef::new(path, node),
+                        Code::DeadCode,
+                        format!("unreachable after the {} on line {}", statement, line),
+                    ));
+                }
+            }
+        }
+        // Only the first statement of a run is reported
+        ended = match node {
+            AST::Return(line, _) => Some((*line, "return")),
+            AST::Jump(line, _, _) => Some((*line, "jump")),
+            _ => ended,
+        };
+
+        if let Some(body) = node.block() {
+            dead_code(body, path, rv);
+        }
+        path.pop();
+    }
+}
+
+/// Speakers not defined as a Character, apart from Ren'Py's built-in ones
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UndefinedCharacter;
+
+impl Rule for UndefinedCharacter {
+    fn name(&self) -> &str {
+        "undefined-character"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk(ctx.ast, &mut |path, node| {
+            if let AST::Say(_, Some(who), ..) = node {
+                let builtin = BUILTIN_CHARACTERS.contains(&who.as_str());
+                if !builtin && !ctx.characters.get(who).is_some_and(|c| c.is_defined()) {
+                    rv.push(Diagnostic::warning(
+                        NodeRef::new(path, node),
+                        Code::UndefinedCharacter,
+                        format!("speaker '{}' is not defined as a Character", who),
+                    ));
+                }
+            }
+        });
+        rv
+    }
+}
+
+/// Characters defined but never speaking
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnusedCharacter;
+
+impl Rule for UnusedCharacter {
+    fn name(&self) -> &str {
+        "unused-character"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk(ctx.ast, &mut |path, node| {
+            if let Some(name) = node.defined_character() {
+                if ctx.characters.get(name).is_none_or(|c| c.lines == 0) {
+                    rv.push(Diagnostic::warning(
+                        NodeRef::new(path, node),
+                        Code::UnusedCharacter,
+                        format!("character '{}' is defined but never speaks", name),
+                    ));
+                }
+            }
+        });
+        rv
+    }
+}
+
+/// Lines of dialogue showing nothing but whitespace, interpolations and text tags
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmptyDialogue;
+
+impl Rule for EmptyDialogue {
+    fn name(&self) -> &str {
+        "empty-dialogue"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk(ctx.ast, &mut |path, node| {
+            if let AST::Say(_, _, text, _) = node {
+                if is_blank(text) {
+                    rv.push(Diagnostic::warning(
+                        NodeRef::new(path, node),
+                        Code::EmptyDialogue,
+                        format!("dialogue {:?} has no text of its own", text),
+                    ));
+                }
+            }
+        });
+        rv
+    }
+}
+
+/// Whether the text is only whitespace, `[interpolations]` and `{text tags}`.
+/// Doubled brackets are literal ones.
+fn is_blank(text: &str) -> bool {
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let close = match c {
+            '[' => ']',
+            '{' => '}',
+            c if c.is_whitespace() => continue,
+            _ => return false,
+        };
+        if chars.next_if_eq(&c).is_some() {
+            return false;
+        }
+        if !chars.by_ref().any(|c| c == close) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Hides of images that aren't shown, tracked in document order ignoring jumps
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HideWithoutShow;
+
+impl Rule for HideWithoutShow {
+    fn name(&self) -> &str {
+        "hide-without-show"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        let mut shown: HashSet<&str> = HashSet::new();
+        walk(ctx.ast, &mut |path, node| match node {
+            AST::Scene(_, image, _, _) => {
+                shown.clear();
+                if let Some(image) = image {
+                    shown.insert(image_tag(image));
+                }
+            }
+            AST::Show(_, image, _) => {
+                shown.insert(image_tag(image));
+            }
+            AST::Hide(_, image, _) if !shown.remove(image_tag(image)) => {
+                rv.push(Diagnostic::warning(
+                    NodeRef::new(path, node),
+                    Code::HideWithoutShow,
+                    format!("hide of '{}' which was never shown", image),
+                ));
+            }
+            _ => {}
+        });
+        rv
+    }
+}
+
+/// Stops of audio channels that aren't playing, tracked in document order ignoring jumps
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StopWithoutPlay;
+
+impl Rule for StopWithoutPlay {
+    fn name(&self) -> &str {
+        "stop-without-play"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        let mut playing: HashSet<&str> = HashSet::new();
+        walk(ctx.ast, &mut |path, node| match node {
+            AST::Play(_, channel, _) => {
+                playing.insert(channel.as_str());
+            }
+            AST::Stop(_, channel, _, _) if !playing.remove(channel.as_str()) => {
+                rv.push(Diagnostic::warning(
+                    NodeRef::new(path, node),
+                    Code::StopWithoutPlay,
+                    format!("stop on channel '{}' which is not playing", channel),
+                ));
+            }
+            _ => {}
+        });
+        rv
+    }
+}
+
+/// Images shown and audio played without a file in the game directory, see
+/// [`AssetReport::missing`](crate::assets::AssetReport::missing) for how files are matched
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub struct MissingAsset {
+    files: GameAssets,
+}
+
+#[cfg(feature = "fs")]
+impl MissingAsset {
+    /// Looks up the files of the game directory, once for every script checked
+    pub fn new(game_dir: &Path) -> Result<Self> {
+        Ok(MissingAsset {
+            files: GameAssets::load(game_dir)?,
+        })
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Rule for MissingAsset {
+    fn name(&self) -> &str {
+        "missing-asset"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk(ctx.ast, &mut |path, node| {
+            let message = match node {
+                AST::Show(_, image, _) | AST::Scene(_, Some(image), ..)
+                    if !self.files.has_image(image) =>
+                {
+                    format!("no file under images/ for image '{}'", image)
+                }
+                AST::Play(_, _, filename) if !self.files.has_audio(filename) => {
+                    format!("audio file '{}' not found", filename)
+                }
+                _ => return,
+            };
+            rv.push(Diagnostic::warning(
+                NodeRef::new(path, node),
+                Code::MissingAsset,
+                message,
+            ));
+        });
+        rv
+    }
+}
+
+/// Ren'Py addresses shown images by their first word
+fn image_tag(image: &str) -> &str {
+    image.split_whitespace().next().unwrap_or_default()
+}
+
+/// The name nearest to `name` by edit distance, if any is within a third of
+/// its length. Ties go to the name sorting first.
+fn closest<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    names
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance counting characters, with swapping two neighbouring
+/// characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
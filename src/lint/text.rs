@@ -0,0 +1,83 @@
+use super::{LintContext, Rule};
+use crate::diagnostics::{Code, Diagnostic};
+use crate::parsers::{walk_labeled, NodeRef, AST};
+use std::fmt;
+
+/// Line of dialogue handed to a [`TextChecker`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogueText<'a> {
+    pub text: &'a str,
+    /// Character saying the line, if not the narrator
+    pub speaker: Option<&'a str>,
+    /// Innermost label the line is in
+    pub label: Option<&'a str>,
+    pub node: NodeRef,
+}
+
+/// Check over dialogue only, such as a spellchecker or a style guide
+pub trait TextChecker: Send + Sync {
+    /// Problems found in the line, as messages
+    fn check(&self, line: &DialogueText) -> Vec<String>;
+}
+
+impl<F> TextChecker for F
+where
+    F: Fn(&DialogueText) -> Vec<String> + Send + Sync,
+{
+    fn check(&self, line: &DialogueText) -> Vec<String> {
+        self(line)
+    }
+}
+
+/// Rule running a [`TextChecker`] over every line of dialogue, reporting
+/// its messages under [`Code::TextCheck`]
+pub struct CheckText<T> {
+    name: String,
+    checker: T,
+}
+
+impl<T: TextChecker> CheckText<T> {
+    /// The rule is configured and suppressed by `name`, like `spelling`
+    pub fn new(name: &str, checker: T) -> Self {
+        CheckText {
+            name: name.to_string(),
+            checker,
+        }
+    }
+}
+
+impl<T: TextChecker> Rule for CheckText<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk_labeled(ctx.ast, &mut |path, node, label| {
+            if let AST::Say(_, speaker, text, _) = node {
+                let line = DialogueText {
+                    text,
+                    speaker: speaker.as_deref(),
+                    label,
+                    node: NodeRef::new(path, node),
+                };
+                for message in self.checker.check(&line) {
+                    rv.push(Diagnostic::warning(
+                        line.node.clone(),
+                        Code::TextCheck,
+                        message,
+                    ));
+                }
+            }
+        });
+        rv
+    }
+}
+
+impl<T> fmt::Debug for CheckText<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CheckText")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
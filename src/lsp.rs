@@ -0,0 +1,511 @@
+use crate::characters::character_usage;
+use crate::diagnostics::{Code, Severity};
+use crate::highlight::{highlight, SemanticKind};
+use crate::lint::Linter;
+use crate::options::{CommentHandling, ParseOptions};
+use crate::parse_script;
+use crate::parsers::{ParseError, AST};
+use crate::symbols::{labels, qualified_name, walk_scoped, LabelInfo};
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::Request as RequestType;
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, SemanticTokensFullRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Location, NumberOrString,
+    OneOf, Position, PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType,
+    SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensParams, SemanticTokensResult, SemanticTokensServerCapabilities,
+    ServerCapabilities, SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+use std::collections::HashMap;
+
+/// An open script, parsed on every change
+struct Document {
+    text: String,
+    ast: Vec<AST>,
+}
+
+/// Token types of the semantic tokens legend, indexed by [`token_type`]
+const TOKEN_TYPES: [SemanticTokenType; 8] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::STRING,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::VARIABLE,
+];
+
+/// Open scripts by URI
+type Documents = HashMap<String, Document>;
+
+/// Serves the language server protocol over stdin and stdout until the client exits
+pub fn serve_stdio() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Answers a client on the connection, from initialization until shutdown
+pub fn run(connection: &Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..SemanticTokensOptions::default()
+            },
+        )),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = respond(&documents, request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                if let Some(params) = update(&mut documents, notification)? {
+                    let notification = Notification::new(PublishDiagnostics::METHOD.into(), params);
+                    connection.sender.send(notification.into())?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Applies a document notification, returning the diagnostics to publish
+fn update(
+    documents: &mut Documents,
+    notification: Notification,
+) -> Result<Option<PublishDiagnosticsParams>> {
+    let (uri, text) = match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+            (params.text_document.uri, Some(params.text_document.text))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+            // Full sync sends the whole text as the last change
+            let text = params.content_changes.into_iter().last().map(|c| c.text);
+            (params.text_document.uri, text)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+            documents.remove(params.text_document.uri.as_str());
+            (params.text_document.uri, None)
+        }
+        _ => return Ok(None),
+    };
+
+    let diagnostics = match text {
+        Some(text) => {
+            let (document, diagnostics) = parse(&uri, text);
+            documents.insert(uri.as_str().to_string(), document);
+            diagnostics
+        }
+        None => Vec::new(),
+    };
+
+    Ok(Some(PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    }))
+}
+
+/// Parses a script, reporting its parse errors and the findings of the
+/// default [`Linter`] not suppressed by comments
+fn parse(uri: &Uri, text: String) -> (Document, Vec<Diagnostic>) {
+    let options = ParseOptions {
+        recover: true,
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let filename = uri.path().as_str();
+
+    let (ast, errors, found) = match parse_script(&text, filename, &options) {
+        Ok(parsed) => {
+            let found = Linter::default().check_script(&parsed);
+            let errors = parsed
+                .diagnostics
+                .iter()
+                .map(|(s, e)| (s, e.clone()))
+                .collect();
+            (parsed.ast, errors, found)
+        }
+        Err(err) => {
+            let error = match err.downcast::<ParseError>() {
+                Ok(error) => error,
+                Err(err) => ParseError::new(filename.to_string(), 1, err.to_string(), None, None),
+            };
+            (Vec::new(), vec![(Severity::Error, error)], Vec::new())
+        }
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = errors
+        .into_iter()
+        .map(|(severity, error)| {
+            let start = error.column.map_or(0, |column| column - 1);
+            let range = line_range(&text, error.line_number, start);
+            diagnostic(range, severity, error.code, error.message)
+        })
+        .collect();
+
+    diagnostics.extend(found.into_iter().map(|found| {
+        let range = line_range(&text, found.node.index, 0);
+        let related = found
+            .related
+            .into_iter()
+            .map(|related| DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), line_range(&text, related.node.index, 0)),
+                message: related.message,
+            })
+            .collect::<Vec<_>>();
+        Diagnostic {
+            related_information: (!related.is_empty()).then_some(related),
+            ..diagnostic(range, found.severity, found.code, found.message)
+        }
+    }));
+
+    (Document { text, ast }, diagnostics)
+}
+
+fn diagnostic(range: Range, severity: Severity, code: Code, message: String) -> Diagnostic {
+    let severity = match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(code.to_string())),
+        source: Some("renpy".to_string()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+fn respond(documents: &Documents, request: Request) -> Response {
+    match request.method.as_str() {
+        GotoDefinition::METHOD => handle::<GotoDefinition>(request, |p| definition(documents, p)),
+        DocumentSymbolRequest::METHOD => {
+            handle::<DocumentSymbolRequest>(request, |p| symbols(documents, p))
+        }
+        Completion::METHOD => handle::<Completion>(request, |p| completion(documents, p)),
+        SemanticTokensFullRequest::METHOD => {
+            handle::<SemanticTokensFullRequest>(request, |p| semantic_tokens(documents, p))
+        }
+        _ => Response::new_err(
+            request.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unsupported request {}", request.method),
+        ),
+    }
+}
+
+fn handle<R: RequestType>(request: Request, f: impl FnOnce(R::Params) -> R::Result) -> Response {
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(request.id, f(params)),
+        Err(err) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, err.to_string()),
+    }
+}
+
+/// Label or character named by the word under the cursor
+fn definition(
+    documents: &Documents,
+    params: GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let document = documents.get(uri.as_str())?;
+    let word = word_at(
+        &document.text,
+        params.text_document_position_params.position,
+    )?;
+
+    let line_number = params.text_document_position_params.position.line as usize + 1;
+    let global = global_label_at(&document.ast, line_number);
+    let range = match labels(&document.ast).get(&qualified_name(&word, global)) {
+        Some(label) => label_range(document, label),
+        None => {
+            let line_number = character_usage(&document.ast)
+                .get(&word)?
+                .definition
+                .as_ref()?
+                .index;
+            name_range(&document.text, line_number, &word)
+        }
+    };
+
+    Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+}
+
+/// Global label local names on the 1-based line belong to
+fn global_label_at(ast: &[AST], line_number: usize) -> Option<&str> {
+    let mut rv = None;
+    walk_scoped(ast, &mut |_, node, global| {
+        if node.index() <= line_number {
+            rv = global;
+        }
+    });
+    rv
+}
+
+/// Range of a label's name as written in its statement, which for local
+/// labels may leave out the global label
+fn label_range(document: &Document, label: &LabelInfo) -> Range {
+    match label.node.resolve(&document.ast) {
+        Some(AST::Label(_, name, _, _)) => name_range(&document.text, label.line_number, name),
+        _ => line_range(&document.text, label.line_number, 0),
+    }
+}
+
+/// Labels and defined characters, in document order
+#[allow(deprecated)]
+fn symbols(documents: &Documents, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+    let document = documents.get(params.text_document.uri.as_str())?;
+    let symbol = |name: String, detail: Option<String>, kind, line_number, selection_range| {
+        let range = line_range(&document.text, line_number, 0);
+        DocumentSymbol {
+            selection_range,
+            name,
+            detail,
+            kind,
+            tags: None,
+            deprecated: None,
+            range,
+            children: None,
+        }
+    };
+
+    let mut rv: Vec<DocumentSymbol> = labels(&document.ast)
+        .into_iter()
+        .map(|(name, label)| {
+            let selection_range = label_range(document, &label);
+            symbol(
+                name,
+                label.parameters,
+                SymbolKind::FUNCTION,
+                label.line_number,
+                selection_range,
+            )
+        })
+        .collect();
+    for (name, usage) in character_usage(&document.ast) {
+        if let Some(definition) = usage.definition {
+            let selection_range = name_range(&document.text, definition.index, &name);
+            let line_number = definition.index;
+            rv.push(symbol(
+                name,
+                None,
+                SymbolKind::VARIABLE,
+                line_number,
+                selection_range,
+            ));
+        }
+    }
+    rv.sort_by_key(|symbol| symbol.range.start.line);
+
+    Some(DocumentSymbolResponse::Nested(rv))
+}
+
+/// Label names after `jump`, otherwise labels and defined characters
+fn completion(documents: &Documents, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position.position;
+    let document = documents.get(params.text_document_position.text_document.uri.as_str())?;
+    let line = document
+        .text
+        .lines()
+        .nth(position.line as usize)
+        .unwrap_or_default();
+    let before = &line[..byte_offset(line, position.character)];
+    let after_jump = before.trim_start().starts_with("jump ");
+
+    let mut items: Vec<CompletionItem> = labels(&document.ast)
+        .into_keys()
+        .map(|name| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::FUNCTION),
+            ..CompletionItem::default()
+        })
+        .collect();
+    if !after_jump {
+        items.extend(
+            character_usage(&document.ast)
+                .into_iter()
+                .filter(|(_, usage)| usage.is_defined())
+                .map(|(name, _)| CompletionItem {
+                    label: name,
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    ..CompletionItem::default()
+                }),
+        );
+    }
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+
+    Some(CompletionResponse::Array(items))
+}
+
+/// Tokens of the whole script, see [`highlight`]
+fn semantic_tokens(
+    documents: &Documents,
+    params: SemanticTokensParams,
+) -> Option<SemanticTokensResult> {
+    let text = &documents.get(params.text_document.uri.as_str())?.text;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    let mut data = Vec::new();
+    let (mut last_line, mut last_start) = (0, 0);
+    for token in highlight(text) {
+        let Some(token_type) = token_type(token.kind) else {
+            continue;
+        };
+
+        let line_index = line_starts.partition_point(|&start| start <= token.span.start) - 1;
+        let mut start = utf16_len(&text[line_starts[line_index]..token.span.start]);
+        // Clients may not support tokens spanning lines
+        for (line, part) in (line_index as u32..).zip(token.text.split('\n')) {
+            let length = utf16_len(part.trim_end_matches('\r'));
+            if length > 0 {
+                data.push(SemanticToken {
+                    delta_line: line - last_line,
+                    delta_start: if line == last_line {
+                        start - last_start
+                    } else {
+                        start
+                    },
+                    length,
+                    token_type,
+                    token_modifiers_bitset: 0,
+                });
+                (last_line, last_start) = (line, start);
+            }
+            start = 0;
+        }
+    }
+
+    Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    }))
+}
+
+/// Index into [`TOKEN_TYPES`], None for punctuation
+fn token_type(kind: SemanticKind) -> Option<u32> {
+    let token_type = match kind {
+        SemanticKind::Keyword => SemanticTokenType::KEYWORD,
+        SemanticKind::Speaker => SemanticTokenType::CLASS,
+        SemanticKind::Dialogue | SemanticKind::String | SemanticKind::Audio => {
+            SemanticTokenType::STRING
+        }
+        SemanticKind::Image => SemanticTokenType::TYPE,
+        SemanticKind::Label => SemanticTokenType::FUNCTION,
+        SemanticKind::Comment => SemanticTokenType::COMMENT,
+        SemanticKind::Number => SemanticTokenType::NUMBER,
+        SemanticKind::Name => SemanticTokenType::VARIABLE,
+        SemanticKind::Punct => return None,
+    };
+    TOKEN_TYPES
+        .iter()
+        .position(|t| *t == token_type)
+        .map(|i| i as u32)
+}
+
+/// Name around a position
+fn word_at(text: &str, position: Position) -> Option<String> {
+    let line = text.lines().nth(position.line as usize)?;
+    let offset = byte_offset(line, position.character);
+
+    let start = line[..offset]
+        .char_indices()
+        .rfind(|&(_, c)| !is_word_char(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let end = line[offset..]
+        .find(|c| !is_word_char(c))
+        .map_or(line.len(), |i| offset + i);
+
+    let word = &line[start..end];
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+/// Byte offset of a position given in UTF-16 code units, as LSP counts them
+fn byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+fn utf16_len(text: &str) -> u32 {
+    text.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+/// Range from a char column to the end of a 1-based line
+fn line_range(text: &str, line_number: usize, column: usize) -> Range {
+    let line_index = line_number.saturating_sub(1);
+    let line = text.lines().nth(line_index).unwrap_or_default();
+    let start: String = line.chars().take(column).collect();
+    let line_index = line_index as u32;
+    Range::new(
+        Position::new(line_index, utf16_len(&start)),
+        Position::new(line_index, utf16_len(line)),
+    )
+}
+
+/// Range of a name on a 1-based line, or the whole line when it isn't found as a word
+fn name_range(text: &str, line_number: usize, name: &str) -> Range {
+    let line_index = line_number.saturating_sub(1);
+    let line = text.lines().nth(line_index).unwrap_or_default();
+    let whole_word = |&(offset, _): &(usize, &str)| {
+        !line[..offset].ends_with(is_word_char)
+            && !line[offset + name.len()..].starts_with(is_word_char)
+    };
+    let Some((offset, _)) = line.match_indices(name).find(whole_word) else {
+        return line_range(text, line_number, 0);
+    };
+
+    let start = utf16_len(&line[..offset]);
+    let line_index = line_index as u32;
+    Range::new(
+        Position::new(line_index, start),
+        Position::new(line_index, start + utf16_len(name)),
+    )
+}
+
+/// Chars of names, dotted parts included
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
@@ -0,0 +1,101 @@
+/// Builds a `Vec<AST>` from inline Ren'Py-like statements at compile time.
+///
+/// Statements following `label name:` belong to that label until the next
+/// `label` or `define`. Image names are a single identifier or a string
+/// literal, e.g. `show eileen` or `show "eileen happy"`.
+///
+/// ```
+/// use renpy_parser::renpy;
+///
+/// let ast = renpy! {
+///     define e = Character("Eileen")
+///     label start:
+///         e "Hello"
+///         jump end
+///     label end:
+///         return
+/// };
+/// ```
+#[macro_export]
+macro_rules! renpy {
+    (@script $s:expr;) => {
+        $s.build()
+    };
+    (@script $s:expr; define $name:ident = $ctor:ident ( $($args:tt)* ) $($rest:tt)*) => {
+        $crate::renpy!(@script $s.define(concat!(stringify!($name), " = ", stringify!($ctor), "(", stringify!($($args)*), ")")); $($rest)*)
+    };
+    (@script $s:expr; define $name:ident = $value:literal $($rest:tt)*) => {
+        $crate::renpy!(@script $s.define(concat!(stringify!($name), " = ", stringify!($value))); $($rest)*)
+    };
+    (@script $s:expr; label $name:ident : $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $crate::builder::Script::new(); $($rest)*)
+    };
+    (@script $s:expr; $($rest:tt)*) => {
+        compile_error!(concat!("unsupported statement in renpy!: ", stringify!($($rest)*)))
+    };
+
+    (@label $s:expr; $name:ident; $b:expr;) => {
+        $crate::renpy!(@script $s.label(stringify!($name), |_| $b);)
+    };
+    (@label $s:expr; $name:ident; $b:expr; label $($rest:tt)*) => {
+        $crate::renpy!(@script $s.label(stringify!($name), |_| $b); label $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; define $($rest:tt)*) => {
+        $crate::renpy!(@script $s.label(stringify!($name), |_| $b); define $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; jump $target:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.jump(stringify!($target)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; return $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.return_(); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; scene $image:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.scene(stringify!($image)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; scene $image:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.scene($image); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; show $image:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.show(stringify!($image)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; show $image:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.show($image); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; hide $image:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.hide(stringify!($image)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; hide $image:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.hide($image); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; play $channel:ident $file:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.play(stringify!($channel), $file); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; stop $channel:ident fadeout $length:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.stop(stringify!($channel), Some($length as f32)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; stop $channel:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.stop(stringify!($channel), None); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; game_mechanic $mechanic:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.game_mechanic($mechanic); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; llm_generate $who:ident $prompt:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.llm_generate(stringify!($who), Some($prompt)); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; llm_generate $who:ident $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.llm_generate(stringify!($who), None); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; $who:ident $what:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.say(stringify!($who), $what); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; $what:literal $($rest:tt)*) => {
+        $crate::renpy!(@label $s; $name; $b.narrate($what); $($rest)*)
+    };
+    (@label $s:expr; $name:ident; $b:expr; $($rest:tt)*) => {
+        compile_error!(concat!("unsupported statement in renpy!: ", stringify!($($rest)*)))
+    };
+
+    ($($script:tt)*) => {
+        $crate::renpy!(@script $crate::builder::Script::new(); $($script)*)
+    };
+}
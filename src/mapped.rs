@@ -0,0 +1,70 @@
+use crate::borrowed::{parse_borrowed, BorrowedAST};
+use crate::encoding::{decode, Decoded, EncodingFallback};
+use anyhow::Result;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// A script file mapped into memory rather than read onto the heap, so large
+/// scripts are paged in by the OS as they're parsed.
+///
+/// The file must not be changed by anyone while it's mapped.
+pub struct MappedScript {
+    /// None for empty files, which can't be mapped
+    map: Option<Mmap>,
+    /// Text of files that aren't UTF-8, decoded onto the heap
+    decoded: Option<Decoded>,
+}
+
+impl MappedScript {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, EncodingFallback::default())
+    }
+
+    /// Like [`MappedScript::open`], reading scripts that aren't UTF-8 as the
+    /// fallback says, see [`decode`]
+    pub fn open_with(path: &Path, fallback: EncodingFallback) -> Result<Self> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(MappedScript {
+                map: None,
+                decoded: None,
+            });
+        }
+
+        // SAFETY: the mapping is only read, and the caller keeps the file
+        // unchanged while it's mapped, as documented on the type
+        let map = unsafe { Mmap::map(&file)? };
+        let decoded = match std::str::from_utf8(&map) {
+            Ok(_) => None,
+            Err(_) => Some(decode(map.to_vec(), &path.to_string_lossy(), fallback)?),
+        };
+        Ok(MappedScript {
+            map: Some(map),
+            decoded,
+        })
+    }
+
+    /// The text of the script, borrowed from the mapping when it's UTF-8
+    pub fn source(&self) -> &str {
+        if let Some(decoded) = &self.decoded {
+            return &decoded.text;
+        }
+
+        let bytes = self.map.as_deref().unwrap_or_default();
+        // SAFETY: the mapping was checked to be UTF-8 when it was opened, and
+        // the file is kept unchanged since
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+
+    /// Parses the script into an AST borrowing its strings from the mapping,
+    /// without building an owned AST along the way. A warning about how the
+    /// script was decoded comes first among the errors.
+    pub fn parse(&self, filename: &str) -> Result<(Vec<BorrowedAST<'_>>, Vec<String>)> {
+        let (ast, mut errors) = parse_borrowed(self.source(), filename)?;
+        if let Some(warning) = self.decoded.as_ref().and_then(|d| d.warning.as_ref()) {
+            errors.insert(0, warning.to_string());
+        }
+        Ok((ast, errors))
+    }
+}
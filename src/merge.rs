@@ -0,0 +1,64 @@
+use crate::parsers::{shift_indices, walk, NodeRef, AST};
+use crate::symbols::labels;
+use std::error;
+use std::fmt;
+
+/// Label defined both in the base script and in the addition
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateLabel {
+    pub name: String,
+    pub base: NodeRef,
+    pub addition: NodeRef,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConflict {
+    pub duplicates: Vec<DuplicateLabel>,
+}
+
+impl error::Error for MergeConflict {}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.duplicates.iter().map(|d| d.name.as_str()).collect();
+        write!(f, "duplicate label definitions: {}", names.join(", "))
+    }
+}
+
+/// Appends the addition after the base script, refusing to merge duplicate labels.
+///
+/// Indices of the addition are offset past the base so they stay unique and ordered.
+pub fn merge(base: Vec<AST>, addition: Vec<AST>) -> Result<Vec<AST>, MergeConflict> {
+    let base_labels = labels(&base);
+    let mut duplicates: Vec<DuplicateLabel> = labels(&addition)
+        .into_iter()
+        .filter_map(|(name, info)| {
+            let base = base_labels.get(&name)?.node.clone();
+            Some(DuplicateLabel {
+                name,
+                base,
+                addition: info.node,
+            })
+        })
+        .collect();
+
+    if !duplicates.is_empty() {
+        duplicates.sort_by_key(|d| d.addition.index);
+        return Err(MergeConflict { duplicates });
+    }
+
+    let mut offset = 0;
+    walk(&base, &mut |_, node| offset = offset.max(node.index()));
+
+    let mut addition = addition;
+    shift_indices(&mut addition, 0, offset as isize);
+
+    let mut merged = base;
+    merged.extend(addition);
+    Ok(merged)
+}
+
+/// Merges several scripts in order, e.g. the chapter files of a project
+pub fn merge_all(scripts: impl IntoIterator<Item = Vec<AST>>) -> Result<Vec<AST>, MergeConflict> {
+    scripts.into_iter().try_fold(Vec::new(), merge)
+}
@@ -0,0 +1,23 @@
+use crate::encoding::{decode, EncodingFallback};
+use crate::parse_scenario_from_string;
+use crate::parsers::AST;
+use anyhow::Result;
+
+/// Parses a script file without blocking the async runtime while reading it
+pub async fn parse_scenario_from_file_async(filename: &str) -> Result<(Vec<AST>, Vec<String>)> {
+    let content = read_script(filename).await?;
+    parse_scenario_from_string(&content, filename)
+}
+
+/// Like [`parse_scenario_from_file_async`], also moving the parse itself to
+/// tokio's blocking thread pool so large scripts don't stall other tasks
+pub async fn parse_scenario_from_file_offloaded(filename: &str) -> Result<(Vec<AST>, Vec<String>)> {
+    let content = read_script(filename).await?;
+    let filename = filename.to_string();
+    tokio::task::spawn_blocking(move || parse_scenario_from_string(&content, &filename)).await?
+}
+
+async fn read_script(filename: &str) -> Result<String> {
+    let bytes = tokio::fs::read(filename).await?;
+    Ok(decode(bytes, filename, EncodingFallback::Reject)?.text)
+}
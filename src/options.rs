@@ -0,0 +1,113 @@
+use crate::encoding::EncodingFallback;
+use crate::prelude::*;
+use crate::progress::Observer;
+use crate::statements::Statements;
+
+/// How tab characters in a script are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TabPolicy {
+    /// Reject scripts containing tabs, as Ren'Py does
+    #[default]
+    Deny,
+    /// Expand tabs to spaces up to the next multiple of the width, with a warning
+    ExpandTo(usize),
+}
+
+/// Required indentation of nested blocks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndentRule {
+    /// Any deeper indentation opens a block, as in Ren'Py
+    #[default]
+    Any,
+    /// Every level is indented by the same number of spaces as the first one
+    Consistent,
+    /// Every level is indented by exactly this many spaces
+    Width(usize),
+}
+
+/// Treatment of whitespace inside string literals
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WhitespacePolicy {
+    /// Keep the text exactly as written, including line breaks
+    #[default]
+    Preserve,
+    /// Collapse runs of whitespace to a single space, as Ren'Py does
+    Collapse,
+}
+
+/// What becomes of comments
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommentHandling {
+    /// Drop them, as Ren'Py does
+    #[default]
+    Discard,
+    /// Attach them to the statements they describe, see [`crate::trivia`]
+    Attach,
+}
+
+/// Nesting allowed by default, far deeper than any script needs
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+/// Settings changing how scripts are parsed; the defaults follow Ren'Py,
+/// except that strings keep their whitespace and the statements this crate
+/// adds are recognized
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail on the first statement with an error instead of parsing on
+    pub strict: bool,
+    /// Keep statements that fail to parse as [`AST::Error`](crate::parsers::AST::Error)
+    /// nodes, and keep labels and init blocks with failing statements in them
+    pub recover: bool,
+    pub tabs: TabPolicy,
+    pub indent: IndentRule,
+    pub whitespace: WhitespacePolicy,
+    /// Words that can't be used as names in addition to
+    /// [`RESERVED_WORDS`](crate::lexer::RESERVED_WORDS), such as the keywords of custom statements
+    pub reserved_words: Vec<String>,
+    /// Words to free up for use as names, even when reserved by default
+    pub unreserved_words: Vec<String>,
+    pub comments: CommentHandling,
+    /// Recognize the statements Ren'Py doesn't have: `game_mechanic`,
+    /// `llm_generate` and `include`
+    pub extended_statements: bool,
+    /// Statements to recognize before the built-in ones, see [`crate::statements`]
+    pub statements: Statements,
+    /// How script files that aren't valid UTF-8 are read
+    pub encoding: EncodingFallback,
+    /// Told about the progress of the parse, and able to cancel it
+    pub observer: Observer,
+    /// Deepest blocks can be nested, so parsing doesn't run out of stack
+    pub max_depth: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            strict: false,
+            recover: false,
+            tabs: TabPolicy::default(),
+            indent: IndentRule::default(),
+            whitespace: WhitespacePolicy::default(),
+            reserved_words: Vec::new(),
+            unreserved_words: Vec::new(),
+            comments: CommentHandling::default(),
+            extended_statements: true,
+            statements: Statements::default(),
+            encoding: EncodingFallback::default(),
+            observer: Observer::default(),
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options for scripts from untrusted sources such as mods: broken
+    /// statements are kept as errors. Parsing never panics with any options,
+    /// short of a custom statement parser panicking.
+    pub fn hardened() -> Self {
+        ParseOptions {
+            recover: true,
+            ..ParseOptions::default()
+        }
+    }
+}
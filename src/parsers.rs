@@ -1,15 +1,24 @@
+use crate::diagnostics::Code;
 use crate::lexer::Lexer;
+use crate::prelude::*;
+use crate::progress::ParseEvent;
+use alloc::borrow::Cow;
 use anyhow::{anyhow, Result};
-use std::error;
-use std::fmt;
+use core::error;
+use core::fmt;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ParseError {
+    pub code: Code,
     pub filename: String,
     pub line_number: usize,
     pub message: String,
     pub line: Option<String>,
+    /// Byte offset into `line`
     pub pos: Option<usize>,
+    /// 1-based column of `pos` in the source, counted in chars on the physical
+    /// line it falls on
+    pub column: Option<usize>,
 }
 
 impl error::Error for ParseError {}
@@ -29,127 +38,534 @@ impl ParseError {
         pos: Option<usize>,
     ) -> Self {
         ParseError {
+            code: Code::Other,
             filename,
             line_number,
             message,
             line,
             pos,
+            column: None,
         }
     }
 
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = code;
+        self
+    }
+
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(&self) -> String {
-        let message = format!(
+        format!(
             "On line {} of {}: {}",
             self.line_number, self.filename, self.message
-        );
-
-        // if let Some(line) = &self.line {
-        //     message.push_str(&format!("\nline:\n{}", line));
-        // }
-
-        // if let Some(pos) = self.pos {
-        //     message.push_str(&format!("\n{:>pos$}", "^", pos = pos + 1));
-        // }
-
-        message
+        )
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AST {
     Define(usize, String),
-    Hide(usize, String),
+    /// Image and the clauses after its name as written, like `at left`
+    Hide(usize, String, Option<String>),
     Init(usize, Vec<AST>, i32),
     Jump(usize, String, bool),
     Label(usize, String, Vec<AST>, Option<String>),
     Play(usize, String, String),
     Return(usize, Option<String>),
-    Say(usize, Option<String>, String),
-    Scene(usize, Option<String>, String),
-    Show(usize, String),
+    /// Speaker and line of dialogue, with how the line was quoted
+    Say(usize, Option<String>, String, Quoting),
+    /// Image, layer and the clauses after the name as written
+    Scene(usize, Option<String>, String, Option<String>),
+    /// Image and the clauses after its name as written, like `at left with dissolve`
+    Show(usize, String, Option<String>),
     Stop(usize, String, Option<String>, Option<f32>),
     GameMechanic(usize, String),
     LLMGenerate(usize, String, Option<String>),
-    Error,
+    /// Another script to insert in place of the statement, see [`crate::project`]
+    Include(usize, String),
+    /// Statement of a [`StatementParser`](crate::statements::StatementParser),
+    /// with its keyword and the rest of its line as written
+    Custom(usize, String, String),
+    /// Statement that failed to parse, kept with its line and source text
+    Error {
+        loc: usize,
+        message: String,
+        raw_text: String,
+    },
+}
+
+/// How a string was written, for writing it back the same way
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quoting {
+    pub quote_char: char,
+    /// Written with an `r` prefix, keeping escapes as they are
+    pub raw: bool,
+    /// Written in triple quotes
+    pub triple: bool,
+}
+
+impl Default for Quoting {
+    fn default() -> Self {
+        Quoting {
+            quote_char: '"',
+            raw: false,
+            triple: false,
+        }
+    }
 }
 
 impl AST {
     pub fn index(&self) -> usize {
         *match self {
             AST::Define(i, _) => i,
-            AST::Hide(i, _) => i,
+            AST::Hide(i, _, _) => i,
             AST::Init(i, _, _) => i,
             AST::Jump(i, _, _) => i,
             AST::Label(i, _, _, _) => i,
             AST::Play(i, _, _) => i,
             AST::Return(i, _) => i,
-            AST::Say(i, _, _) => i,
-            AST::Scene(i, _, _) => i,
-            AST::Show(i, _) => i,
+            AST::Say(i, _, _, _) => i,
+            AST::Scene(i, _, _, _) => i,
+            AST::Show(i, _, _) => i,
             AST::Stop(i, _, _, _) => i,
             AST::GameMechanic(i, _) => i,
             AST::LLMGenerate(i, _, _) => i,
-            AST::Error => todo!(),
+            AST::Include(i, _) => i,
+            AST::Custom(i, _, _) => i,
+            AST::Error { loc, .. } => loc,
         }
     }
 
     pub fn set_index(&mut self, index: usize) {
         *match self {
             AST::Define(i, _) => i,
-            AST::Hide(i, _) => i,
+            AST::Hide(i, _, _) => i,
             AST::Init(i, _, _) => i,
             AST::Jump(i, _, _) => i,
             AST::Label(i, _, _, _) => i,
             AST::Play(i, _, _) => i,
             AST::Return(i, _) => i,
-            AST::Say(i, _, _) => i,
-            AST::Scene(i, _, _) => i,
-            AST::Show(i, _) => i,
+            AST::Say(i, _, _, _) => i,
+            AST::Scene(i, _, _, _) => i,
+            AST::Show(i, _, _) => i,
             AST::Stop(i, _, _, _) => i,
             AST::GameMechanic(i, _) => i,
             AST::LLMGenerate(i, _, _) => i,
-            AST::Error => todo!(),
+            AST::Include(i, _) => i,
+            AST::Custom(i, _, _) => i,
+            AST::Error { loc, .. } => loc,
         } = index;
     }
+
+    /// Statements nested under this node, for labels and init blocks
+    pub fn block(&self) -> Option<&Vec<AST>> {
+        match self {
+            AST::Label(_, _, block, _) | AST::Init(_, block, _) => Some(block),
+            _ => None,
+        }
+    }
+
+    pub fn block_mut(&mut self) -> Option<&mut Vec<AST>> {
+        match self {
+            AST::Label(_, _, block, _) | AST::Init(_, block, _) => Some(block),
+            _ => None,
+        }
+    }
+
+    /// Name bound by `define name = Character(...)`
+    pub fn defined_character(&self) -> Option<&str> {
+        let AST::Define(_, definition) = self else {
+            return None;
+        };
+
+        let (name, value) = definition.split_once('=')?;
+        if value.trim_start().starts_with("Character(") {
+            Some(name.trim())
+        } else {
+            None
+        }
+    }
+}
+
+/// Reference to a node: its index and its position within each enclosing block
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeRef {
+    pub index: usize,
+    pub path: Vec<usize>,
+}
+
+impl NodeRef {
+    pub fn new(path: &[usize], node: &AST) -> Self {
+        NodeRef {
+            index: node.index(),
+            path: path.to_vec(),
+        }
+    }
+
+    pub fn resolve<'a>(&self, ast: &'a [AST]) -> Option<&'a AST> {
+        node_at(ast, &self.path)
+    }
+}
+
+/// Node at the path through nested blocks, see [`NodeRef::path`]
+pub(crate) fn node_at<'a>(ast: &'a [AST], path: &[usize]) -> Option<&'a AST> {
+    let (last, parents) = path.split_last()?;
+    let mut block = ast;
+    for &position in parents {
+        block = block.get(position)?.block()?;
+    }
+    block.get(*last)
+}
+
+/// Visits every node in document order, passing its path through nested blocks
+pub fn walk<'a>(ast: &'a [AST], f: &mut impl FnMut(&[usize], &'a AST)) {
+    fn walk_block<'a>(
+        block: &'a [AST],
+        path: &mut Vec<usize>,
+        f: &mut impl FnMut(&[usize], &'a AST),
+    ) {
+        for (position, item) in block.iter().enumerate() {
+            path.push(position);
+            f(path, item);
+            if let Some(block) = item.block() {
+                walk_block(block, path, f);
+            }
+            path.pop();
+        }
+    }
+
+    walk_block(ast, &mut Vec::new(), f);
+}
+
+/// Like `walk`, also passing the innermost label containing the node (labels contain themselves)
+pub fn walk_labeled<'a>(ast: &'a [AST], f: &mut impl FnMut(&[usize], &'a AST, Option<&'a str>)) {
+    let mut enclosing: Vec<(usize, &str)> = Vec::new();
+
+    walk(ast, &mut |path, node| {
+        let depth = path.len();
+        while enclosing.last().is_some_and(|(d, _)| *d >= depth) {
+            enclosing.pop();
+        }
+        if let AST::Label(_, name, _, _) = node {
+            enclosing.push((depth, name));
+        }
+
+        f(path, node, enclosing.last().map(|(_, name)| *name));
+    });
+}
+
+pub(crate) fn shift_indices(ast: &mut [AST], from: usize, delta: isize) {
+    for item in ast {
+        if item.index() >= from {
+            item.set_index(item.index().saturating_add_signed(delta));
+        }
+        if let Some(block) = item.block_mut() {
+            shift_indices(block, from, delta);
+        }
+    }
+}
+
+/// Assigns sequential indices, starting from 1, in document order across nested blocks
+pub fn renumber(ast: &mut [AST]) {
+    fn number(block: &mut [AST], next: &mut usize) {
+        for item in block {
+            item.set_index(*next);
+            *next += 1;
+
+            if let Some(block) = item.block_mut() {
+                number(block, next);
+            }
+        }
+    }
+
+    number(ast, &mut 1);
 }
 
+fn collapse_whitespace(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                rv.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            rv.push(c);
+            in_whitespace = false;
+        }
+    }
+    rv
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Clauses of an image statement without surrounding whitespace or an
+/// explicit master layer, if there are any
+fn normalize_clauses(clauses: &Option<String>) -> Option<String> {
+    clauses
+        .as_deref()
+        .map(without_master_layer)
+        .filter(|clauses| !clauses.is_empty())
+}
+
+/// The clauses with `onlayer master` left out, as it's the default layer
+fn without_master_layer(clauses: &str) -> String {
+    let mut rest = clauses.trim();
+    let mut rv = String::with_capacity(rest.len());
+    while let Some(i) = rest.find("onlayer") {
+        let after = &rest[i + "onlayer".len()..];
+        let layer = after.trim_start();
+        let explicit_master = (i == 0 || rest[..i].ends_with(char::is_whitespace))
+            && layer.len() < after.len()
+            && layer.split_whitespace().next() == Some("master");
+        if !explicit_master {
+            rv.push_str(&rest[..i + "onlayer".len()]);
+            rest = after;
+            continue;
+        }
+
+        rv.push_str(rest[..i].trim_end());
+        rest = layer["master".len()..].trim_start();
+        if !rv.is_empty() && !rest.is_empty() {
+            rv.push(' ');
+        }
+    }
+    rv.push_str(rest);
+    rv
+}
+
+/// Canonicalizes cosmetically different forms so equivalent scripts compare equal.
+///
+/// Quote style needs no handling here since strings are stored unescaped.
+pub fn normalize(ast: &mut [AST]) {
+    fn normalize_block(block: &mut [AST]) {
+        for item in block.iter_mut() {
+            match item {
+                AST::Define(_, definition) => *definition = definition.trim().to_string(),
+                AST::Hide(_, image, clauses) | AST::Show(_, image, clauses) => {
+                    *image = normalize_name(image);
+                    *clauses = normalize_clauses(clauses);
+                }
+                AST::Jump(_, target, _) => *target = target.trim().to_string(),
+                AST::Label(_, name, _, _) => *name = name.trim().to_string(),
+                AST::Play(_, channel, filename) => {
+                    *channel = channel.trim().to_string();
+                    *filename = filename.trim().to_string();
+                }
+                AST::Return(_, value) => {
+                    *value = Some(value.as_deref().unwrap_or_default().trim().to_string())
+                }
+                AST::Say(_, who, what, quoting) => {
+                    *who = who.as_deref().map(normalize_name);
+                    *what = collapse_whitespace(what);
+                    *quoting = Quoting::default();
+                }
+                AST::Scene(_, image, layer, clauses) => {
+                    *clauses = normalize_clauses(clauses);
+                    *image = image
+                        .as_deref()
+                        .map(normalize_name)
+                        .filter(|image| !image.is_empty());
+                    if layer.trim().is_empty() {
+                        *layer = "master".to_string();
+                    }
+                }
+                AST::Stop(_, channel, _, _) => *channel = channel.trim().to_string(),
+                AST::Include(_, path) => *path = path.trim().to_string(),
+                AST::Custom(_, _, text) => *text = text.trim().to_string(),
+                AST::GameMechanic(_, mechanic) => *mechanic = collapse_whitespace(mechanic),
+                AST::LLMGenerate(_, who, prompt) => {
+                    *who = who.trim().to_string();
+                    *prompt = prompt.as_deref().map(collapse_whitespace);
+                }
+                AST::Init(..) | AST::Error { .. } => {}
+            }
+
+            if let Some(block) = item.block_mut() {
+                normalize_block(block);
+            }
+        }
+    }
+
+    normalize_block(ast);
+    renumber(ast);
+}
+
+/// Inserts a node before the statement currently holding its index, which may live in a label body
 pub fn inject_node(ast: Vec<AST>, node: AST) -> Vec<AST> {
+    // Path of the first node in document order with index >= node_index
+    fn find(block: &[AST], node_index: usize, path: &mut Vec<usize>) -> bool {
+        for (position, item) in block.iter().enumerate() {
+            path.push(position);
+            if item.index() >= node_index {
+                return true;
+            }
+            if let Some(block) = item.block() {
+                if find(block, node_index, path) {
+                    return true;
+                }
+            }
+            path.pop();
+        }
+        false
+    }
+
     let node_index = node.index();
+    let mut path = Vec::new();
+    let found = find(&ast, node_index, &mut path);
 
     // Step 1: Shift all nodes with index >= node_index
-    let mut shifted_ast: Vec<AST> = ast
-        .into_iter()
-        .map(|mut item| {
-            if item.index() >= node_index {
-                item.set_index(item.index() + 1);
-            }
-            item
-        })
-        .collect();
+    let mut shifted_ast = ast;
+    shift_indices(&mut shifted_ast, node_index, 1);
 
     // Step 2: Insert the new node at the correct position
-    shifted_ast.push(node);
+    if !found {
+        shifted_ast.push(node);
+        return shifted_ast;
+    }
 
-    // Sort the vector by index to maintain order
-    shifted_ast.sort_by_key(|item| item.index());
+    // Descends along the path, which find only leads through nodes with a block
+    fn insert(block: &mut Vec<AST>, path: &[usize], node: AST) {
+        match path {
+            [position] => block.insert(*position, node),
+            [step, rest @ ..] => match block[*step].block_mut() {
+                Some(inner) => insert(inner, rest, node),
+                None => block.push(node),
+            },
+            [] => block.push(node),
+        }
+    }
 
+    insert(&mut shifted_ast, &path, node);
     shifted_ast
 }
 
-fn parse_image_name(lexer: &mut Lexer) -> Result<Vec<String>> {
-    let name = lexer.name().unwrap_or_default();
+/// Appends a node to the end of the named label's body, renumbering the statements after it
+pub fn inject_into_label(ast: Vec<AST>, label_name: &str, node: AST) -> Result<Vec<AST>> {
+    fn find_label<'a>(block: &'a mut [AST], label_name: &str) -> Option<&'a mut AST> {
+        for item in block {
+            if matches!(item, AST::Label(_, name, _, _) if name == label_name) {
+                return Some(item);
+            }
+            if let Some(label) = item.block_mut().and_then(|b| find_label(b, label_name)) {
+                return Some(label);
+            }
+        }
+        None
+    }
 
-    let mut rv: Vec<String> = vec![name.clone()];
+    let mut ast = ast;
+    let node_index = match find_label(&mut ast, label_name) {
+        Some(label) => last_index(label) + 1,
+        None => return Err(anyhow!("label '{}' not found", label_name)),
+    };
 
-    loop {
-        let name = lexer.name();
-        if let Some(n) = name {
-            rv.push(n.trim().to_string());
-        } else {
-            break;
+    shift_indices(&mut ast, node_index, 1);
+
+    let mut node = node;
+    node.set_index(node_index);
+    if let Some(block) = find_label(&mut ast, label_name).and_then(AST::block_mut) {
+        block.push(node);
+    }
+
+    Ok(ast)
+}
+
+/// Index of the last statement of the node, itself unless it has a block
+fn last_index(item: &AST) -> usize {
+    item.block()
+        .and_then(|block| block.last())
+        .map_or(item.index(), last_index)
+}
+
+/// Removes the node with the given index along with its block, searching
+/// label bodies, and shifts later indices down by the lines it spanned
+pub fn remove_node(ast: Vec<AST>, index: usize) -> Vec<AST> {
+    fn remove(block: &mut Vec<AST>, index: usize) -> Option<AST> {
+        if let Some(position) = block.iter().position(|item| item.index() == index) {
+            return Some(block.remove(position));
+        }
+
+        block
+            .iter_mut()
+            .filter_map(AST::block_mut)
+            .find_map(|block| remove(block, index))
+    }
+
+    let mut ast = ast;
+    if let Some(removed) = remove(&mut ast, index) {
+        let last = last_index(&removed);
+        shift_indices(&mut ast, last + 1, -((last - index + 1) as isize));
+    }
+
+    ast
+}
+
+/// Replaces the node with the given index, searching label bodies; the new node takes over that index
+pub fn replace_node(ast: Vec<AST>, index: usize, node: AST) -> Vec<AST> {
+    fn replace(block: &mut [AST], index: usize, node: &mut Option<AST>) -> bool {
+        for item in block {
+            if item.index() == index {
+                if let Some(mut node) = node.take() {
+                    node.set_index(index);
+                    *item = node;
+                }
+                return true;
+            }
+            if let Some(block) = item.block_mut() {
+                if replace(block, index, node) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    let mut ast = ast;
+    replace(&mut ast, index, &mut Some(node));
+
+    ast
+}
+
+/// Clauses that can follow the name of an image in show, scene and hide statements
+pub(crate) const IMAGE_CLAUSES: [&str; 6] = ["at", "as", "behind", "onlayer", "with", "zorder"];
+
+/// Pattern matching any of the [`IMAGE_CLAUSES`]
+fn image_clause() -> String {
+    format!("^({})", IMAGE_CLAUSES.join("|"))
+}
+
+/// Words of an image name, borrowed as written when single spaces separate them
+fn parse_image_name<'src>(lexer: &mut Lexer<'_, 'src>) -> Result<Cow<'src, str>> {
+    let Some(first) = lexer.name() else {
+        return Ok(Cow::Borrowed(""));
+    };
+    let start = lexer.pos() - first.len();
+
+    let mut names = vec![first];
+    while lexer.peek_keyword(&image_clause())?.is_none() {
+        match lexer.name() {
+            Some(name) => names.push(name),
+            None => break,
         }
     }
-    Ok(rv)
+
+    let written = lexer.span(start..lexer.pos());
+    if written.split(' ').count() == names.len() {
+        Ok(written)
+    } else {
+        Ok(Cow::Owned(names.join(" ")))
+    }
 }
 
 pub fn parse_simple_expression_list(input: &str) -> Result<Vec<String>> {
@@ -173,37 +589,91 @@ pub fn parse_with(_: &mut Lexer, node: AST) -> Result<Vec<AST>> {
     Ok(vec![node])
 }
 
-pub fn parse_image_specifier(lexer: &mut Lexer) -> Result<(String, Option<String>, String)> {
-    let layer: Option<String> = None;
-
-    let image_names = parse_image_name(lexer)?;
+/// Image name, expression, layer and clauses of a show, scene or hide statement.
+/// The clauses are everything after the image name, as written.
+pub type ImageSpecifier<'src> = (
+    Cow<'src, str>,
+    Option<Cow<'src, str>>,
+    Cow<'src, str>,
+    Option<Cow<'src, str>>,
+);
+
+pub fn parse_image_specifier<'src>(lexer: &mut Lexer<'_, 'src>) -> Result<ImageSpecifier<'src>> {
+    let image_name = parse_image_name(lexer)?;
+    let expression = None;
+
+    let start = lexer.after_whitespace();
+    let mut layer = Cow::Borrowed("master");
+    loop {
+        let at = lexer.after_whitespace();
+        let Some(clause) = lexer.keyword(&image_clause())? else {
+            break;
+        };
+        if clause == "onlayer" {
+            match lexer.name() {
+                Some(name) => layer = name,
+                None => {
+                    lexer.error(Code::MissingArgument, "expected layer name after 'onlayer'")?
+                }
+            }
+            continue;
+        }
 
-    let image_name: String = image_names.join(" ");
-    let expression: Option<String> = None;
+        lexer.warning_at(
+            at,
+            Code::UnsupportedClause,
+            &format!("'{}' clause is kept as written but not interpreted", clause),
+        );
+        while !lexer.eol() && lexer.peek_keyword(&image_clause())?.is_none() {
+            if lexer.string().is_none() && lexer.match_(r"^\S+")?.is_none() {
+                break;
+            }
+        }
+    }
 
-    let layer = layer.unwrap_or_else(|| "master".to_string());
+    let clauses = (lexer.pos() > start).then(|| lexer.span(start..lexer.pos()));
+    Ok((image_name, expression, layer, clauses))
+}
 
-    Ok((image_name, expression, layer))
+/// Offset of the first call of an image manipulator like `im.Scale(...)`
+fn image_manipulator(text: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices("im.").map(|(i, _)| i).find(|&i| {
+        let call = text[i + 3..].trim_start_matches(is_word);
+        !text[..i].ends_with(|c: char| is_word(c) || c == '.')
+            && call.len() < text.len() - i - 3
+            && call.starts_with('(')
+    })
 }
 
-pub fn parse_audio_specifier(lexer: &mut Lexer) -> Result<String> {
+pub fn parse_audio_specifier<'src>(lexer: &mut Lexer<'_, 'src>) -> Result<Cow<'src, str>> {
+    let start = lexer.checkpoint();
     let play_type = lexer.name().unwrap_or_default();
 
     if play_type == "music" || play_type == "sound" {
         return Ok(play_type);
     }
 
-    Err(anyhow!("Play or sound is required"))
+    // Point at the channel rather than past it
+    lexer.revert(start);
+    Err(lexer
+        .parse_error(Code::InvalidAudioChannel, "Play or sound is required")
+        .into())
 }
 
-pub fn parse_audio_filename(lexer: &mut Lexer) -> Result<String> {
-    let audio_filename = lexer.audio_filename();
-
-    if audio_filename.is_none() {
-        return Err(anyhow!("provide mp3, ogg or wav file"));
+pub fn parse_audio_filename<'src>(lexer: &mut Lexer<'_, 'src>) -> Result<Cow<'src, str>> {
+    let Some(audio_filename) = lexer.audio_filename() else {
+        return Err(lexer
+            .parse_error(Code::InvalidAudioFile, "provide mp3, ogg or wav file")
+            .into());
+    };
+
+    // Quotes only ever surround the name, so it's borrowed from inside them
+    let quoted = &audio_filename[1..audio_filename.len() - 1];
+    match audio_filename {
+        Cow::Borrowed(name) if !quoted.contains('"') => Ok(Cow::Borrowed(&name[1..name.len() - 1])),
+        _ => Ok(Cow::Owned(audio_filename.replace("\"", ""))),
     }
-
-    Ok(audio_filename.unwrap().replace("\"", ""))
 }
 
 #[derive(Debug)]
@@ -214,103 +684,347 @@ pub struct ParameterInfo {
     pub extrakw: Option<String>,
 }
 
+/// A statement as the parser reads it: its text is borrowed from the source
+/// wherever it's written there verbatim, and its block is built by a [`Tree`]
+pub(crate) enum Stmt<'src, B> {
+    Define(usize, Cow<'src, str>),
+    Hide(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Init(usize, B, i32),
+    Jump(usize, Cow<'src, str>, bool),
+    Label(usize, Cow<'src, str>, B, Option<Cow<'src, str>>),
+    Play(usize, Cow<'src, str>, Cow<'src, str>),
+    Return(usize, Option<Cow<'src, str>>),
+    Say(usize, Option<Cow<'src, str>>, Cow<'src, str>, Quoting),
+    Scene(
+        usize,
+        Option<Cow<'src, str>>,
+        Cow<'src, str>,
+        Option<Cow<'src, str>>,
+    ),
+    Show(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Stop(usize, Cow<'src, str>, Option<Cow<'src, str>>, Option<f32>),
+    GameMechanic(usize, Cow<'src, str>),
+    LLMGenerate(usize, Cow<'src, str>, Option<Cow<'src, str>>),
+    Include(usize, Cow<'src, str>),
+    Custom(usize, Cow<'src, str>, Cow<'src, str>),
+    Error {
+        loc: usize,
+        message: String,
+        raw_text: Cow<'src, str>,
+    },
+}
+
+/// Calls `$then!` with every statement of the [`AST`]: the type it's exported
+/// as, its variant and its fields after the index. Fields are named as in an
+/// exported `Node`, with the kind of conversion they need and, when a document
+/// may leave them out, their default.
+///
+/// The trees mirroring the AST convert their nodes with the statements listed
+/// here, so a statement added to the AST can't be missing from any of them.
+macro_rules! statements {
+    ($then:ident!($($args:tt)*)) => {
+        $then! {
+            ($($args)*)
+            "define" Define(definition: text),
+            "hide" Hide(image: name, clauses: opt_text),
+            "init" Init(block: nodes, priority: copy),
+            "jump" Jump(target: text, expression: copy),
+            "label" Label(name: text, block: nodes, parameters: opt_text),
+            "play" Play(channel: text, filename: text),
+            "return" Return(value: opt_text),
+            "say" Say(who: opt_name, what: text, quoting: copy),
+            "scene" Scene(image: opt_name, layer: text = "master", clauses: opt_text),
+            "show" Show(image: name, clauses: opt_text),
+            "stop" Stop(channel: text, effect: opt_text, length: copy),
+            "game_mechanic" GameMechanic(mechanic: text),
+            "llm_generate" LLMGenerate(who: name, prompt: opt_text),
+            "include" Include(filename: text),
+            "custom" Custom(name: text, value: text = ""),
+            "error" Error { message: message = "", raw_text: text = "" },
+        }
+    };
+}
+pub(crate) use statements;
+
+/// Converts `$node` to the same statement of another tree, converting each
+/// field with the method of `$c` named after its kind, as in
+/// `statements!(convert!(node, BorrowedAST => AST, c))`
+macro_rules! convert {
+    (
+        ($node:expr, $from:ident => $to:ident, $c:ident)
+        $($kind:literal $variant:ident
+            $(($($field:ident: $field_kind:ident $(= $default:literal)?),*))?
+            $({$($named:ident: $named_kind:ident $(= $named_default:literal)?),*})?
+        ),* $(,)?
+    ) => {
+        match $node {
+            $($from::$variant $((i, $($field),*))? $({ loc: i, $($named),* })? => {
+                $to::$variant
+                    $((i, $($c.$field_kind($field)),*))?
+                    $({ loc: i, $($named: $c.$named_kind($named)),* })?
+            })*
+        }
+    };
+}
+pub(crate) use convert;
+
+/// Makes the nodes a parse produces, so that trees borrowing from the source,
+/// living in an arena or sharing names are built as statements are parsed
+pub(crate) trait Tree<'src> {
+    type Node;
+    /// Statements of a label, init block or script
+    type Block;
+
+    fn block(&mut self) -> Self::Block;
+
+    fn push(&mut self, block: &mut Self::Block, node: Self::Node);
+
+    fn node(&mut self, stmt: Stmt<'src, Self::Block>) -> Self::Node;
+
+    /// Tells the observer of the options about a parsed statement
+    fn parsed(&self, _l: &Lexer, _node: &Self::Node) {}
+}
+
+/// Tree of owned [`AST`] nodes
+pub(crate) struct Owned;
+
+impl<'src> Tree<'src> for Owned {
+    type Node = AST;
+    type Block = Vec<AST>;
+
+    fn block(&mut self) -> Vec<AST> {
+        Vec::new()
+    }
+
+    fn push(&mut self, block: &mut Vec<AST>, node: AST) {
+        block.push(node);
+    }
+
+    fn node(&mut self, stmt: Stmt<'src, Vec<AST>>) -> AST {
+        let c = self;
+        statements!(convert!(stmt, Stmt => AST, c))
+    }
+
+    fn parsed(&self, l: &Lexer, node: &AST) {
+        l.notify(ParseEvent::StatementParsed(node));
+    }
+}
+
+/// Fields of an owned [`AST`] out of the ones of a [`Stmt`] or of any tree
+/// mirroring the AST, see [`convert`]
+impl Owned {
+    pub(crate) fn text(&mut self, text: impl Into<String>) -> String {
+        text.into()
+    }
+
+    pub(crate) fn opt_text(&mut self, text: Option<impl Into<String>>) -> Option<String> {
+        text.map(Into::into)
+    }
+
+    pub(crate) fn name(&mut self, name: impl AsRef<str>) -> String {
+        name.as_ref().to_string()
+    }
+
+    pub(crate) fn opt_name(&mut self, name: Option<impl AsRef<str>>) -> Option<String> {
+        name.map(|name| name.as_ref().to_string())
+    }
+
+    pub(crate) fn message(&mut self, message: impl Into<String>) -> String {
+        message.into()
+    }
+
+    pub(crate) fn copy<T>(&mut self, value: T) -> T {
+        value
+    }
+
+    pub(crate) fn nodes<N: Into<AST>>(&mut self, nodes: impl IntoIterator<Item = N>) -> Vec<AST> {
+        nodes.into_iter().map(Into::into).collect()
+    }
+}
+
+/// Makes a node of the tree out of one a [`StatementParser`](crate::statements::StatementParser)
+/// returned
+pub(crate) fn from_ast<'src, T: Tree<'src>>(tree: &mut T, node: AST) -> T::Node {
+    let c = &mut FromAst(tree);
+    let stmt = statements!(convert!(node, AST => Stmt, c));
+    tree.node(stmt)
+}
+
+/// Fields of a [`Stmt`] out of the ones of an [`AST`], building blocks in the tree
+struct FromAst<'t, T>(&'t mut T);
+
+impl<T> FromAst<'_, T> {
+    fn text(&mut self, text: String) -> Cow<'static, str> {
+        Cow::Owned(text)
+    }
+
+    fn opt_text(&mut self, text: Option<String>) -> Option<Cow<'static, str>> {
+        text.map(Cow::Owned)
+    }
+
+    fn name(&mut self, name: String) -> Cow<'static, str> {
+        Cow::Owned(name)
+    }
+
+    fn opt_name(&mut self, name: Option<String>) -> Option<Cow<'static, str>> {
+        name.map(Cow::Owned)
+    }
+
+    fn message(&mut self, message: String) -> String {
+        message
+    }
+
+    fn copy<V>(&mut self, value: V) -> V {
+        value
+    }
+
+    fn nodes<'src>(&mut self, nodes: Vec<AST>) -> T::Block
+    where
+        T: Tree<'src>,
+    {
+        let mut rv = self.0.block();
+        for node in nodes {
+            let node = from_ast(self.0, node);
+            self.0.push(&mut rv, node);
+        }
+        rv
+    }
+}
+
 pub fn parse_statement(l: &mut Lexer) -> Result<AST> {
+    statement(l, &mut Owned)
+}
+
+/// Parses the statement at the lexer's position into a node of the tree
+pub(crate) fn statement<'src, T: Tree<'src>>(
+    l: &mut Lexer<'_, 'src>,
+    tree: &mut T,
+) -> Result<T::Node> {
     let loc = l.get_location();
 
-    if l.keyword("^return").is_some() {
-        let nonblock = l.expect_noblock("return statement");
-        if nonblock.is_err() {
-            return Err(nonblock.err().unwrap());
+    if !l.options().statements.is_empty() {
+        let statements = l.options().statements.clone();
+        for parser in statements.iter() {
+            let keyword = format!("^{}", regex_syntax::escape(parser.keyword()));
+            if l.keyword(&keyword)?.is_some() {
+                let node = parser.parse(l, loc)?;
+                return Ok(from_ast(tree, node));
+            }
         }
+    }
+
+    if l.keyword("^return")?.is_some() {
+        l.expect_noblock("return statement")?;
 
         let rest = l.rest();
 
-        let eol = l.expect_eol();
-        if eol.is_err() {
-            return Err(eol.err().unwrap());
-        }
+        l.expect_eol()?;
 
         l.advance();
-        return Ok(AST::Return(loc, Some(rest)));
+        return Ok(tree.node(Stmt::Return(loc, Some(rest))));
     }
 
-    if l.keyword("^jump").is_some() {
-        let nonblock = l.expect_noblock("jump statement");
-        if nonblock.is_err() {
-            return Err(nonblock.err().unwrap());
-        }
+    if l.keyword("^jump")?.is_some() {
+        l.expect_noblock("jump statement")?;
 
-        let target = l.name().unwrap_or_default();
+        let target = l.dotted_name().unwrap_or_default();
 
         l.expect_eol()?;
         l.advance();
-        return Ok(AST::Jump(loc, target, false));
+        return Ok(tree.node(Stmt::Jump(loc, target, false)));
     }
 
-    if l.keyword("^scene").is_some() {
+    if l.keyword("^scene")?.is_some() {
         l.expect_noblock("scene statement")?;
 
-        let layer = "master".to_string();
         if l.eol() {
             l.advance();
-            return Ok(AST::Scene(loc, None, layer));
+            return Ok(tree.node(Stmt::Scene(loc, None, Cow::Borrowed("master"), None)));
         }
 
-        let imspec = parse_image_specifier(l)?.0;
+        let (imspec, _, layer, clauses) = parse_image_specifier(l)?;
+        let imspec = if imspec.is_empty() {
+            None
+        } else {
+            Some(imspec)
+        };
 
         l.advance();
-        return Ok(AST::Scene(loc, Some(imspec), layer));
+        return Ok(tree.node(Stmt::Scene(loc, imspec, layer, clauses)));
     }
 
-    if l.keyword("^game_mechanic").is_some() {
-        let argument = l.string();
+    let extended = l.options().extended_statements;
 
-        if argument.is_none() {
-            l.error("Expected a string after 'game_mechanic' keyword.")?;
-        }
+    if extended && l.keyword("^game_mechanic")?.is_some() {
+        let Some(argument) = l.string().map(|s| s.value) else {
+            return Err(l
+                .parse_error(
+                    Code::MissingArgument,
+                    "Expected a string after 'game_mechanic' keyword.",
+                )
+                .into());
+        };
 
         l.expect_eol()?;
         l.expect_noblock("game_mechanic statement")?;
         l.advance();
 
-        return Ok(AST::GameMechanic(loc, argument.unwrap()));
+        return Ok(tree.node(Stmt::GameMechanic(loc, argument)));
     }
 
-    if l.keyword("^llm_generate").is_some() {
+    if extended && l.keyword("^include")?.is_some() {
+        let Some(path) = l.string().map(|s| s.value) else {
+            return Err(l
+                .parse_error(
+                    Code::MissingArgument,
+                    "Expected a file name after 'include' keyword.",
+                )
+                .into());
+        };
+
+        l.expect_eol()?;
+        l.expect_noblock("include statement")?;
+        l.advance();
+
+        return Ok(tree.node(Stmt::Include(loc, path)));
+    }
+
+    if extended && l.keyword("^llm_generate")?.is_some() {
         if let Some(who) = l.word() {
-            let prompt = l.string();
+            let prompt = l.string().map(|s| s.value);
 
             l.expect_eol()?;
             l.expect_noblock("game_mechanic statement")?;
             l.advance();
 
-            return Ok(AST::LLMGenerate(loc, who, prompt));
+            return Ok(tree.node(Stmt::LLMGenerate(loc, who, prompt)));
         }
 
-        l.error("Expected word after 'llm_generate' keyword.")?;
+        l.error(
+            Code::MissingArgument,
+            "Expected word after 'llm_generate' keyword.",
+        )?;
     }
 
-    if l.keyword("^show").is_some() {
-        let imspec = parse_image_specifier(l)?.0;
-        let rv = parse_with(l, AST::Show(loc, imspec))?[0].clone();
+    if l.keyword("^show")?.is_some() {
+        let (imspec, _, _, clauses) = parse_image_specifier(l)?;
 
         l.expect_eol()?;
         l.expect_noblock("show statement")?;
         l.advance();
-        return Ok(rv);
+        return Ok(tree.node(Stmt::Show(loc, imspec, clauses)));
     }
 
-    if l.keyword("^hide").is_some() {
-        let imspec = parse_image_specifier(l)?.0;
-        let rv = parse_with(l, AST::Hide(loc, imspec))?[0].clone();
+    if l.keyword("^hide")?.is_some() {
+        let (imspec, _, _, clauses) = parse_image_specifier(l)?;
 
         l.expect_eol()?;
         l.expect_noblock("hide statement")?;
         l.advance();
-        return Ok(rv);
+        return Ok(tree.node(Stmt::Hide(loc, imspec, clauses)));
     }
 
-    if l.keyword("^play").is_some() {
+    if l.keyword("^play")?.is_some() {
         let play_type = parse_audio_specifier(l)?;
 
         let filename = parse_audio_filename(l)?;
@@ -318,111 +1032,144 @@ pub fn parse_statement(l: &mut Lexer) -> Result<AST> {
         l.expect_eol()?;
         l.advance();
 
-        return Ok(AST::Play(loc, play_type, filename));
+        return Ok(tree.node(Stmt::Play(loc, play_type, filename)));
     }
 
-    if l.keyword("^stop").is_some() {
+    if l.keyword("^stop")?.is_some() {
         let audio_specifier = parse_audio_specifier(l)?;
 
-        let (effect, length) = l.stop_arguments();
+        let (effect, length) = l.stop_arguments()?;
 
         l.expect_eol()?;
         l.advance();
 
-        return Ok(AST::Stop(loc, audio_specifier, effect, length));
+        return Ok(tree.node(Stmt::Stop(loc, audio_specifier, effect, length)));
     }
 
-    if l.keyword("^label").is_some() {
-        let name = l.name().unwrap_or_default();
+    if l.keyword("^label")?.is_some() {
+        let name = l.dotted_name().unwrap_or_default();
 
-        let (block_ast, block_err) = parse_block(&mut l.subblock_lexer(false));
+        let (block_ast, block_err) = block(&mut l.child_lexer(false), tree);
 
-        if !block_err.is_empty() {
-            for err in block_err {
-                l.error(&err)?;
-            }
-        }
+        block_errors(l, block_err)?;
 
         l.advance();
 
-        // let label = AST::Label(loc, name, block_ast, parameters);
-        let label = AST::Label(loc, name, block_ast, None);
-        return Ok(label);
+        return Ok(tree.node(Stmt::Label(loc, name, block_ast, None)));
     }
 
-    if l.keyword("^define").is_some() {
+    if l.keyword("^define")?.is_some() {
+        let start = l.after_whitespace();
         let definition = l.rest();
+        if let Some(found) = image_manipulator(&definition) {
+            l.warning_at(
+                start + found,
+                Code::DeprecatedForm,
+                "image manipulators (im.*) are deprecated, use Transform instead",
+            );
+        }
         l.expect_eol()?;
         l.advance();
 
-        let label = AST::Define(loc, definition);
-        return Ok(label);
+        return Ok(tree.node(Stmt::Define(loc, definition)));
     }
 
-    if l.keyword("^init").is_some() {
-        let priority = l.integer().map_or(0, |p| p.parse::<i32>().unwrap_or(0));
+    if l.keyword("^init")?.is_some() {
+        let priority = l.int_value()?.map_or(Some(0), |p| i32::try_from(p).ok());
+        let Some(priority) = priority else {
+            return Err(l
+                .parse_error(
+                    Code::InitPriorityOutOfRange,
+                    "init priority is out of range",
+                )
+                .into());
+        };
 
         let (block_ast, block_err) = {
             l.require(":")?;
             l.expect_eol()?;
             l.expect_block("init statement")?;
-            parse_block(&mut l.subblock_lexer(false))
+            block(&mut l.child_lexer(false), tree)
         };
 
-        if !block_err.is_empty() {
-            for err in block_err {
-                l.error(&err)?;
-            }
-        }
+        block_errors(l, block_err)?;
 
         l.advance();
-        let ast = AST::Init(loc, block_ast, priority);
-        return Ok(ast);
+        return Ok(tree.node(Stmt::Init(loc, block_ast, priority)));
     }
 
-    // Handle user statements or say statements.
+    // Handle say statements. Dialogue without a speaker is tried first, so
+    // `r"..."` reads as a raw string rather than a speaker named r.
     let state = l.checkpoint();
 
-    if let Some(word) = l.word() {
-        let text = l.string();
-        if text.is_none() {
-            l.error("empty text in say statement")?;
+    if let Some(what) = l.string() {
+        if l.eol() {
+            l.expect_noblock("say statement")?;
+            l.advance();
+
+            let quoting = what.quoting();
+            return Ok(tree.node(Stmt::Say(loc, None, what.value, quoting)));
         }
+    }
+
+    l.revert(state);
+
+    if let Some(word) = l.word() {
+        let Some(text) = l.string() else {
+            return Err(l
+                .parse_error(Code::MissingSayText, "empty text in say statement")
+                .into());
+        };
 
         l.expect_noblock(&format!("{} statement", word))?;
         l.advance();
 
-        let rv = AST::Say(loc, Some(word), text.unwrap());
-        return Ok(rv);
+        let quoting = text.quoting();
+        return Ok(tree.node(Stmt::Say(loc, Some(word), text.value, quoting)));
     }
 
-    l.revert(state.clone());
-    let what = l.string();
-
-    if let Some(what) = what {
-        if l.eol() {
-            l.expect_noblock("say statement")?;
-            l.advance();
-
-            return Ok(AST::Say(loc, None, what));
-        }
-    }
+    Err(l
+        .parse_error(Code::UnknownStatement, "expected statement.")
+        .into())
+}
 
-    let err = l.error("expected statement.").err().unwrap();
-    Err(err)
+pub fn parse_block(l: &mut Lexer) -> (Vec<AST>, Vec<ParseError>) {
+    block(l, &mut Owned)
 }
 
-pub fn parse_block(l: &mut Lexer) -> (Vec<AST>, Vec<String>) {
-    let mut rv = Vec::new();
+/// Parses the statements of the lexer's block into the tree
+pub(crate) fn block<'src, T: Tree<'src>>(
+    l: &mut Lexer<'_, 'src>,
+    tree: &mut T,
+) -> (T::Block, Vec<ParseError>) {
+    let mut rv = tree.block();
     let mut parse_errors = Vec::new();
 
     l.advance();
 
-    while !l.eob() {
-        match parse_statement(l) {
-            Ok(stmt) => rv.push(stmt),
+    while !l.eob() && !l.is_cancelled() {
+        let start = l.checkpoint();
+        match statement(l, tree) {
+            Ok(stmt) => {
+                tree.parsed(l, &stmt);
+                tree.push(&mut rv, stmt);
+            }
             Err(e) => {
-                parse_errors.push(format!("{}", e));
+                let error = to_parse_error(l, e);
+                if l.options().recover {
+                    l.revert(start);
+                    let node = tree.node(Stmt::Error {
+                        loc: l.get_location(),
+                        message: error.message.clone(),
+                        raw_text: l.span(0..l.text().len()),
+                    });
+                    tree.push(&mut rv, node);
+                }
+                // Errors of nested blocks are told about by the statement owning the block
+                if !l.is_nested() {
+                    l.notify(ParseEvent::Error(&error));
+                }
+                parse_errors.push(error);
                 l.advance();
             }
         }
@@ -430,3 +1177,28 @@ pub fn parse_block(l: &mut Lexer) -> (Vec<AST>, Vec<String>) {
 
     (rv, parse_errors)
 }
+
+/// The error a statement failed with, located at the lexer's position when it
+/// doesn't carry a location itself
+fn to_parse_error(l: &Lexer, error: anyhow::Error) -> ParseError {
+    error
+        .downcast::<ParseError>()
+        .unwrap_or_else(|error| l.parse_error(Code::Other, &error.to_string()))
+}
+
+/// Fails a label or init statement with the first error of its block, or in
+/// recovery mode reports them all and keeps the statement
+fn block_errors(l: &Lexer, errors: Vec<ParseError>) -> Result<()> {
+    if l.options().recover {
+        for error in errors {
+            l.notify(ParseEvent::Error(&error));
+            l.report_error(error);
+        }
+        return Ok(());
+    }
+
+    match errors.into_iter().next() {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
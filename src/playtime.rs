@@ -0,0 +1,123 @@
+use crate::flow::{paths_from, StoryPath};
+use crate::parsers::{walk_labeled, AST};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How fast players read
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReadingSpeed {
+    pub words_per_minute: f32,
+    /// Time to click on to the next line
+    pub seconds_per_line: f32,
+}
+
+impl Default for ReadingSpeed {
+    fn default() -> Self {
+        ReadingSpeed {
+            words_per_minute: 200.0,
+            seconds_per_line: 0.5,
+        }
+    }
+}
+
+/// Estimated time to read a script
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadingTime {
+    pub total: Duration,
+    /// Time of the dialogue directly in each label, not in labels nested in it
+    pub labels: BTreeMap<String, Duration>,
+}
+
+/// Longest a single line is counted as taking, however long its pauses
+pub const MAX_LINE_TIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Time to read a line of dialogue: its words, the `{w=seconds}` and
+/// `{p=seconds}` pauses in it, and a click to go on, up to [`MAX_LINE_TIME`].
+///
+/// Pauses that aren't a finite number of seconds are ignored, as are bare
+/// `{w}` and `{p}` tags, which wait for the player's click already counted
+/// by `seconds_per_line`. `pause` statements aren't counted either.
+pub fn line_time(text: &str, speed: &ReadingSpeed) -> Duration {
+    let mut words = 0;
+    let mut pauses = 0.0;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        words += rest[..start].split_whitespace().count();
+        rest = &rest[start + 1..];
+        if rest.starts_with('{') {
+            rest = &rest[1..];
+            continue;
+        }
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let tag = &rest[..end];
+        if let Some(("w" | "p", seconds)) = tag.split_once('=') {
+            match seconds.trim().parse::<f32>() {
+                Ok(seconds) if seconds.is_finite() && seconds > 0.0 => pauses += seconds,
+                _ => {}
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    words += rest.split_whitespace().count();
+
+    let reading = match speed.words_per_minute {
+        wpm if wpm > 0.0 => words as f32 * 60.0 / wpm,
+        _ => 0.0,
+    };
+    let seconds = (reading + pauses + speed.seconds_per_line).clamp(0.0, f32::MAX);
+    match Duration::try_from_secs_f32(seconds) {
+        Ok(time) => time.min(MAX_LINE_TIME),
+        // Not a number, from a reading speed that isn't one
+        Err(_) if seconds.is_nan() => Duration::ZERO,
+        Err(_) => MAX_LINE_TIME,
+    }
+}
+
+/// Adds up the time to read every line of dialogue, in total and per label
+pub fn reading_time(ast: &[AST], speed: &ReadingSpeed) -> ReadingTime {
+    let mut rv = ReadingTime::default();
+    walk_labeled(ast, &mut |_, node, label| {
+        if let AST::Say(_, _, text, _) = node {
+            let time = line_time(text, speed);
+            rv.total = rv.total.saturating_add(time);
+            if let Some(label) = label {
+                let total = rv.labels.entry(label.to_string()).or_default();
+                *total = total.saturating_add(time);
+            }
+        }
+    });
+    rv
+}
+
+/// Time to read along each path from `start`, up to `limit` paths as in
+/// [`crate::flow::paths`]
+pub fn route_times(ast: &[AST], speed: &ReadingSpeed, limit: usize) -> Vec<(StoryPath, Duration)> {
+    route_times_from(ast, "start", speed, limit)
+}
+
+/// Like [`route_times`], from the label `entry`
+pub fn route_times_from(
+    ast: &[AST],
+    entry: &str,
+    speed: &ReadingSpeed,
+    limit: usize,
+) -> Vec<(StoryPath, Duration)> {
+    paths_from(ast, entry, limit)
+        .into_iter()
+        .map(|path| {
+            let time = path
+                .statements
+                .iter()
+                .filter_map(|node| match node.resolve(ast) {
+                    Some(AST::Say(_, _, text, _)) => Some(line_time(text, speed)),
+                    _ => None,
+                })
+                .fold(Duration::ZERO, Duration::saturating_add);
+            (path, time)
+        })
+        .collect()
+}
@@ -0,0 +1,73 @@
+use crate::parsers::{ParseError, AST};
+use alloc::sync::Arc;
+use anyhow::Result;
+use core::error;
+use core::fmt;
+use core::ops::ControlFlow;
+
+/// Something that happened while parsing, for showing progress
+#[derive(Clone, Copy, Debug)]
+pub enum ParseEvent<'a> {
+    /// A script is about to be read, named by its filename
+    FileStarted(&'a str),
+    /// The script has been split into this many logical lines
+    LinesScanned(&'a str, usize),
+    /// A statement was parsed, after the statements nested under it
+    StatementParsed(&'a AST),
+    /// A statement failed to parse
+    Error(&'a ParseError),
+    /// The script has been parsed
+    FileFinished(&'a str),
+}
+
+type Callback = dyn Fn(&ParseEvent) -> ControlFlow<()> + Send + Sync;
+
+/// Callback told about each [`ParseEvent`], which stops parsing by breaking
+#[derive(Clone, Default)]
+pub struct Observer(Option<Arc<Callback>>);
+
+impl Observer {
+    pub fn new(callback: impl Fn(&ParseEvent) -> ControlFlow<()> + Send + Sync + 'static) -> Self {
+        Observer(Some(Arc::new(callback)))
+    }
+
+    /// Passes the event to the callback, failing with [`Cancelled`] when it breaks
+    pub fn notify(&self, event: ParseEvent) -> Result<()> {
+        match self.0.as_ref().map(|callback| callback(&event)) {
+            Some(ControlFlow::Break(())) => Err(Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Observer(..)"),
+            None => write!(f, "Observer(None)"),
+        }
+    }
+}
+
+impl PartialEq for Observer {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+}
+
+impl Eq for Observer {}
+
+/// Error of a parse stopped by its [`Observer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parsing was cancelled")
+    }
+}
+
+impl error::Error for Cancelled {}
@@ -0,0 +1,522 @@
+use crate::characters::{character_usage, CharacterUsage};
+use crate::diagnostics::{Code, Severity};
+use crate::include::{self, Origins};
+use crate::lint::rules::{DuplicateLabel, UndefinedLabel};
+use crate::lint::{is_suppressed, LintContext, Linter};
+use crate::options::{CommentHandling, ParseOptions};
+use crate::parse_script_from_bytes;
+use crate::parsers::{walk, NodeRef, ParseError, AST};
+use crate::progress::Cancelled;
+use crate::symbols::{labels, LabelInfo};
+use crate::trivia::Comments;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A parsed script of a project
+#[derive(Clone, Debug)]
+pub struct ScriptFile {
+    pub path: PathBuf,
+    pub ast: Vec<AST>,
+    /// Scripts the statements put in place of include statements come from
+    pub origins: Origins<PathBuf>,
+    /// Comments of the script and of the scripts it includes, by path, for
+    /// suppressing lint rules
+    pub comments: HashMap<PathBuf, Comments>,
+}
+
+impl ScriptFile {
+    /// Path of the script the statement at the path comes from, which its
+    /// line numbers refer to
+    pub fn origin(&self, path: &[usize]) -> &Path {
+        self.origins.get(path).unwrap_or(&self.path)
+    }
+
+    /// Comments of the script the statement at the path comes from
+    pub fn comments(&self, path: &[usize]) -> Option<&Comments> {
+        self.comments.get(self.origin(path))
+    }
+}
+
+/// Label along with the file defining it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectLabel {
+    /// Index of the file in [`Project::files`]
+    pub file: usize,
+    pub info: LabelInfo,
+}
+
+/// Problem found in one of the files of a project
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectDiagnostic {
+    pub path: PathBuf,
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    /// Statement the problem is about, when it got parsed. Statements of
+    /// included scripts are found in the scripts including them.
+    pub node: Option<NodeRef>,
+    /// Other statements the problem is about, in whichever files they are
+    pub related: Vec<ProjectRelated>,
+}
+
+/// Note pointing at another statement of a [`ProjectDiagnostic`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProjectRelated {
+    /// Script the statement comes from
+    pub path: PathBuf,
+    pub node: NodeRef,
+    pub message: String,
+}
+
+impl fmt::Display for ProjectDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}[{}]: {}",
+            self.path.display(),
+            self.severity,
+            self.code,
+            self.message
+        )?;
+        for related in &self.related {
+            write!(
+                f,
+                "\n  {}: note on line {}: {}",
+                related.path.display(),
+                related.node.index,
+                related.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Every script of a game, with labels resolved across files
+#[derive(Clone, Debug, Default)]
+pub struct Project {
+    pub files: Vec<ScriptFile>,
+    pub labels: HashMap<String, ProjectLabel>,
+    pub diagnostics: Vec<ProjectDiagnostic>,
+}
+
+/// Parses every `.rpy` file under the directory, in path order like Ren'Py.
+///
+/// `include "file.rpy"` statements are replaced by the statements of the named
+/// file, relative to the including one. Included files are only part of the
+/// project through the scripts including them, unless only scripts in a cycle
+/// of includes do. Their statements keep their own line numbers, and
+/// [`ScriptFile::origin`] tells their file.
+///
+/// Files that can't be read or fail to parse, statements with errors,
+/// includes that can't be resolved, labels defined more than once and jumps
+/// to labels defined nowhere are reported as diagnostics. Directories linked
+/// to more than once are read once.
+///
+/// With the `parallel` feature the scripts are parsed across threads, with the
+/// same result.
+pub fn parse_project(dir: &Path) -> Result<Project> {
+    parse_project_with_options(dir, &ParseOptions::default())
+}
+
+/// Like [`parse_project`], parsing every script with the options, except that
+/// comments are always attached for [`Project::lint`] to honor suppression
+/// comments. Fails with [`Cancelled`] when their observer stops the parse.
+/// With the `parallel` feature the observer hears about several scripts at
+/// once.
+pub fn parse_project_with_options(dir: &Path, options: &ParseOptions) -> Result<Project> {
+    let mut loader = Loader {
+        options: ParseOptions {
+            comments: CommentHandling::Attach,
+            ..options.clone()
+        },
+        ..Loader::default()
+    };
+
+    let mut paths = Vec::new();
+    let mut visited = HashSet::from([key(dir)]);
+    collect_scripts(dir, &mut paths, &mut visited, &mut loader.diagnostics)?;
+    paths.sort();
+
+    #[cfg(feature = "parallel")]
+    loader.preload(&paths)?;
+    for path in &paths {
+        loader.load(path)?;
+    }
+    loader.load_included()?;
+
+    // Scripts no other script includes, then ones that only scripts in a
+    // cycle of includes do, which would be left out otherwise
+    let included = loader.included();
+    let (roots, rest): (Vec<_>, Vec<_>) = paths
+        .into_iter()
+        .partition(|path| !included.contains(&key(path)));
+
+    let mut project = Project::default();
+    let mut reached = HashSet::new();
+    for path in roots {
+        loader.add(&mut project, path, &mut reached);
+    }
+    for path in rest {
+        if !reached.contains(&key(&path)) {
+            loader.add(&mut project, path, &mut reached);
+        }
+    }
+
+    project.diagnostics.splice(0..0, loader.diagnostics);
+    project.check_labels();
+    Ok(project)
+}
+
+/// Parses each script once, keyed by canonical path
+#[derive(Default)]
+struct Loader {
+    options: ParseOptions,
+    scripts: HashMap<PathBuf, Option<Vec<AST>>>,
+    comments: HashMap<PathBuf, Comments>,
+    /// Path each script was first found by
+    paths: HashMap<PathBuf, PathBuf>,
+    diagnostics: Vec<ProjectDiagnostic>,
+}
+
+impl Loader {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        if self.scripts.contains_key(&key(path)) {
+            return Ok(());
+        }
+
+        let parsed = parse_file(path, &self.options);
+        self.insert(path, parsed)
+    }
+
+    /// Parses the scripts across threads, keeping the results in path order
+    #[cfg(feature = "parallel")]
+    fn preload(&mut self, paths: &[PathBuf]) -> Result<()> {
+        use rayon::prelude::*;
+
+        let options = &self.options;
+        let parsed: Vec<_> = paths
+            .par_iter()
+            .map(|path| parse_file(path, options))
+            .collect();
+
+        for (path, parsed) in paths.iter().zip(parsed) {
+            if !self.scripts.contains_key(&key(path)) {
+                self.insert(path, parsed)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, path: &Path, parsed: Result<ParsedFile>) -> Result<()> {
+        let (parsed, diagnostics) = parsed?;
+        self.diagnostics.extend(diagnostics);
+        let ast = parsed.map(|(ast, comments)| {
+            self.comments.insert(key(path), comments);
+            ast
+        });
+        self.scripts.insert(key(path), ast);
+        self.paths.insert(key(path), path.to_path_buf());
+        Ok(())
+    }
+
+    /// Loads the scripts included by the loaded ones, such as ones outside of
+    /// the project directory, until every include that names a file is loaded
+    fn load_included(&mut self) -> Result<()> {
+        let mut pending: Vec<PathBuf> = self.paths.keys().cloned().collect();
+        while let Some(script) = pending.pop() {
+            for target in self.includes(&script) {
+                let key = key(&target);
+                if target.is_file() && !self.scripts.contains_key(&key) {
+                    self.load(&target)?;
+                    pending.push(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths named by the include statements of a loaded script
+    fn includes(&self, script: &Path) -> Vec<PathBuf> {
+        let mut rv = Vec::new();
+        if let Some(Some(ast)) = self.scripts.get(script) {
+            walk(ast, &mut |_, node| {
+                if let AST::Include(_, target) = node {
+                    rv.push(resolve(&self.paths[script], target));
+                }
+            });
+        }
+        rv
+    }
+
+    /// Scripts named by an include statement of another script
+    fn included(&self) -> HashSet<PathBuf> {
+        self.scripts
+            .keys()
+            .flat_map(|script| self.includes(script))
+            .map(|path| key(&path))
+            .collect()
+    }
+
+    /// Adds the script to the project with its includes replaced, noting the
+    /// scripts `reached` by doing so
+    fn add(&mut self, project: &mut Project, path: PathBuf, reached: &mut HashSet<PathBuf>) {
+        let root = key(&path);
+        let Some(Some(ast)) = self.scripts.get(&root).cloned() else {
+            return;
+        };
+
+        let mut lookup = |script: &PathBuf, target: &str| {
+            let key = key(&resolve(&self.paths[script], target));
+            match self.scripts.get(&key) {
+                Some(Some(ast)) => Ok((key, ast.clone())),
+                Some(None) => Err((
+                    Code::IncludeUnreadable,
+                    format!("included file '{}' can't be read or parsed", target),
+                )),
+                None => Err((
+                    Code::IncludeNotFound,
+                    format!("included file '{}' not found", target),
+                )),
+            }
+        };
+        let expanded = include::expand(root.clone(), ast, &mut lookup);
+
+        for problem in expanded.problems {
+            self.diagnostics.push(ProjectDiagnostic {
+                path: self.paths[&problem.file].clone(),
+                severity: Severity::Error,
+                code: problem.code,
+                message: format!("on line {}: {}", problem.line_number, problem.message),
+                node: None,
+                related: Vec::new(),
+            });
+        }
+
+        let comments = std::iter::once(&root)
+            .chain(&expanded.included)
+            .filter_map(|script| {
+                let comments = self.comments.get(script)?;
+                Some((self.paths[script].clone(), comments.clone()))
+            })
+            .collect();
+
+        reached.insert(root);
+        reached.extend(expanded.included);
+        let origins = expanded.origins.map(|script| self.paths[&script].clone());
+        project.add_file(ScriptFile {
+            path,
+            ast: expanded.ast,
+            origins,
+            comments,
+        });
+    }
+}
+
+/// Statements and comments of a script, unless it failed to parse, and its
+/// problems
+type ParsedFile = (Option<(Vec<AST>, Comments)>, Vec<ProjectDiagnostic>);
+
+fn parse_file(path: &Path, options: &ParseOptions) -> Result<ParsedFile> {
+    let diagnostic = |severity: Severity, code: Code, message: String| ProjectDiagnostic {
+        path: path.to_path_buf(),
+        severity,
+        code,
+        message,
+        node: None,
+        related: Vec::new(),
+    };
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let message = format!("can't read script: {}", err);
+            return Ok((
+                None,
+                vec![diagnostic(Severity::Error, Code::Unreadable, message)],
+            ));
+        }
+    };
+
+    let filename = path.to_string_lossy();
+    match parse_script_from_bytes(bytes, &filename, options) {
+        Ok(script) => {
+            let problems = script.diagnostics.iter();
+            let diagnostics = problems
+                .map(|(severity, error)| diagnostic(severity, error.code, error.to_string()))
+                .collect();
+            let comments = script.comments.unwrap_or_default();
+            Ok((Some((script.ast, comments)), diagnostics))
+        }
+        Err(err) if err.is::<Cancelled>() => Err(err),
+        Err(err) => {
+            let code = err
+                .downcast_ref::<ParseError>()
+                .map_or(Code::Other, |e| e.code);
+            let message = err.to_string();
+            Ok((None, vec![diagnostic(Severity::Error, code, message)]))
+        }
+    }
+}
+
+/// Path of an included script, relative to the directory of the including one
+fn resolve(path: &Path, target: &str) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).join(target)
+}
+
+/// Identity of a script file, regardless of how its path is spelled
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Project {
+    fn add_file(&mut self, script: ScriptFile) {
+        let file = self.files.len();
+        let mut defined: Vec<(String, LabelInfo)> = labels(&script.ast).into_iter().collect();
+        defined.sort_by(|(_, a), (_, b)| a.node.path.cmp(&b.node.path));
+
+        for (name, info) in defined {
+            if let Some(existing) = self.labels.get(&name) {
+                let existing_file = &self.files[existing.file];
+                self.diagnostics.push(ProjectDiagnostic {
+                    path: script.origin(&info.node.path).to_path_buf(),
+                    severity: Severity::Error,
+                    code: Code::DuplicateLabel,
+                    message: format!(
+                        "label '{}' on line {} is already defined in {} on line {}",
+                        name,
+                        info.line_number,
+                        existing_file.origin(&existing.info.node.path).display(),
+                        existing.info.line_number
+                    ),
+                    node: Some(info.node),
+                    related: vec![ProjectRelated {
+                        path: existing_file.origin(&existing.info.node.path).to_path_buf(),
+                        node: existing.info.node.clone(),
+                        message: format!("'{}' is first defined here", name),
+                    }],
+                });
+                continue;
+            }
+            self.labels.insert(name, ProjectLabel { file, info });
+        }
+
+        self.files.push(script);
+    }
+
+    fn check_labels(&mut self) {
+        let linter = Linter::new().with(UndefinedLabel).with(DuplicateLabel);
+        let diagnostics = self.lint(&linter);
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Runs the linter over every file, resolving labels and characters
+    /// across the project. Comments disable rules on statements as with
+    /// [`Linter::check_script`], in the script the statements come from.
+    pub fn lint(&self, linter: &Linter) -> Vec<ProjectDiagnostic> {
+        let labels: HashMap<String, LabelInfo> = self
+            .labels
+            .iter()
+            .map(|(name, label)| (name.clone(), label.info.clone()))
+            .collect();
+
+        let mut characters: BTreeMap<String, CharacterUsage> = BTreeMap::new();
+        for file in &self.files {
+            for (name, usage) in character_usage(&file.ast) {
+                characters.entry(name).or_default().merge(usage);
+            }
+        }
+
+        let mut rv = Vec::new();
+        for file in &self.files {
+            let ctx = LintContext {
+                ast: &file.ast,
+                labels: labels.clone(),
+                characters: characters.clone(),
+                comments: None,
+            };
+            let mut found: Vec<_> = linter
+                .findings(&ctx)
+                .into_iter()
+                .filter(|(rule, found)| {
+                    let comments = file.comments(&found.node.path);
+                    !comments.is_some_and(|c| is_suppressed(c, rule, found.node.index))
+                })
+                .map(|(_, found)| found)
+                .collect();
+            found.sort_by(|a, b| a.node.path.cmp(&b.node.path));
+
+            rv.extend(found.into_iter().map(|found| {
+                ProjectDiagnostic {
+                    path: file.origin(&found.node.path).to_path_buf(),
+                    severity: found.severity,
+                    code: found.code,
+                    message: found.message,
+                    node: Some(found.node),
+                    related: found
+                        .related
+                        .into_iter()
+                        .map(|related| ProjectRelated {
+                            path: file.origin(&related.node.path).to_path_buf(),
+                            node: related.node,
+                            message: related.message,
+                        })
+                        .collect(),
+                }
+            }));
+        }
+        rv
+    }
+
+    /// The file and statements of a label
+    pub fn label(&self, name: &str) -> Option<(&ScriptFile, &AST)> {
+        let label = self.labels.get(name)?;
+        let file = &self.files[label.file];
+        Some((file, label.info.node.resolve(&file.ast)?))
+    }
+}
+
+/// Finds the scripts in the directory and the ones under it, failing only
+/// when the directory itself can't be read. Directories under it that can't
+/// be read are reported, and ones already `visited` through symbolic links
+/// are skipped.
+fn collect_scripts(
+    dir: &Path,
+    paths: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+    diagnostics: &mut Vec<ProjectDiagnostic>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                diagnostics.push(unreadable(dir, err));
+                continue;
+            }
+        };
+
+        if path.is_dir() {
+            if !visited.insert(key(&path)) {
+                continue;
+            }
+            if let Err(err) = collect_scripts(&path, paths, visited, diagnostics) {
+                diagnostics.push(unreadable(&path, err));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rpy") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn unreadable(dir: &Path, err: std::io::Error) -> ProjectDiagnostic {
+    ProjectDiagnostic {
+        path: dir.to_path_buf(),
+        severity: Severity::Error,
+        code: Code::Unreadable,
+        message: format!("can't read directory: {}", err),
+        node: None,
+        related: Vec::new(),
+    }
+}
@@ -0,0 +1,127 @@
+use crate::diagnostics::{Code, Diagnostics, Severity};
+use crate::parsers::ParseError;
+use miette::{Diagnostic, GraphicalReportHandler, LabeledSpan, NamedSource, SourceCode};
+use std::fmt;
+
+/// A parse error or warning along with the script it was found in, for miette
+/// to show with the offending line
+#[derive(Debug)]
+pub struct Report {
+    pub severity: Severity,
+    pub error: ParseError,
+    source: NamedSource<String>,
+    /// Byte range of the offending text in the source
+    span: Option<(usize, usize)>,
+}
+
+impl Report {
+    pub fn new(severity: Severity, error: &ParseError, source: &str) -> Self {
+        Report {
+            severity,
+            error: error.clone(),
+            source: NamedSource::new(&error.filename, source.to_string()),
+            span: span(error, source),
+        }
+    }
+
+    /// The report as miette prints it, with the source line and a pointer
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        match GraphicalReportHandler::new().render_report(&mut out, self) {
+            Ok(()) => out,
+            Err(_) => self.error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error.message)
+    }
+}
+
+impl std::error::Error for Report {}
+
+impl Diagnostic for Report {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(match self.severity {
+            Severity::Error => miette::Severity::Error,
+            Severity::Warning => miette::Severity::Warning,
+        })
+    }
+
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.error.code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = help(self.error.code)?;
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.source)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let (offset, len) = self.span?;
+        let label = LabeledSpan::new(Some("here".to_string()), offset, len);
+        Some(Box::new(std::iter::once(label)))
+    }
+}
+
+/// Reports for every problem of a script, in order
+pub fn reports(diagnostics: &Diagnostics, source: &str) -> Vec<Report> {
+    diagnostics
+        .iter()
+        .map(|(severity, error)| Report::new(severity, error, source))
+        .collect()
+}
+
+/// The word at the error's position, found from its line and column
+fn span(error: &ParseError, source: &str) -> Option<(usize, usize)> {
+    let (line, pos, column) = (error.line.as_deref()?, error.pos?, error.column?);
+    let physical_line = error.line_number + line.get(..pos)?.matches('\n').count();
+
+    let mut line_start = match physical_line {
+        0 => return None,
+        1 => 0,
+        n => source.match_indices('\n').nth(n - 2)?.0 + 1,
+    };
+    let mut text = source[line_start..].split('\n').next()?;
+    if let Some(rest) = text.strip_prefix('\u{feff}') {
+        line_start += '\u{feff}'.len_utf8();
+        text = rest;
+    }
+
+    let start = text
+        .char_indices()
+        .nth(column.saturating_sub(1))
+        .map_or(text.trim_end().len(), |(i, _)| i);
+    // The lexer reports positions before the whitespace it skips
+    let start = start + (text[start..].len() - text[start..].trim_start().len());
+    let len = text[start..]
+        .find(char::is_whitespace)
+        .unwrap_or(text.len() - start);
+
+    Some((line_start + start, len))
+}
+
+fn help(code: Code) -> Option<&'static str> {
+    Some(match code {
+        Code::TabCharacter | Code::TabExpanded => "indent with spaces instead of tabs",
+        Code::UnterminatedLine => "close the string or parenthesis opened on this line",
+        Code::UnexpectedBlock | Code::IndentMismatch => {
+            "lines of a block have to be indented by the same number of spaces"
+        }
+        Code::NestingTooDeep => "move deeply nested statements into labels of their own",
+        Code::SuspiciousIndentation => "indent every block by the same number of spaces",
+        Code::DeprecatedForm => "see the Ren'Py documentation for the replacement",
+        Code::MissingSayText => "put the line of dialogue in quotes after the speaker",
+        Code::UnknownStatement => {
+            "statements start with a keyword, a character name or a string of dialogue"
+        }
+        Code::InvalidAudioChannel => "play and stop take either music or sound",
+        _ => return None,
+    })
+}
@@ -0,0 +1,146 @@
+use crate::parsers::{node_at, walk, NodeRef, AST};
+use crate::trivia::Comment;
+use crate::ParsedScript;
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// What to look for: a case-sensitive string or a regular expression
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Ok(Pattern::Regex(Regex::new(pattern)?))
+    }
+
+    /// Byte ranges of the non-overlapping, non-empty occurrences in `text`
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        match self {
+            Pattern::Text(needle) if needle.is_empty() => Vec::new(),
+            Pattern::Text(needle) => text
+                .match_indices(needle.as_str())
+                .map(|(start, found)| start..start + found.len())
+                .collect(),
+            Pattern::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(text: &str) -> Self {
+        Pattern::Text(text.to_string())
+    }
+}
+
+impl From<Regex> for Pattern {
+    fn from(regex: Regex) -> Self {
+        Pattern::Regex(regex)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    Dialogue,
+    Comment,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub kind: MatchKind,
+    /// The say statement, or the statement a comment is attached to; none for
+    /// comments after the last statement
+    pub node: Option<NodeRef>,
+    pub line_number: usize,
+    /// Names of the labels around the node, outermost first
+    pub labels: Vec<String>,
+    /// Speaker of the dialogue
+    pub speaker: Option<String>,
+    /// Dialogue or comment text the pattern was found in
+    pub text: String,
+    /// Byte range of the match within `text`
+    pub span: Range<usize>,
+}
+
+/// Finds the pattern in the text of every say statement, in document order
+pub fn search(ast: &[AST], pattern: &Pattern) -> Vec<Match> {
+    let mut rv = Vec::new();
+    walk(ast, &mut |path, node| {
+        if let AST::Say(line_number, speaker, text, _) = node {
+            for span in pattern.find(text) {
+                rv.push(Match {
+                    kind: MatchKind::Dialogue,
+                    node: Some(NodeRef::new(path, node)),
+                    line_number: *line_number,
+                    labels: labels(ast, path),
+                    speaker: speaker.clone(),
+                    text: text.clone(),
+                    span,
+                });
+            }
+        }
+    });
+    rv
+}
+
+/// Like [`search`], also looking in the comments of the script when it was
+/// parsed with them attached, in line order with dialogue before comments
+pub fn search_script(script: &ParsedScript, pattern: &Pattern) -> Vec<Match> {
+    let mut rv = search(&script.ast, pattern);
+    let Some(comments) = &script.comments else {
+        return rv;
+    };
+
+    let mut paths = HashMap::new();
+    walk(&script.ast, &mut |path, node| {
+        paths.insert(node.index(), path.to_vec());
+    });
+
+    let mut push = |comment: &Comment, path: Option<&Vec<usize>>| {
+        for span in pattern.find(&comment.text) {
+            rv.push(Match {
+                kind: MatchKind::Comment,
+                node: path.and_then(|path| {
+                    node_at(&script.ast, path).map(|node| NodeRef::new(path, node))
+                }),
+                line_number: comment.line_number,
+                labels: path
+                    .map(|path| labels(&script.ast, path))
+                    .unwrap_or_default(),
+                speaker: None,
+                text: comment.text.clone(),
+                span,
+            });
+        }
+    };
+
+    for (index, trivia) in &comments.trivia {
+        for comment in trivia.leading.iter().chain(&trivia.trailing) {
+            push(comment, paths.get(index));
+        }
+    }
+    for comment in &comments.dangling {
+        push(comment, None);
+    }
+
+    rv.sort_by_key(|found| found.line_number);
+    rv
+}
+
+/// Labels along the path through nested blocks, including the node itself
+fn labels(ast: &[AST], path: &[usize]) -> Vec<String> {
+    (1..=path.len())
+        .filter_map(|depth| match node_at(ast, &path[..depth]) {
+            Some(AST::Label(_, name, _, _)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
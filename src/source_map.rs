@@ -0,0 +1,60 @@
+use crate::lexer::{Block, Lexer};
+use crate::parsers::{parse_block, AST};
+use crate::{group_logical_lines, parse_logical_lines};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Byte ranges of statements in their source, keyed by line number
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    spans: BTreeMap<usize, Range<usize>>,
+}
+
+impl SourceMap {
+    pub fn new(blocks: &[Block]) -> Self {
+        fn collect(blocks: &[Block], spans: &mut BTreeMap<usize, Range<usize>>) {
+            for block in blocks {
+                spans.insert(block.line_number, block.span.clone());
+                collect(&block.subblocks, spans);
+            }
+        }
+
+        let mut spans = BTreeMap::new();
+        collect(blocks, &mut spans);
+        SourceMap { spans }
+    }
+
+    /// Byte range of the logical line starting at the given line number
+    pub fn line(&self, line_number: usize) -> Option<Range<usize>> {
+        self.spans.get(&line_number).cloned()
+    }
+
+    /// Byte range of a parsed node, including any statements nested under it
+    pub fn span(&self, node: &AST) -> Option<Range<usize>> {
+        let mut span = self.line(node.index())?;
+
+        let last = node.block().and_then(|block| block.last());
+        if let Some(end) = last.and_then(|last| self.span(last)).map(|last| last.end) {
+            span.end = span.end.max(end);
+        }
+
+        Some(span)
+    }
+}
+
+/// Parses a script along with the byte ranges of its statements
+pub fn parse_with_source_map(
+    content: &str,
+    filename: &str,
+) -> Result<(Vec<AST>, SourceMap, Vec<String>)> {
+    let lines = parse_logical_lines(content, filename)?;
+    let blocks = group_logical_lines(lines)?;
+    let source_map = SourceMap::new(&blocks);
+
+    let mut lexer = Lexer::borrowed(&blocks, true);
+    let (ast, errors) = parse_block(&mut lexer);
+    let errors = errors.iter().map(ToString::to_string).collect();
+
+    Ok((ast, source_map, errors))
+}
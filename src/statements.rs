@@ -0,0 +1,99 @@
+use crate::lexer::Lexer;
+use crate::parsers::AST;
+use crate::prelude::*;
+use alloc::sync::Arc;
+use anyhow::Result;
+use core::fmt;
+
+/// A statement added to the language, like Ren'Py's creator-defined statements
+pub trait StatementParser: Send + Sync {
+    /// Word the statement starts with
+    fn keyword(&self) -> &str;
+
+    /// Parses the statement from after its keyword, advancing the lexer to the
+    /// next statement. `loc` is the line the statement starts on.
+    fn parse(&self, l: &mut Lexer, loc: usize) -> Result<AST>;
+
+    /// Source text of a node made by this parser, when it differs from what
+    /// the writer would produce
+    fn render(&self, _node: &AST) -> Option<String> {
+        None
+    }
+}
+
+/// A statement keeping the rest of its line as written, as an [`AST::Custom`] node
+#[derive(Clone, Debug)]
+pub struct LineStatement {
+    pub keyword: String,
+}
+
+impl LineStatement {
+    pub fn new(keyword: &str) -> Self {
+        LineStatement {
+            keyword: keyword.to_string(),
+        }
+    }
+}
+
+impl StatementParser for LineStatement {
+    fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    fn parse(&self, l: &mut Lexer, loc: usize) -> Result<AST> {
+        let text = l.rest();
+        l.expect_noblock(&format!("{} statement", self.keyword))?;
+        l.advance();
+        Ok(AST::Custom(loc, self.keyword.clone(), text.into_owned()))
+    }
+}
+
+/// Statement parsers consulted before the built-in statements, in order
+#[derive(Clone, Default)]
+pub struct Statements(Vec<Arc<dyn StatementParser>>);
+
+impl Statements {
+    pub fn new() -> Self {
+        Statements::default()
+    }
+
+    pub fn with(mut self, parser: impl StatementParser + 'static) -> Self {
+        self.push(parser);
+        self
+    }
+
+    pub fn push(&mut self, parser: impl StatementParser + 'static) {
+        self.0.push(Arc::new(parser));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn StatementParser> {
+        self.0.iter().map(|parser| parser.as_ref())
+    }
+
+    pub fn keywords(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|parser| parser.keyword())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Source text of the node from the first parser rendering it
+    pub fn render(&self, node: &AST) -> Option<String> {
+        self.iter().find_map(|parser| parser.render(node))
+    }
+}
+
+impl fmt::Debug for Statements {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.keywords()).finish()
+    }
+}
+
+impl PartialEq for Statements {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for Statements {}
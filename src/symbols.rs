@@ -0,0 +1,104 @@
+use crate::parsers::{walk, NodeRef, AST};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelInfo {
+    pub node: NodeRef,
+    pub line_number: usize,
+    pub parameters: Option<String>,
+    /// Qualified name of the enclosing label, for labels nested in another
+    /// label's body
+    pub parent: Option<String>,
+    /// Number of blocks enclosing the label, 0 for top-level labels
+    pub depth: usize,
+}
+
+/// Fully qualified name of a label or jump target: local names like `.sub`
+/// belong to the global label before them, making `start.sub`
+pub fn qualified_name(name: &str, global: Option<&str>) -> String {
+    match global {
+        Some(global) if name.starts_with('.') => format!("{}{}", global, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Visits every node in document order like [`walk`], along with the global
+/// label the local names in it belong to
+pub fn walk_scoped<'a>(ast: &'a [AST], f: &mut impl FnMut(&[usize], &'a AST, Option<&'a str>)) {
+    let mut global = None;
+    walk(ast, &mut |path, node| {
+        if let AST::Label(_, name, _, _) = node {
+            if !name.contains('.') {
+                global = Some(name.as_str());
+            }
+        }
+        f(path, node, global);
+    });
+}
+
+/// Collects every label in the script by its qualified name, see
+/// [`qualified_name`]; the first definition of a name wins
+pub fn labels(ast: &[AST]) -> HashMap<String, LabelInfo> {
+    let mut rv = HashMap::new();
+    // Innermost enclosing labels, as (depth, qualified name)
+    let mut enclosing: Vec<(usize, String)> = Vec::new();
+
+    walk_scoped(ast, &mut |path, node, global| {
+        let depth = path.len() - 1;
+        while enclosing.last().is_some_and(|(d, _)| *d >= depth) {
+            enclosing.pop();
+        }
+
+        if let AST::Label(index, name, _, parameters) = node {
+            let name = qualified_name(name, global);
+            rv.entry(name.clone()).or_insert_with(|| LabelInfo {
+                node: NodeRef::new(path, node),
+                line_number: *index,
+                parameters: parameters.clone(),
+                parent: enclosing.last().map(|(_, parent)| parent.clone()),
+                depth,
+            });
+            enclosing.push((depth, name));
+        }
+    });
+
+    rv
+}
+
+/// Jump whose target label is not defined anywhere in the script
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnresolvedJump {
+    pub target: String,
+    /// Script the jump is in
+    pub filename: String,
+    pub node: NodeRef,
+    pub line_number: usize,
+}
+
+/// Checks every jump against the defined labels. Local targets like `.sub`
+/// resolve against the global label the jump is under, and fully qualified
+/// ones like `start.sub` as written. `filename` names the script in the jumps
+/// reported.
+pub fn resolve_targets(ast: &[AST], filename: &str) -> Result<(), Vec<UnresolvedJump>> {
+    let labels = labels(ast);
+    let mut unresolved = Vec::new();
+
+    walk_scoped(ast, &mut |path, node, global| {
+        if let AST::Jump(index, target, false) = node {
+            if !labels.contains_key(&qualified_name(target, global)) {
+                unresolved.push(UnresolvedJump {
+                    target: target.clone(),
+                    filename: filename.to_string(),
+                    node: NodeRef::new(path, node),
+                    line_number: *index,
+                });
+            }
+        }
+    });
+
+    if unresolved.is_empty() {
+        Ok(())
+    } else {
+        Err(unresolved)
+    }
+}
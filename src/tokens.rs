@@ -0,0 +1,117 @@
+use crate::lexer::compiled;
+use std::ops::Range;
+
+/// Words starting or modifying the statements the parser knows
+pub const KEYWORDS: [&str; 16] = [
+    "define",
+    "expression",
+    "fadeout",
+    "game_mechanic",
+    "hide",
+    "include",
+    "init",
+    "jump",
+    "label",
+    "llm_generate",
+    "onlayer",
+    "play",
+    "return",
+    "scene",
+    "show",
+    "stop",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Name,
+    String,
+    Number,
+    Punct,
+    Comment,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// Source text of the token
+    pub text: &'a str,
+    /// Byte range in the source
+    pub span: Range<usize>,
+}
+
+/// Iterator over the tokens of a script, without parsing statements.
+///
+/// Unterminated strings run to the end of the source, and any character that
+/// starts no other token is punctuation, so tokenizing never fails.
+pub struct Tokens<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let pos = if source.starts_with('\u{feff}') { 3 } else { 0 };
+        Tokens { source, pos }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let rest = &self.source[self.pos..];
+        let skipped = rest.len() - rest.trim_start().len();
+        self.pos += skipped;
+
+        let rest = &self.source[self.pos..];
+        let c = rest.chars().next()?;
+
+        let patterns: [(TokenKind, &str); 7] = [
+            (TokenKind::Comment, r"^#[^\n]*"),
+            (TokenKind::String, r#"^r?"""(?s:[^\\]|\\.)*?""""#),
+            (TokenKind::String, r"^r?'''(?s:[^\\]|\\.)*?'''"),
+            (TokenKind::String, r"^r?```(?s:[^\\]|\\.)*?```"),
+            (
+                TokenKind::String,
+                r#"^r?(?:"(?s:[^\\"]|\\.)*"|'(?s:[^\\']|\\.)*'|`(?s:[^\\`]|\\.)*`)"#,
+            ),
+            (
+                TokenKind::Number,
+                r"^(?:[0-9]+\.?[0-9]*|\.[0-9]+)(?:[eE][-+]?[0-9]+)?",
+            ),
+            (
+                TokenKind::Name,
+                r"^[a-zA-Z_\u00a0-\ufffd][0-9a-zA-Z_\u00a0-\ufffd]*",
+            ),
+        ];
+
+        let matched = patterns.iter().find_map(|(kind, pattern)| {
+            let m = compiled(pattern).ok()?.find(rest)?;
+            Some((*kind, m.end()))
+        });
+
+        let (kind, len) = match matched {
+            Some((TokenKind::Name, len)) if KEYWORDS.contains(&&rest[..len]) => {
+                (TokenKind::Keyword, len)
+            }
+            Some(matched) => matched,
+            // Unterminated strings
+            None if matches!(c, '"' | '\'' | '`') => (TokenKind::String, rest.len()),
+            None => (TokenKind::Punct, c.len_utf8()),
+        };
+
+        let span = self.pos..self.pos + len;
+        self.pos += len;
+
+        Some(Token {
+            kind,
+            text: &self.source[span.clone()],
+            span,
+        })
+    }
+}
+
+pub fn tokenize(source: &str) -> Vec<Token<'_>> {
+    Tokens::new(source).collect()
+}
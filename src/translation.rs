@@ -0,0 +1,101 @@
+use crate::parsers::{NodeRef, AST};
+use crate::symbols::{qualified_name, walk_scoped};
+use crate::writer::quote;
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranslatableString {
+    /// Identifier used by `translate` blocks, computed the way Ren'Py does
+    pub identifier: String,
+    /// Qualified name of the innermost label, see [`qualified_name`]
+    pub label: Option<String>,
+    pub text: String,
+    /// Source code of the statement the identifier is derived from
+    pub code: String,
+    pub node: NodeRef,
+}
+
+/// Collects say statements with Ren'Py-compatible translation identifiers.
+///
+/// Identifiers are the qualified name of the enclosing label followed by the
+/// first 8 hex digits of the MD5 of the statement's code, with `_1`, `_2`, ...
+/// appended on collision.
+pub fn translatable_strings(ast: &[AST]) -> Vec<TranslatableString> {
+    let mut rv = Vec::new();
+    let mut identifiers = HashSet::new();
+    // Innermost enclosing labels, as (depth, qualified name)
+    let mut enclosing: Vec<(usize, String)> = Vec::new();
+
+    walk_scoped(ast, &mut |path, node, global| {
+        let depth = path.len();
+        while enclosing.last().is_some_and(|(d, _)| *d >= depth) {
+            enclosing.pop();
+        }
+
+        let (who, what) = match node {
+            AST::Label(_, name, _, _) => {
+                enclosing.push((depth, qualified_name(name, global)));
+                return;
+            }
+            AST::Say(_, who, what, _) => (who, what),
+            _ => return,
+        };
+        let label = enclosing.last().map(|(_, name)| name.as_str());
+        let code = match who {
+            Some(who) => format!("{} {}", who, encode_say_string(what)),
+            None => encode_say_string(what),
+        };
+
+        let digest = format!("{:x}", md5::compute(format!("{}\r\n", code)));
+        let base = match label {
+            Some(label) => format!("{}_{}", label.replace('.', "_"), &digest[..8]),
+            None => digest[..8].to_string(),
+        };
+
+        let mut identifier = base.clone();
+        let mut suffix = 1;
+        while identifiers.contains(&identifier) {
+            identifier = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        identifiers.insert(identifier.clone());
+
+        rv.push(TranslatableString {
+            identifier,
+            label: label.map(str::to_string),
+            text: what.clone(),
+            code,
+            node: NodeRef::new(path, node),
+        });
+    });
+
+    rv
+}
+
+/// Quotes dialogue the way Ren'Py's `encode_say_string` does for the code
+/// identifiers are hashed from, escaping every space after the first of a run
+fn encode_say_string(what: &str) -> String {
+    let quoted = quote(what);
+    let mut rv = String::with_capacity(quoted.len());
+    let mut after_space = false;
+    for c in quoted.chars() {
+        if c == ' ' && after_space {
+            rv.push('\\');
+        }
+        rv.push(c);
+        after_space = c == ' ';
+    }
+    rv
+}
+
+/// Renders `translate` blocks for the strings, pre-filled with the original text
+pub fn translate_template(strings: &[TranslatableString], language: &str) -> String {
+    let mut out = String::new();
+    for string in strings {
+        out.push_str(&format!(
+            "translate {} {}:\n\n    # {}\n    {}\n\n",
+            language, string.identifier, string.code, string.code
+        ));
+    }
+    out
+}
@@ -0,0 +1,159 @@
+use crate::options::{CommentHandling, ParseOptions};
+use crate::parse_script;
+use crate::parsers::{walk, AST};
+use crate::prelude::*;
+use alloc::collections::{BTreeMap, BTreeSet};
+use anyhow::Result;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    pub line_number: usize,
+    /// Text after the `#`
+    pub text: String,
+    /// Whether code precedes the comment on its line
+    pub trailing: bool,
+    /// Blank lines directly above the comment, for comment lines
+    pub blank_lines_before: usize,
+}
+
+/// Comments belonging to a statement
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trivia {
+    /// Comment lines between the previous statement and this one
+    pub leading: Vec<Comment>,
+    /// Comments at the end of the statement's lines
+    pub trailing: Vec<Comment>,
+    /// Blank lines directly above the statement, below its leading comments
+    pub blank_lines_before: usize,
+}
+
+/// Comments and blank lines attached to the statements they describe, keyed by statement index
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comments {
+    pub trivia: BTreeMap<usize, Trivia>,
+    /// Comments after the last statement
+    pub dangling: Vec<Comment>,
+}
+
+impl Comments {
+    /// Attaches comment lines to the following statement and end-of-line
+    /// comments to the statement they share a line with. Blank lines are
+    /// counted towards the comment or statement below them.
+    pub fn new(ast: &[AST], comments: Vec<Comment>, blank_lines: &[usize]) -> Self {
+        let mut indices = Vec::new();
+        walk(ast, &mut |_, node| indices.push(node.index()));
+        indices.sort_unstable();
+        indices.dedup();
+
+        let blank_lines: BTreeSet<usize> = blank_lines.iter().copied().collect();
+        let blank_lines_before = |line_number: usize| {
+            (1..line_number)
+                .rev()
+                .take_while(|line| blank_lines.contains(line))
+                .count()
+        };
+
+        let mut rv = Comments::default();
+        for &index in &indices {
+            let blank_lines = blank_lines_before(index);
+            if blank_lines > 0 {
+                rv.trivia.entry(index).or_default().blank_lines_before = blank_lines;
+            }
+        }
+
+        for mut comment in comments {
+            if !comment.trailing {
+                comment.blank_lines_before = blank_lines_before(comment.line_number);
+            }
+
+            let after = indices.partition_point(|&i| i <= comment.line_number);
+            let owner = if comment.trailing {
+                after.checked_sub(1).map(|position| indices[position])
+            } else {
+                indices.get(after).copied()
+            };
+
+            match owner {
+                Some(index) => {
+                    let trivia = rv.trivia.entry(index).or_default();
+                    if comment.trailing {
+                        trivia.trailing.push(comment);
+                    } else {
+                        trivia.leading.push(comment);
+                    }
+                }
+                None => rv.dangling.push(comment),
+            }
+        }
+
+        rv
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Trivia> {
+        self.trivia.get(&index)
+    }
+}
+
+/// Character sequence ending the lines of a script
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+/// How a script file is laid out beyond its text, for writing it back the same way
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceFormat {
+    /// Line ending used by most of the lines
+    pub line_ending: LineEnding,
+    /// Whether the script starts with a byte order mark
+    pub bom: bool,
+}
+
+impl SourceFormat {
+    pub fn detect(source: &str) -> Self {
+        let lines = source.matches('\n').count();
+        let crlf = source.matches("\r\n").count();
+
+        SourceFormat {
+            line_ending: if crlf * 2 > lines {
+                LineEnding::CrLf
+            } else {
+                LineEnding::Lf
+            },
+            bom: source.starts_with('\u{feff}'),
+        }
+    }
+
+    /// Converts text with `\n` line endings and no byte order mark to this format
+    pub fn apply(&self, text: &str) -> String {
+        let mut rv = String::with_capacity(text.len());
+        if self.bom {
+            rv.push('\u{feff}');
+        }
+        match self.line_ending {
+            LineEnding::Lf => rv.push_str(text),
+            LineEnding::CrLf => rv.push_str(&text.replace('\n', "\r\n")),
+        }
+        rv
+    }
+}
+
+/// Parses a script, keeping its comments and blank lines alongside the AST
+pub fn parse_scenario_with_comments(
+    content: &str,
+    filename: &str,
+) -> Result<(Vec<AST>, Comments, Vec<String>)> {
+    let options = ParseOptions {
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(content, filename, &options)?;
+    let errors = script
+        .diagnostics
+        .errors()
+        .map(ToString::to_string)
+        .collect();
+    Ok((script.ast, script.comments.unwrap_or_default(), errors))
+}
@@ -0,0 +1,18 @@
+use crate::diagnostics::Diagnostic;
+use crate::lint::rules::{HideWithoutShow, StopWithoutPlay, UndefinedCharacter, UndefinedLabel};
+use crate::lint::Linter;
+use crate::parsers::AST;
+
+/// Checks cross-statement invariants the parser can't see: jumps to undefined
+/// labels, undefined speakers, and hides and stops of images and channels that
+/// aren't shown or playing. See [`crate::lint`] for the full set of checks.
+///
+/// Image and channel state is tracked in document order, ignoring jumps.
+pub fn validate(ast: &[AST]) -> Vec<Diagnostic> {
+    Linter::new()
+        .with(UndefinedLabel)
+        .with(UndefinedCharacter)
+        .with(HideWithoutShow)
+        .with(StopWithoutPlay)
+        .check(ast)
+}
@@ -0,0 +1,349 @@
+use crate::options::{CommentHandling, ParseOptions, WhitespacePolicy};
+use crate::parse_script;
+use crate::parsers::{Quoting, AST};
+use crate::statements::Statements;
+use crate::trivia::{Comment, Comments, SourceFormat};
+use anyhow::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    /// Quotes a string, escaping it the way Lexer::string unescapes it
+    pub fn quote(&self, text: &str) -> String {
+        let delim = match self {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+        };
+        escaped(text, delim, 1)
+    }
+}
+
+/// Quotes a string the way it was written. Raw strings that can't hold the
+/// text as it is are written with escapes instead.
+pub fn quote_as(text: &str, quoting: Quoting) -> String {
+    let delim = quoting.quote_char;
+    let quotes = if quoting.triple { 3 } else { 1 };
+    if quoting.raw && fits_raw(text, delim, quoting.triple) {
+        let delims = delim.to_string().repeat(quotes);
+        format!("r{}{}{}", delims, text, delims)
+    } else {
+        escaped(text, delim, quotes)
+    }
+}
+
+fn escaped(text: &str, delim: char, quotes: usize) -> String {
+    let mut rv = String::with_capacity(text.len() + 2 * quotes);
+    let delims = delim.to_string().repeat(quotes);
+    rv.push_str(&delims);
+    for c in text.chars() {
+        match c {
+            '\\' => rv.push_str("\\\\"),
+            '\n' => rv.push_str("\\n"),
+            c if c == delim => {
+                rv.push('\\');
+                rv.push(c);
+            }
+            _ => rv.push(c),
+        }
+    }
+    rv.push_str(&delims);
+    rv
+}
+
+/// Whether the lexer reads the text back unchanged from a raw string: every
+/// backslash escapes a character and no quote ends the string early
+fn fits_raw(text: &str, delim: char, triple: bool) -> bool {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.next().is_none() => return false,
+            '\\' => {}
+            '\n' if !triple => return false,
+            c if c == delim => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Pretty-printer settings for emitting scripts in a house style
+#[derive(Clone, Debug)]
+pub struct Formatter {
+    pub indent_width: usize,
+    pub quote_style: QuoteStyle,
+    /// Quote dialogue the way it was written instead of in `quote_style`
+    pub preserve_quoting: bool,
+    pub blank_lines_between_labels: usize,
+    /// Pad with blank lines so statements land on the line given by their index
+    pub preserve_line_numbers: bool,
+    /// Reproduce the script's blank lines when formatting with comments,
+    /// instead of separating labels by `blank_lines_between_labels`
+    pub preserve_blank_lines: bool,
+    /// Parsers of custom statements, to render the nodes they made
+    pub statements: Statements,
+    /// Line endings and byte order mark of the output, see [`ParsedScript::format`](crate::ParsedScript::format)
+    pub source_format: SourceFormat,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Formatter {
+            indent_width: 4,
+            quote_style: QuoteStyle::Double,
+            preserve_quoting: false,
+            blank_lines_between_labels: 1,
+            preserve_line_numbers: false,
+            preserve_blank_lines: false,
+            statements: Statements::default(),
+            source_format: SourceFormat::default(),
+        }
+    }
+}
+
+impl Formatter {
+    pub fn format(&self, ast: &[AST]) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        self.write_block(&mut out, &mut line, ast, 0, None);
+        self.finish(out)
+    }
+
+    /// Formats the script, putting comments back next to their statements
+    pub fn format_with_comments(&self, ast: &[AST], comments: &Comments) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        self.write_block(&mut out, &mut line, ast, 0, Some(comments));
+        for comment in &comments.dangling {
+            self.write_comment(&mut out, &mut line, comment, 0);
+        }
+        self.finish(out)
+    }
+
+    fn finish(&self, out: String) -> String {
+        if self.source_format == SourceFormat::default() {
+            return out;
+        }
+        self.source_format.apply(&out)
+    }
+
+    fn write_block(
+        &self,
+        out: &mut String,
+        line: &mut usize,
+        block: &[AST],
+        depth: usize,
+        comments: Option<&Comments>,
+    ) {
+        for (position, node) in block.iter().enumerate() {
+            let Some(mut text) = self.statement(node) else {
+                continue;
+            };
+
+            let preserve_blank_lines = self.preserve_blank_lines && comments.is_some();
+            if position > 0 && matches!(node, AST::Label(..)) && !preserve_blank_lines {
+                self.blank_lines(out, line, self.blank_lines_between_labels);
+            }
+
+            let trivia = comments.and_then(|comments| comments.get(node.index()));
+            if let Some(trivia) = trivia {
+                for comment in &trivia.leading {
+                    self.write_comment(out, line, comment, depth);
+                }
+                for comment in &trivia.trailing {
+                    text.push_str(&format!("  #{}", comment.text));
+                }
+                if preserve_blank_lines {
+                    self.blank_lines(out, line, trivia.blank_lines_before);
+                }
+            }
+
+            self.pad_to(out, line, node.index());
+            out.push_str(&" ".repeat(depth * self.indent_width));
+            out.push_str(&text);
+            out.push('\n');
+            *line += text.matches('\n').count() + 1;
+
+            if let Some(block) = node.block() {
+                self.write_block(out, line, block, depth + 1, comments);
+            }
+        }
+    }
+
+    fn write_comment(&self, out: &mut String, line: &mut usize, comment: &Comment, depth: usize) {
+        if self.preserve_blank_lines {
+            self.blank_lines(out, line, comment.blank_lines_before);
+        }
+        self.pad_to(out, line, comment.line_number);
+        out.push_str(&" ".repeat(depth * self.indent_width));
+        out.push('#');
+        out.push_str(&comment.text);
+        out.push('\n');
+        *line += 1;
+    }
+
+    fn blank_lines(&self, out: &mut String, line: &mut usize, count: usize) {
+        for _ in 0..count {
+            out.push('\n');
+            *line += 1;
+        }
+    }
+
+    /// Pads with blank lines so the next line written lands on its original line
+    fn pad_to(&self, out: &mut String, line: &mut usize, line_number: usize) {
+        if self.preserve_line_numbers {
+            while *line + 1 < line_number {
+                out.push('\n');
+                *line += 1;
+            }
+        }
+    }
+
+    fn quote_dialogue(&self, text: &str, quoting: Quoting) -> String {
+        if self.preserve_quoting {
+            quote_as(text, quoting)
+        } else {
+            self.quote_style.quote(text)
+        }
+    }
+
+    /// Source text of a single statement line, without indentation
+    pub fn statement(&self, node: &AST) -> Option<String> {
+        let quote = |text: &str| self.quote_style.quote(text);
+
+        if let Some(text) = self.statements.render(node) {
+            return Some(text);
+        }
+
+        let text = match node {
+            AST::Define(_, definition) => format!("define {}", definition),
+            AST::Hide(_, image, clauses) => with_clauses(format!("hide {}", image), clauses),
+            AST::Init(_, _, 0) => "init:".to_string(),
+            AST::Init(_, _, priority) => format!("init {}:", priority),
+            AST::Jump(_, target, _) => format!("jump {}", target),
+            AST::Label(_, name, _, _) => format!("label {}:", name),
+            AST::Play(_, channel, filename) => format!("play {} {}", channel, quote(filename)),
+            AST::Return(_, Some(value)) if !value.is_empty() => format!("return {}", value),
+            AST::Return(_, _) => "return".to_string(),
+            AST::Say(_, Some(who), what, quoting) => {
+                format!("{} {}", who, self.quote_dialogue(what, *quoting))
+            }
+            AST::Say(_, None, what, quoting) => self.quote_dialogue(what, *quoting),
+            AST::Scene(_, image, layer, clauses) => {
+                let mut text = "scene".to_string();
+                if let Some(image) = image {
+                    text.push(' ');
+                    text.push_str(image);
+                }
+                let mut text = with_clauses(text, clauses);
+                let onlayer = clauses
+                    .as_deref()
+                    .is_some_and(|clauses| clauses.split_whitespace().any(|w| w == "onlayer"));
+                if layer != "master" && !onlayer {
+                    text.push_str(" onlayer ");
+                    text.push_str(layer);
+                }
+                text
+            }
+            AST::Show(_, image, clauses) => with_clauses(format!("show {}", image), clauses),
+            AST::Stop(_, channel, Some(effect), Some(length)) => {
+                format!("stop {} {} {}", channel, effect, fadeout_length(*length))
+            }
+            AST::Stop(_, channel, _, _) => format!("stop {}", channel),
+            AST::GameMechanic(_, mechanic) => format!("game_mechanic {}", quote(mechanic)),
+            AST::LLMGenerate(_, who, Some(prompt)) => {
+                format!("llm_generate {} {}", who, quote(prompt))
+            }
+            AST::LLMGenerate(_, who, None) => format!("llm_generate {}", who),
+            AST::Include(_, path) => format!("include {}", quote(path)),
+            AST::Custom(_, keyword, text) if text.is_empty() => keyword.clone(),
+            AST::Custom(_, keyword, text) => format!("{} {}", keyword, text),
+            AST::Error { raw_text, .. } if !raw_text.is_empty() => raw_text.clone(),
+            AST::Error { .. } => return None,
+        };
+
+        Some(text)
+    }
+}
+
+/// Renders the AST back into Ren'Py source that re-parses into the same AST.
+///
+/// Statements are placed on the line given by their index whenever indices
+/// increase in document order, so parsed scripts keep their line numbers.
+pub fn to_source(ast: &[AST]) -> String {
+    let formatter = Formatter {
+        blank_lines_between_labels: 0,
+        preserve_line_numbers: true,
+        preserve_quoting: true,
+        ..Formatter::default()
+    };
+
+    formatter.format(ast)
+}
+
+/// Like [`to_source`], keeping the script's comments
+pub fn to_source_with_comments(ast: &[AST], comments: &Comments) -> String {
+    let formatter = Formatter {
+        blank_lines_between_labels: 0,
+        preserve_line_numbers: true,
+        preserve_quoting: true,
+        ..Formatter::default()
+    };
+
+    formatter.format_with_comments(ast, comments)
+}
+
+/// Rewrites a script in the default [`Formatter`] style, keeping its comments,
+/// blank lines and line endings.
+///
+/// Strings have their whitespace collapsed, as Ren'Py reads them. Fails on the
+/// first statement with an error, so no text is lost.
+pub fn format_source(source: &str) -> Result<String> {
+    let options = ParseOptions {
+        strict: true,
+        whitespace: WhitespacePolicy::Collapse,
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(source, "<source>", &options)?;
+
+    let formatter = Formatter {
+        preserve_blank_lines: true,
+        source_format: script.format,
+        ..Formatter::default()
+    };
+    let comments = script.comments.unwrap_or_default();
+    Ok(formatter.format_with_comments(&script.ast, &comments))
+}
+
+/// Source text of a single statement line, without indentation
+pub fn statement(node: &AST) -> Option<String> {
+    Formatter::default().statement(node)
+}
+
+/// Double-quotes a string, escaping it the way Lexer::string unescapes it
+pub fn quote(text: &str) -> String {
+    QuoteStyle::Double.quote(text)
+}
+
+/// Writes a fadeout length the way the lexer reads it back, with digits on
+/// both sides of the decimal point
+fn fadeout_length(length: f32) -> String {
+    let mut text = length.to_string();
+    if !text.contains('.') {
+        text.push_str(".0");
+    }
+    text
+}
+
+/// Appends the clauses of an image statement, like `at left`, to its text
+fn with_clauses(mut text: String, clauses: &Option<String>) -> String {
+    if let Some(clauses) = clauses {
+        text.push(' ');
+        text.push_str(clauses);
+    }
+    text
+}
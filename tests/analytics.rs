@@ -0,0 +1,118 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::analytics::{speaker_stats, to_csv, Monologue, SpeakerStats};
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::parsers::AST;
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "analytics.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+const SCRIPT: &str = "label start:\n\
+    \x20   \"It was late.\"\n\
+    \x20   e \"Hi there\"\n\
+    \x20   show eileen happy\n\
+    \x20   e \"How are you doing\"\n\
+    \x20   m \"Fine\"\n\
+    \x20   e \"Good\"\n\
+    \x20   jump end\n\
+    label end:\n\
+    \x20   e \"Bye, then\"\n\
+    \x20   return\n";
+
+#[test]
+fn dialogue_is_counted_per_speaker_and_label() {
+    let stats = speaker_stats(&parse(SCRIPT));
+
+    assert_eq!(
+        stats,
+        vec![
+            SpeakerStats {
+                speaker: None,
+                lines: 1,
+                words: 3,
+                labels: vec!["start".to_string()],
+                longest_monologue: Monologue {
+                    lines: 1,
+                    words: 3,
+                    line_number: 2,
+                },
+            },
+            SpeakerStats {
+                speaker: Some("e".to_string()),
+                lines: 4,
+                words: 9,
+                labels: vec!["start".to_string(), "end".to_string()],
+                longest_monologue: Monologue {
+                    lines: 2,
+                    words: 6,
+                    line_number: 3,
+                },
+            },
+            SpeakerStats {
+                speaker: Some("m".to_string()),
+                lines: 1,
+                words: 1,
+                labels: vec!["start".to_string()],
+                longest_monologue: Monologue {
+                    lines: 1,
+                    words: 1,
+                    line_number: 6,
+                },
+            },
+        ]
+    );
+}
+
+#[test]
+fn monologues_end_at_the_label() {
+    let stats = speaker_stats(&parse(
+        "label a:\n    e \"One\"\nlabel b:\n    e \"Two\"\n    e \"Three\"\n",
+    ));
+
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].lines, 3);
+    assert_eq!(
+        stats[0].longest_monologue,
+        Monologue {
+            lines: 2,
+            words: 2,
+            line_number: 4,
+        }
+    );
+}
+
+#[test]
+fn stats_are_written_as_csv() {
+    let csv = to_csv(&speaker_stats(&parse(SCRIPT)));
+
+    assert_eq!(
+        csv,
+        "speaker,lines,words,labels,longest_monologue_lines,longest_monologue_words,longest_monologue_line\n\
+         ,1,3,start,1,3,2\n\
+         e,4,9,start end,2,6,3\n\
+         m,1,1,start,1,1,6\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn stats_are_written_as_json() {
+    use renpy_parser::analytics::to_json;
+
+    let stats = speaker_stats(&parse("label start:\n    e \"Hi there\"\n"));
+    let json: serde_json::Value = serde_json::from_str(&to_json(&stats)).unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "speaker": "e",
+            "lines": 1,
+            "words": 2,
+            "labels": ["start"],
+            "longest_monologue": {"lines": 1, "words": 2, "line_number": 2},
+        }])
+    );
+}
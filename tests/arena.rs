@@ -0,0 +1,28 @@
+#![cfg(feature = "ast_arena")]
+
+use bumpalo::Bump;
+use renpy_parser::arena::{parse_in, ArenaAST};
+use renpy_parser::parse_scenario_from_string;
+
+#[test]
+fn scripts_parse_into_the_arena() {
+    let source = "label start:\n    e \"Hello\"\n    init:\n        define e = Character(\"E\")\n    jump end\n";
+    let bump = Bump::new();
+    let (ast, errors) = parse_in(&bump, source, "arena.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let owned: Vec<_> = ast.iter().map(ArenaAST::to_owned_ast).collect();
+    assert_eq!(
+        owned,
+        parse_scenario_from_string(source, "arena.rpy").unwrap().0
+    );
+
+    // Strings are copied into the arena rather than borrowed from the source
+    let ArenaAST::Label(_, name, block, _) = ast[0] else {
+        panic!("expected a label, got {:?}", ast[0]);
+    };
+    let source_range = source.as_bytes().as_ptr_range();
+    assert!(!source_range.contains(&name.as_ptr()));
+    assert_eq!(block.len(), 3);
+    assert!(bump.allocated_bytes() > 0);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "fs")]
+
+use renpy_parser::assets::assets;
+use renpy_parser::parse_scenario_from_string;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn game_dir(name: &str, images: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("renpy-assets-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("images")).unwrap();
+    for image in images {
+        fs::write(dir.join("images").join(image), b"").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn references_are_listed_with_their_locations() {
+    let source = "label start:\n    scene bg room with fade\n    show eileen happy\n    play music \"theme.ogg\"\n    hide eileen\n";
+    let (ast, _) = parse_scenario_from_string(source, "assets.rpy").unwrap();
+    let report = assets(&ast);
+
+    let listed = |refs: &[renpy_parser::assets::AssetRef]| -> Vec<(String, usize, Vec<usize>)> {
+        refs.iter()
+            .map(|r| (r.name.clone(), r.node.index, r.node.path.clone()))
+            .collect()
+    };
+    assert_eq!(
+        listed(&report.images),
+        [
+            ("bg room".to_string(), 2, vec![0, 0]),
+            ("eileen happy".to_string(), 3, vec![0, 1]),
+        ]
+    );
+    assert_eq!(
+        listed(&report.audio),
+        [("theme.ogg".to_string(), 4, vec![0, 2])]
+    );
+    assert_eq!(
+        listed(&report.tags),
+        [("eileen".to_string(), 5, vec![0, 3])]
+    );
+}
+
+fn missing(source: &str, dir: &Path) -> Vec<String> {
+    let (ast, errors) = parse_scenario_from_string(source, "assets.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let report = assets(&ast).missing(dir).unwrap();
+    report
+        .images
+        .iter()
+        .chain(&report.tags)
+        .map(|asset| asset.name.clone())
+        .collect()
+}
+
+#[test]
+fn shown_images_need_a_matching_file() {
+    let dir = game_dir("show", &["eileen happy.png", "eileen_sad_frown.png"]);
+
+    let source = "show eileen happy\nshow eileen angry\nshow eileen sad\nscene bg room\nhide eileen\nhide lucy\n";
+    assert_eq!(missing(source, &dir), ["eileen angry", "bg room", "lucy"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn attributes_have_to_pick_a_single_image() {
+    let dir = game_dir(
+        "ambiguous",
+        &["lucy happy smile.png", "lucy happy frown.png"],
+    );
+
+    let source = "show lucy happy smile\nshow lucy smile\nshow lucy happy\n";
+    assert_eq!(missing(source, &dir), ["lucy happy"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,54 @@
+use renpy_parser::borrowed::{parse_borrowed, BorrowedAST};
+use std::borrow::Cow;
+
+/// Byte offset of borrowed text in the source
+fn offset(source: &str, text: Cow<str>) -> Option<usize> {
+    match text {
+        Cow::Borrowed(text) => Some(text.as_ptr() as usize - source.as_ptr() as usize),
+        Cow::Owned(_) => None,
+    }
+}
+
+#[test]
+fn strings_borrow_the_text_they_were_parsed_from() {
+    let source = "label start:\n    e \"e\"\n    \"say \\\"hi\\\"\"\n";
+    let (ast, errors) = parse_borrowed(source, "borrowed.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let BorrowedAST::Label(_, name, block, _) = &ast[0] else {
+        panic!("expected a label, got {:?}", ast[0]);
+    };
+    assert_eq!(offset(source, name.clone()), Some(6));
+
+    // The text is the `e` in quotes rather than the first `e` of the line
+    let BorrowedAST::Say(_, Some(who), what, _) = &block[0] else {
+        panic!("expected dialogue, got {:?}", block[0]);
+    };
+    assert_eq!(offset(source, who.clone()), Some(17));
+    assert_eq!(offset(source, what.clone()), Some(20));
+
+    // Escapes have to be resolved, so the text is owned
+    let BorrowedAST::Say(_, None, what, _) = &block[1] else {
+        panic!("expected narration, got {:?}", block[1]);
+    };
+    assert_eq!(what, "say \"hi\"");
+    assert_eq!(offset(source, what.clone()), None);
+}
+
+#[test]
+fn joined_lines_are_owned() {
+    let source = "define x = 1 + \\\n    2\nshow eileen   happy\n";
+    let (ast, _) = parse_borrowed(source, "borrowed.rpy").unwrap();
+
+    let BorrowedAST::Define(_, definition) = &ast[0] else {
+        panic!("expected a define, got {:?}", ast[0]);
+    };
+    assert_eq!(offset(source, definition.clone()), None);
+
+    let BorrowedAST::Show(_, image, _) = &ast[1] else {
+        panic!("expected a show, got {:?}", ast[1]);
+    };
+    assert_eq!(image, "eileen happy");
+    assert_eq!(offset(source, image.clone()), None);
+    assert_eq!(ast[1].clone().into_owned().index(), 3);
+}
@@ -0,0 +1,33 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::builder::Script;
+use renpy_parser::parse_scenario_from_string;
+
+#[test]
+fn built_scripts_are_indexed_like_parsed_ones() {
+    let ast = Script::new()
+        .define("e = Character(\"Eileen\")")
+        .label("start", |l| {
+            l.scene("bg room")
+                .show("eileen")
+                .say("e", "Hi")
+                .narrate("It's late")
+                .jump("end")
+        })
+        .label("end", |l| l.stop("music", Some(1.5)).return_())
+        .build();
+
+    let source = "define e = Character(\"Eileen\")\n\
+        label start:\n\
+        \x20   scene bg room\n\
+        \x20   show eileen\n\
+        \x20   e \"Hi\"\n\
+        \x20   \"It's late\"\n\
+        \x20   jump end\n\
+        label end:\n\
+        \x20   stop music fadeout 1.5\n\
+        \x20   return\n";
+    let (parsed, errors) = parse_scenario_from_string(source, "built.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(ast, parsed);
+}
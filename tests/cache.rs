@@ -0,0 +1,32 @@
+#![cfg(feature = "cache")]
+
+use renpy_parser::cache::{load, parse_cached, save};
+use renpy_parser::parse_scenario_from_string;
+use std::fs;
+
+#[test]
+fn cached_asts_are_only_loaded_for_the_same_source() {
+    let dir = std::env::temp_dir().join(format!("renpy-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let cache = dir.join("script.rpyc");
+
+    let source = "label start:\n    e \"Hi\"\n    stop music fadeout 1.5\n";
+    let (ast, _) = parse_scenario_from_string(source, "script.rpy").unwrap();
+    save(&cache, source, &ast).unwrap();
+    assert_eq!(load(&cache, source).unwrap(), Some(ast.clone()));
+    assert_eq!(load(&cache, "label start:\n    return\n").unwrap(), None);
+
+    fs::write(&cache, b"not a cache").unwrap();
+    assert_eq!(load(&cache, source).unwrap(), None);
+
+    let script = dir.join("script.rpy");
+    fs::write(&script, source).unwrap();
+    let cache = dir.join("parsed.rpyc");
+    let script = script.to_str().unwrap();
+    assert_eq!(parse_cached(script, &cache).unwrap(), (ast.clone(), vec![]));
+    assert!(cache.is_file());
+    assert_eq!(parse_cached(script, &cache).unwrap(), (ast, vec![]));
+
+    fs::remove_dir_all(dir).unwrap();
+}
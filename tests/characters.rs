@@ -0,0 +1,28 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::characters::character_usage;
+use renpy_parser::parse_scenario_from_string;
+
+#[test]
+fn speakers_are_counted_per_label_with_their_definitions() {
+    let source = "define e = Character(\"Eileen\")\n\
+        define l = Character(\"Lucy\")\n\
+        label start:\n\
+        \x20   e \"Hi\"\n\
+        \x20   m \"Hello\"\n\
+        \x20   e \"Bye\"\n\
+        label end:\n\
+        \x20   e \"The end\"\n";
+    let (ast, errors) = parse_scenario_from_string(source, "characters.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let usage = character_usage(&ast);
+
+    assert_eq!(usage.keys().collect::<Vec<_>>(), ["e", "l", "m"]);
+    assert_eq!(usage["e"].lines, 3);
+    assert_eq!(usage["e"].labels, ["start", "end"]);
+    assert_eq!(usage["e"].definition.as_ref().map(|d| d.index), Some(1));
+    assert_eq!(usage["l"].lines, 0);
+    assert!(usage["l"].is_defined());
+    assert_eq!(usage["m"].lines, 1);
+    assert!(!usage["m"].is_defined());
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn scripts(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("renpy-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("chapters")).unwrap();
+    fs::write(
+        dir.join("script.rpy"),
+        "define e = Character(\"Eileen\")\nlabel start:\n    e \"Hi there\"\n    play video \"theme.ogg\"\n    return\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("chapters/next.rpy"),
+        "label next:\n    \"Later\"\n    return\n",
+    )
+    .unwrap();
+    dir
+}
+
+/// Exit code and standard output of the CLI
+fn run(args: &[&str], dir: &Path) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_renpy-parse"))
+        .args(args)
+        .arg(dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (output.status.code().unwrap(), stdout.replace('\\', "/"))
+}
+
+#[test]
+fn check_prints_diagnostics_and_fails_on_errors() {
+    let dir = scripts("check");
+    let (code, stdout) = run(&["check"], &dir);
+    assert_eq!(code, 1);
+    assert!(
+        stdout.ends_with("script.rpy:4: error[RPY0205]: Play or sound is required\n"),
+        "{}",
+        stdout
+    );
+
+    let (code, stdout) = run(&["check"], &dir.join("chapters"));
+    assert_eq!((code, stdout.as_str()), (0, ""));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn dump_and_stats_describe_the_scripts() {
+    let dir = scripts("dump");
+    let (code, json) = run(&["dump"], &dir.join("chapters/next.rpy"));
+    assert_eq!(code, 0);
+    assert!(json.contains("\"type\": \"label\""), "{}", json);
+    let (_, ron) = run(&["dump", "--format", "ron"], &dir.join("chapters/next.rpy"));
+    assert!(ron.contains("type: \"label\""), "{}", ron);
+
+    let (code, stats) = run(&["stats"], &dir);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stats,
+        "files: 2\nlabels: 2\ncharacters: 1\ndialogue lines: 2\ndialogue words: 3\nstatements: 8\n  define: 1\n  error: 1\n  label: 2\n  return: 2\n  say: 2\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fmt_rewrites_scripts_or_lists_the_unformatted_ones() {
+    let dir = scripts("fmt");
+    let next = dir.join("chapters/next.rpy");
+    fs::write(&next, "label   next:\n  \"Later\"\n  return\n").unwrap();
+
+    let (code, stdout) = run(&["fmt", "--check"], &dir.join("chapters"));
+    assert_eq!(code, 1);
+    assert!(stdout.ends_with("chapters/next.rpy\n"), "{}", stdout);
+
+    let (code, _) = run(&["fmt"], &dir.join("chapters"));
+    assert_eq!(code, 0);
+    assert_eq!(
+        fs::read_to_string(&next).unwrap(),
+        "label next:\n    \"Later\"\n    return\n"
+    );
+    assert_eq!(run(&["fmt", "--check"], &dir.join("chapters")).0, 0);
+
+    // Scripts with errors are left alone
+    let (code, _) = run(&["fmt"], &dir);
+    assert_eq!(code, 1);
+    assert!(fs::read_to_string(dir.join("script.rpy"))
+        .unwrap()
+        .contains("play video"));
+    fs::remove_dir_all(dir).unwrap();
+}
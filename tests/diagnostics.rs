@@ -0,0 +1,45 @@
+use renpy_parser::diagnostics::Code;
+use renpy_parser::options::{ParseOptions, TabPolicy};
+use renpy_parser::parse_script;
+use renpy_parser::parsers::ParseError;
+
+#[test]
+fn codes_are_unique_and_read_back() {
+    for (i, code) in Code::ALL.iter().enumerate() {
+        assert!(Code::ALL[..i]
+            .iter()
+            .all(|other| other.as_str() != code.as_str()));
+        assert_eq!(Code::parse(code.as_str()), Some(*code));
+        assert_eq!(code.to_string(), code.as_str());
+    }
+    assert_eq!(Code::MissingSayText.as_str(), "RPY0201");
+    assert_eq!(Code::parse("RPY9999"), None);
+}
+
+#[test]
+fn problems_carry_their_codes() {
+    let source = "label start:\n\te \"Hi\"\n    play video \"theme.ogg\"\n    e\n";
+    let error = parse_script(source, "codes.rpy", &ParseOptions::default()).unwrap_err();
+    let error = error.downcast_ref::<ParseError>().unwrap();
+    assert_eq!((error.code, error.line_number), (Code::TabCharacter, 2));
+
+    let options = ParseOptions {
+        tabs: TabPolicy::ExpandTo(4),
+        ..ParseOptions::hardened()
+    };
+    let script = parse_script(source, "codes.rpy", &options).unwrap();
+    let codes = |problems: Vec<&ParseError>| {
+        problems
+            .iter()
+            .map(|p| (p.code, p.line_number))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        codes(script.diagnostics.errors().collect()),
+        [(Code::InvalidAudioChannel, 3), (Code::MissingSayText, 4)]
+    );
+    assert_eq!(
+        codes(script.diagnostics.warnings().collect()),
+        [(Code::TabExpanded, 2)]
+    );
+}
@@ -0,0 +1,50 @@
+use renpy_parser::diagnostics::Code;
+use renpy_parser::encoding::{decode, Encoding, EncodingFallback};
+use renpy_parser::parsers::ParseError;
+
+#[test]
+fn byte_order_marks_pick_utf16() {
+    let text = "e \"Héllo\"\n";
+    let mut le = vec![0xff, 0xfe];
+    le.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    let mut be = vec![0xfe, 0xff];
+    be.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+
+    for (bytes, encoding) in [(le, Encoding::Utf16Le), (be, Encoding::Utf16Be)] {
+        let decoded = decode(bytes, "utf16.rpy", EncodingFallback::Reject).unwrap();
+        assert_eq!((decoded.text.as_str(), decoded.encoding), (text, encoding));
+        assert!(decoded.warning.is_none());
+    }
+
+    let decoded = decode(
+        b"\xef\xbb\xbfreturn\n".to_vec(),
+        "bom.rpy",
+        EncodingFallback::Reject,
+    );
+    assert_eq!(decoded.unwrap().text, "\u{feff}return\n");
+}
+
+#[test]
+fn invalid_utf8_is_rejected_or_read_with_a_warning() {
+    let bytes = b"return\ne \"caf\xe9\"\n".to_vec();
+
+    let error = decode(bytes.clone(), "latin1.rpy", EncodingFallback::Reject).unwrap_err();
+    let error = error.downcast_ref::<ParseError>().unwrap();
+    assert_eq!((error.code, error.line_number), (Code::InvalidEncoding, 2));
+    assert_eq!(
+        error.message,
+        "byte 0xe9 is not valid UTF-8, save the script as UTF-8"
+    );
+
+    let lossy = decode(bytes.clone(), "latin1.rpy", EncodingFallback::Lossy).unwrap();
+    assert_eq!(lossy.text, "return\ne \"caf\u{fffd}\"\n");
+    assert_eq!(lossy.encoding, Encoding::LossyUtf8);
+
+    let latin1 = decode(bytes, "latin1.rpy", EncodingFallback::Latin1).unwrap();
+    assert_eq!(latin1.text, "return\ne \"café\"\n");
+    let warning = latin1.warning.unwrap();
+    assert_eq!(
+        (warning.code, warning.line_number),
+        (Code::InvalidEncoding, 2)
+    );
+}
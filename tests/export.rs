@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::parsers::AST;
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "export.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+const SCRIPT: &str = "define e = Character(\"Eileen\")\n\
+    label start:\n\
+    \x20   scene bg room with fade\n\
+    \x20   e \"Hi, \\\"you\\\"\"\n\
+    \x20   \"Two\\nlines\"\n\
+    \x20   jump end\n\
+    label end:\n\
+    \x20   stop music fadeout 1.5\n\
+    \x20   return\n";
+
+#[test]
+#[cfg(feature = "json")]
+fn json_has_a_versioned_layout_and_reads_back() {
+    use renpy_parser::export::json::{from_json, to_json};
+
+    let json = to_json(&parse("label start:\n    e \"Hi\"\n"));
+    assert_eq!(
+        json,
+        r#"{
+  "version": 1,
+  "nodes": [
+    {
+      "type": "label",
+      "index": 1,
+      "name": "start",
+      "block": [
+        {
+          "type": "say",
+          "index": 2,
+          "who": "e",
+          "what": "Hi"
+        }
+      ]
+    }
+  ]
+}"#
+    );
+
+    let ast = parse(SCRIPT);
+    assert_eq!(from_json(&to_json(&ast)).unwrap(), ast);
+
+    let newer = json.replace("\"version\": 1", "\"version\": 2");
+    let err = from_json(&newer).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported document version 2");
+    assert!(from_json(r#"{"version": 1, "nodes": [{"type": "jump", "index": 1}]}"#).is_err());
+}
+
+#[test]
+#[cfg(all(feature = "ron", feature = "yaml"))]
+fn ron_and_yaml_mirror_the_json_layout() {
+    use renpy_parser::export::ron::{from_ron, to_ron};
+    use renpy_parser::export::yaml::{from_yaml, to_yaml};
+
+    let ast = parse(SCRIPT);
+    assert_eq!(from_ron(&to_ron(&ast)).unwrap(), ast);
+    assert_eq!(from_yaml(&to_yaml(&ast)).unwrap(), ast);
+
+    let say = parse("e \"Hi\"\n");
+    assert_eq!(
+        to_yaml(&say),
+        "version: 1\nnodes:\n- type: say\n  index: 1\n  who: e\n  what: Hi\n"
+    );
+    assert!(to_ron(&say).contains("type: \"say\""));
+}
+
+#[test]
+fn dot_edges_are_labeled_with_their_lines() {
+    use renpy_parser::export::dot::to_dot;
+
+    let ast =
+        parse("label start:\n    jump end\nlabel middle:\n    \"hi\"\nlabel end:\n    return\n");
+    assert_eq!(
+        to_dot(&ast),
+        "digraph script {\n    \"start\";\n    \"middle\";\n    \"end\";\n    \"start\" -> \"end\" [label=\"line 2\"];\n    \"middle\" -> \"end\" [label=\"line 4\", style=dashed];\n}\n"
+    );
+}
+
+#[test]
+fn dialogue_rows_are_escaped_for_spreadsheets() {
+    use renpy_parser::export::csv::{to_csv, to_tsv};
+
+    let ast = parse(SCRIPT);
+    assert_eq!(
+        to_csv(&ast, "script.rpy"),
+        "file,line,label,speaker,text\nscript.rpy,4,start,e,\"Hi, \"\"you\"\"\"\nscript.rpy,5,start,,\"Two\nlines\"\n"
+    );
+    assert_eq!(
+        to_tsv(&ast, "script.rpy"),
+        "file\tline\tlabel\tspeaker\ttext\nscript.rpy\t4\tstart\te\tHi, \"you\"\nscript.rpy\t5\tstart\t\tTwo\\nlines\n"
+    );
+}
+
+#[test]
+fn labels_become_yarn_nodes() {
+    use renpy_parser::export::yarn::to_yarn;
+
+    let ast = parse(
+        "label start:\n    show eileen\n    e \"Hi [name] <3\"\n    \"#1\"\nlabel next:\n    play music \"theme.ogg\"\n    jump start\n",
+    );
+    assert_eq!(
+        to_yarn(&ast),
+        "title: start\n---\n<<show eileen>>\ne: Hi \\[name\\] \\<3\n\\#1\n<<jump next>>\n===\ntitle: next\n---\n<<play music \"theme.ogg\">>\n<<jump start>>\n===\n"
+    );
+}
+
+#[test]
+fn local_labels_become_yarn_nodes_of_their_own() {
+    use renpy_parser::export::yarn::to_yarn;
+
+    let ast = parse(
+        "label start:\n    jump .sub\n    label .sub:\n        \"In start\"\nlabel other:\n    label .sub:\n        jump start.sub\n",
+    );
+    assert_eq!(
+        to_yarn(&ast),
+        "title: start\n---\n<<jump start_sub>>\n===\ntitle: start_sub\n---\nIn start\n<<jump other>>\n===\ntitle: other\n---\n<<jump other_sub>>\n===\ntitle: other_sub\n---\n<<jump start_sub>>\n===\n"
+    );
+}
+
+#[test]
+fn ink_lists_the_statements_it_cannot_convert() {
+    use renpy_parser::export::ink::{to_ink, Unconverted};
+
+    let ast = parse(
+        "label intro:\n    \"- Hello\"\nlabel start:\n    e \"Hi {b}you{/b}\"\n    game_mechanic \"roll dice\"\n    return\n",
+    );
+    let export = to_ink(&ast);
+    assert_eq!(
+        export.source,
+        "-> start\n\n=== intro ===\n\\- Hello\n-> start\n\n=== start ===\ne: Hi \\{b\\}you\\{\\/b\\}\n// game_mechanic \"roll dice\"\n-> END\n"
+    );
+    assert_eq!(
+        export.unconverted,
+        [Unconverted {
+            line_number: 5,
+            statement: "game_mechanic \"roll dice\"".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn local_labels_become_knots_of_their_own() {
+    use renpy_parser::export::ink::to_ink;
+
+    let ast = parse(
+        "label start:\n    jump .sub\n    label .sub:\n        \"In start\"\nlabel other:\n    label .sub:\n        jump start.sub\n",
+    );
+    assert_eq!(
+        to_ink(&ast).source,
+        "-> start\n\n=== start ===\n-> start_sub\n\n=== start_sub ===\nIn start\n-> other\n\n=== other ===\n-> other_sub\n\n=== other_sub ===\n-> start_sub\n"
+    );
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "ffi")]
+
+use renpy_parser::ffi::*;
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+fn text<'a>(ptr: *const c_char) -> Option<&'a str> {
+    match ptr.is_null() {
+        true => None,
+        false => Some(unsafe { CStr::from_ptr(ptr) }.to_str().unwrap()),
+    }
+}
+
+#[test]
+fn scripts_are_walked_through_the_c_interface() {
+    let source = c"label start:\n    e \"Hi\"\n    play video \"a.ogg\"\nreturn\n";
+    unsafe {
+        let script = renpy_parser_parse(source.as_ptr(), c"ffi.rpy".as_ptr());
+        assert!(!script.is_null());
+
+        let count = renpy_parser_node_count(script);
+        let nodes: Vec<_> = (0..count)
+            .map(|i| {
+                let kind = text(renpy_parser_node_kind(script, i)).unwrap();
+                let line = renpy_parser_node_line(script, i);
+                (kind, line, renpy_parser_node_depth(script, i))
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                ("label", 1, 0),
+                ("say", 2, 1),
+                ("error", 3, 1),
+                ("return", 4, 0)
+            ]
+        );
+        assert!(renpy_parser_node_kind(script, count).is_null());
+
+        assert_eq!(renpy_parser_diagnostic_count(script), 1);
+        assert!(renpy_parser_diagnostic_is_error(script, 0));
+        assert_eq!(
+            text(renpy_parser_diagnostic_code(script, 0)),
+            Some("RPY0205")
+        );
+        assert_eq!(renpy_parser_diagnostic_line(script, 0), 3);
+        assert_eq!(
+            text(renpy_parser_diagnostic_message(script, 0)),
+            Some("Play or sound is required")
+        );
+        assert!(renpy_parser_diagnostic_message(script, 1).is_null());
+
+        let json = renpy_parser_node_json(script, 1);
+        assert!(text(json).unwrap().contains("\"Hi\""));
+        renpy_parser_string_free(json);
+        let json = renpy_parser_to_json(script);
+        assert!(text(json).unwrap().contains("\"start\""));
+        renpy_parser_string_free(json);
+
+        renpy_parser_free(script);
+        assert!(renpy_parser_parse(ptr::null(), ptr::null()).is_null());
+        assert_eq!(renpy_parser_node_count(ptr::null()), 0);
+        renpy_parser_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn scripts_that_cannot_be_read_carry_the_error() {
+    let source = c"label start:\n\treturn\n";
+    unsafe {
+        let script = renpy_parser_parse(source.as_ptr(), c"ffi.rpy".as_ptr());
+        assert!(!script.is_null());
+
+        assert_eq!(renpy_parser_node_count(script), 0);
+        assert_eq!(renpy_parser_diagnostic_count(script), 1);
+        assert!(renpy_parser_diagnostic_is_error(script, 0));
+        assert_eq!(renpy_parser_diagnostic_line(script, 0), 2);
+        assert_eq!(
+            text(renpy_parser_diagnostic_code(script, 0)),
+            Some("RPY0001")
+        );
+        assert!(text(renpy_parser_diagnostic_message(script, 0)).is_some());
+
+        renpy_parser_free(script);
+    }
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::flow::{
+    complexity, paths, paths_within, unreachable, unreachable_from, EdgeKind, FlowGraph, LabelEdge,
+};
+use renpy_parser::parse_scenario_from_string;
+
+fn parse(source: &str) -> Vec<renpy_parser::parsers::AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "flow.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+#[test]
+fn depth_counts_the_labels_of_loops_once() {
+    // a and b jump to each other; the longest way through is start, a, b, c
+    let ast = parse(
+        "label start:\n    jump a\n    jump b\nlabel a:\n    jump b\nlabel b:\n    jump a\n    jump c\nlabel c:\n    return\n",
+    );
+    assert_eq!(complexity(&ast).max_depth, 4);
+
+    let ast = parse(
+        "label start:\n    jump hub\nlabel hub:\n    jump one\n    jump two\nlabel one:\n    jump hub\nlabel two:\n    jump hub\n    jump end\nlabel end:\n    return\n",
+    );
+    assert_eq!(complexity(&ast).max_depth, 5);
+}
+
+#[test]
+fn depth_without_loops() {
+    let ast = parse("label start:\n    jump a\nlabel a:\n    jump b\nlabel b:\n    return\n");
+    assert_eq!(complexity(&ast).max_depth, 3);
+}
+
+#[test]
+fn paths_are_limited() {
+    let ast =
+        parse("label start:\n    jump a\n    jump b\nlabel a:\n    return\nlabel b:\n    return\n");
+    assert_eq!(paths(&ast, 1).len(), 1);
+}
+
+#[test]
+fn path_search_is_bounded_by_steps() {
+    let mut source = String::new();
+    for i in 0..100 {
+        source.push_str(&format!("label l{}:\n    jump l{}\n", i, i + 1));
+    }
+    source.push_str("label l100:\n    return\n");
+    let ast = parse(&source);
+
+    assert_eq!(paths_within(&ast, "l0", 10, 1_000).len(), 1);
+    // Gives up before reaching the return
+    assert!(paths_within(&ast, "l0", 10, 100).is_empty());
+}
+
+#[test]
+fn graphs_link_statements_by_fall_through_and_jumps() {
+    let ast = parse("label start:\n    \"hi\"\n    jump end\nlabel middle:\n    \"skipped\"\nlabel end:\n    return\n");
+    let graph = FlowGraph::new(&ast);
+
+    let indices: Vec<usize> = graph.nodes.iter().map(|n| n.node.index).collect();
+    assert_eq!(indices, [1, 2, 3, 4, 5, 6, 7]);
+    let edges: Vec<_> = graph.edges.iter().map(|e| (e.from, e.to, e.kind)).collect();
+    assert_eq!(
+        edges,
+        [
+            (0, 1, EdgeKind::FallThrough),
+            (1, 2, EdgeKind::FallThrough),
+            (2, 5, EdgeKind::Jump),
+            (3, 4, EdgeKind::FallThrough),
+            (4, 5, EdgeKind::FallThrough),
+            (5, 6, EdgeKind::FallThrough),
+        ]
+    );
+
+    let edge = |from: &str, to: &str, kind, line_number| LabelEdge {
+        from: from.to_string(),
+        to: to.to_string(),
+        kind,
+        line_number,
+    };
+    assert_eq!(
+        graph.label_edges(),
+        [
+            edge("start", "end", EdgeKind::Jump, 3),
+            edge("middle", "end", EdgeKind::FallThrough, 5),
+        ]
+    );
+    assert_eq!(graph.reachable("start").len(), 5);
+}
+
+#[test]
+fn dead_statements_and_orphan_labels_are_reported() {
+    let ast = parse(
+        "init:\n    define x = 1\nlabel start:\n    jump end\n    \"dead\"\nlabel orphan:\n    return\nlabel end:\n    return\n    \"after return\"\n",
+    );
+    let indices = |nodes: &[renpy_parser::flow::FlowNode]| -> Vec<usize> {
+        nodes.iter().map(|n| n.node.index).collect()
+    };
+
+    let found = unreachable(&ast);
+    assert_eq!(indices(&found.statements), [5, 10]);
+    assert_eq!(indices(&found.orphan_labels), [6]);
+
+    let found = unreachable_from(&ast, "orphan");
+    assert_eq!(indices(&found.orphan_labels), [3, 8]);
+}
+
+#[test]
+fn local_labels_are_told_apart_by_their_global_label() {
+    let ast = parse(
+        "label start:\n    jump .sub\n    label .sub:\n        return\nlabel other:\n    jump .sub\n    label .sub:\n        jump start.sub\n",
+    );
+    let graph = FlowGraph::new(&ast);
+
+    let labels: Vec<_> = graph.nodes.iter().map(|n| n.label.as_deref()).collect();
+    assert_eq!(
+        labels,
+        [
+            Some("start"),
+            Some("start"),
+            Some("start.sub"),
+            Some("start.sub"),
+            Some("other"),
+            Some("other"),
+            Some("other.sub"),
+            Some("other.sub"),
+        ]
+    );
+    assert_eq!(graph.label_node("start.sub"), Some(2));
+    assert_eq!(graph.label_node("other.sub"), Some(6));
+    assert_eq!(graph.label_node(".sub"), None);
+
+    let jumps: Vec<_> = graph
+        .label_edges()
+        .into_iter()
+        .filter(|e| e.kind == EdgeKind::Jump)
+        .map(|e| (e.from, e.to))
+        .collect();
+    assert_eq!(
+        jumps,
+        [
+            ("start".to_string(), "start.sub".to_string()),
+            ("other".to_string(), "other.sub".to_string()),
+            ("other.sub".to_string(), "start.sub".to_string()),
+        ]
+    );
+
+    let found = unreachable_from(&ast, "other");
+    assert_eq!(found.orphan_labels.len(), 1);
+    assert_eq!(found.orphan_labels[0].node.index, 1);
+}
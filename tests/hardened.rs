@@ -0,0 +1,153 @@
+use renpy_parser::encoding::EncodingFallback;
+use renpy_parser::lexer::Lexer;
+use renpy_parser::options::{IndentRule, ParseOptions, TabPolicy};
+use renpy_parser::{
+    group_logical_lines, parse_logical_lines, parse_scenario_from_string, parse_script,
+    parse_script_from_bytes,
+};
+
+const SCRIPT: &str = "define e = Character(\"Эйлин\", color=\"#c8ffc8\")\n\
+label start:\n\
+\x20   scene bg 教室 with dissolve\n\
+\x20   show eileen happy at left\n\
+\x20   e \"こんにちは、世界！\"\n\
+\x20   \"Привет {b}мир{/b} — ✨\"\n\
+\x20   play music \"音楽/テーマ.ogg\" fadein 1.5\n\
+\x20   menu:\n\
+\x20       \"Да\":\n\
+\x20           $ points = {\"ключ\": [1, (2, 3)]}\n\
+\x20           jump .конец\n\
+\x20       \"いいえ\" if points > 0:\n\
+\x20           return\n\
+label .конец:\n\
+\x20   stop music fadeout 2.0\n\
+\x20   return \"終\"\n";
+
+const ODD: &[&str] = &[
+    "",
+    "\n\n\n",
+    ":",
+    "label",
+    "label :",
+    "label start(:\n    return\n",
+    "define x = (\n",
+    "define x = [1, {2: (3\n",
+    "$ x = ')]}\n",
+    "e \"unterminated\n",
+    "e '''never\nclosed\n",
+    "\"\\",
+    "show\tbg\tstart\n",
+    "\tlabel start:\n\t\treturn\n",
+    "label start:\n        return\n  return\n",
+    "jump .\n",
+    "play music \"a.ogg\" fadein\n",
+    "stop music fadeout 1.\n",
+    "menu:\n    \"a\" if:\n",
+    "python:\n",
+    "init 99999999999999999999999 python:\n    pass\n",
+    "e \"\u{0}\u{feff}\u{200b}\"\n",
+    "\u{feff}label start:\n    return\n",
+    "label start:\r\n    return\r",
+    "é\u{301}\u{301}\u{301}:",
+    "🎉 \"🎉🎉\"\n",
+];
+
+fn options() -> Vec<ParseOptions> {
+    vec![
+        ParseOptions::default(),
+        ParseOptions::hardened(),
+        ParseOptions {
+            strict: true,
+            tabs: TabPolicy::ExpandTo(3),
+            indent: IndentRule::Width(usize::MAX),
+            ..ParseOptions::default()
+        },
+        ParseOptions {
+            max_depth: Some(1),
+            indent: IndentRule::Consistent,
+            ..ParseOptions::hardened()
+        },
+    ]
+}
+
+fn parse_everywhere(source: &str) {
+    let _ = parse_scenario_from_string(source, "odd.rpy");
+    for options in options() {
+        let _ = parse_script(source, "odd.rpy", &options);
+    }
+}
+
+#[test]
+fn every_prefix_of_a_script_parses_without_panicking() {
+    let boundaries = SCRIPT.char_indices().map(|(i, _)| i).chain([SCRIPT.len()]);
+    for end in boundaries {
+        for source in [&SCRIPT[..end], &SCRIPT[end..]] {
+            let _ = parse_scenario_from_string(source, "odd.rpy");
+            let _ = parse_script(source, "odd.rpy", &ParseOptions::hardened());
+        }
+    }
+}
+
+#[test]
+fn unbalanced_and_unusual_inputs_parse_without_panicking() {
+    for source in ODD {
+        parse_everywhere(source);
+        parse_everywhere(&source.repeat(3));
+    }
+
+    let deep = (0..200).fold(String::new(), |mut source, depth| {
+        source.push_str(&" ".repeat(depth * 4));
+        source.push_str("menu:\n");
+        source
+    });
+    parse_everywhere(&deep);
+    parse_everywhere(&format!("define x = {}\n", "(".repeat(10_000)));
+
+    assert!(parse_scenario_from_string(ODD[7], "odd.rpy").is_err());
+}
+
+#[test]
+fn truncated_and_misencoded_bytes_are_errors() {
+    let mut utf16 = vec![0xff, 0xfe];
+    utf16.extend(SCRIPT.encode_utf16().flat_map(u16::to_le_bytes));
+
+    let inputs = [
+        SCRIPT.as_bytes().to_vec(),
+        utf16,
+        b"\xef\xbb\xbflabel start:\n    return\n".to_vec(),
+        b"e \"\xe3\x81\"\n\xff\xfe\xfd".to_vec(),
+    ];
+
+    for bytes in inputs {
+        for end in 0..=bytes.len() {
+            let encoding = [
+                EncodingFallback::Reject,
+                EncodingFallback::Lossy,
+                EncodingFallback::Latin1,
+            ][end % 3];
+            let options = ParseOptions {
+                encoding,
+                ..ParseOptions::hardened()
+            };
+            let _ = parse_script_from_bytes(bytes[..end].to_vec(), "odd.rpy", &options);
+        }
+    }
+
+    let broken = b"label start:\n    e \"\xe3\x81\"\n".to_vec();
+    assert!(parse_script_from_bytes(broken, "odd.rpy", &ParseOptions::default()).is_err());
+}
+
+#[test]
+fn invalid_patterns_are_errors() {
+    let lines = parse_logical_lines("label start:\n", "odd.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    assert!(l.match_("(").is_err());
+
+    l.advance();
+
+    assert!(l.match_("(").is_err());
+    assert!(l.keyword("[").is_err());
+    assert!(l.require("label(").is_err());
+    assert_eq!(l.keyword("label").unwrap().as_deref(), Some("label"));
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::highlight::{highlight, SemanticKind};
+
+fn kinds(source: &str) -> Vec<(SemanticKind, &str)> {
+    highlight(source)
+        .into_iter()
+        .filter(|token| token.kind != SemanticKind::Punct)
+        .map(|token| (token.kind, token.text))
+        .collect()
+}
+
+#[test]
+fn tokens_are_classified_the_way_the_parser_reads_them() {
+    use SemanticKind::*;
+
+    let source = "define e = Character(\"Eileen\")\nlabel start: # begin\n    show eileen happy at left\n    e \"Hi\"\n    play music \"theme.ogg\"\n    jump start\n";
+    let tokens = highlight(source);
+    assert!(tokens.iter().all(|t| &source[t.span.clone()] == t.text));
+
+    assert_eq!(
+        kinds(source),
+        [
+            (Keyword, "define"),
+            (Speaker, "e"),
+            (Name, "Character"),
+            (String, "\"Eileen\""),
+            (Keyword, "label"),
+            (Label, "start"),
+            (Comment, "# begin"),
+            (Keyword, "show"),
+            (Image, "eileen"),
+            (Image, "happy"),
+            (Keyword, "at"),
+            (Name, "left"),
+            (Speaker, "e"),
+            (Dialogue, "\"Hi\""),
+            (Keyword, "play"),
+            (Name, "music"),
+            (Audio, "\"theme.ogg\""),
+            (Keyword, "jump"),
+            (Label, "start"),
+        ]
+    );
+}
+
+#[test]
+fn broken_statements_fall_back_to_plain_tokens() {
+    use SemanticKind::*;
+
+    assert_eq!(
+        kinds("jump 5 \"x\"\n"),
+        [(Keyword, "jump"), (Number, "5"), (String, "\"x\"")]
+    );
+    assert_eq!(
+        kinds("e \"never closed\n"),
+        [(Name, "e"), (String, "\"never closed\n")]
+    );
+}
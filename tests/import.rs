@@ -0,0 +1,47 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::import::twee::from_twee;
+use renpy_parser::parsers::{Quoting, AST};
+
+#[test]
+fn passages_become_labels_and_links_jumps() {
+    let source = ":: StoryTitle\n\
+        Demo\n\
+        \n\
+        :: StoryData\n\
+        {\"ifid\": \"ABC\", \"start\": \"The Door\"}\n\
+        \n\
+        :: Style [stylesheet]\n\
+        body {}\n\
+        \n\
+        :: The Door {\"position\": \"100,100\"}\n\
+        You see a [[door->Open it]].\n\
+        [[Leave]]\n\
+        \n\
+        :: Open it\n\
+        It creaks.\n\
+        \n\
+        :: 2nd floor\n";
+    let say = |line, text: &str| AST::Say(line, None, text.to_string(), Quoting::default());
+    let label = |line, name: &str, body| AST::Label(line, name.to_string(), body, None);
+
+    assert_eq!(
+        from_twee(source),
+        [
+            label(
+                10,
+                "start",
+                vec![
+                    say(11, "You see a door."),
+                    AST::Jump(11, "open_it".to_string(), false)
+                ],
+            ),
+            label(
+                14,
+                "open_it",
+                vec![say(15, "It creaks."), AST::Return(15, None)]
+            ),
+            label(17, "passage_2nd_floor", vec![AST::Return(17, None)]),
+        ]
+    );
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::incremental::{IncrementalParse, TextEdit};
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parse_scenario_with_options;
+use std::ops::Range;
+
+const SCRIPT: &str = "label start:\n    e \"Hi\"\n    jump two\n\nlabel two:\n    play\n    return\n\nlabel three:\n    return\n";
+
+fn edit(parse: &mut IncrementalParse, find: &str, replacement: &str) {
+    let start = parse.source().find(find).unwrap();
+    parse
+        .edit(&TextEdit {
+            range: start..start + find.len(),
+            replacement: replacement.to_string(),
+        })
+        .unwrap();
+
+    let options = ParseOptions::default();
+    let (ast, errors) = match parse_scenario_with_options(parse.source(), "edit.rpy", &options) {
+        Ok(parsed) => parsed,
+        Err(err) => (Vec::new(), vec![err.to_string()]),
+    };
+    assert_eq!(parse.ast(), ast, "after replacing {:?}", find);
+    assert_eq!(parse.errors(), errors, "after replacing {:?}", find);
+}
+
+#[test]
+fn edits_give_the_same_result_as_parsing_again() {
+    let mut parse = IncrementalParse::new(SCRIPT, "edit.rpy", &ParseOptions::default());
+    assert_eq!(parse.errors().len(), 1);
+
+    edit(&mut parse, "\"Hi\"", "\"Hello\"\n    e \"Again\"");
+    edit(&mut parse, "    play\n", "    play music \"theme.ogg\"\n");
+    edit(
+        &mut parse,
+        "\nlabel three",
+        "\nlabel middle:\n    return\n\nlabel three",
+    );
+    edit(&mut parse, "label two:", "    label two:");
+    edit(&mut parse, "    label two:", "label two:");
+    edit(&mut parse, "jump two", "jump \"two");
+    edit(&mut parse, "jump \"two", "jump two");
+    edit(&mut parse, "label middle:\n    return\n\n", "");
+    assert!(parse.errors().is_empty(), "{:?}", parse.errors());
+}
+
+#[test]
+fn edits_outside_of_the_source_are_errors() {
+    let mut parse = IncrementalParse::new("e \"é\"\n", "edit.rpy", &ParseOptions::default());
+    for range in [4..9, Range { start: 4, end: 3 }, 3..4] {
+        let edit = TextEdit {
+            range,
+            replacement: String::new(),
+        };
+        assert!(parse.edit(&edit).is_err());
+    }
+    assert_eq!(parse.source(), "e \"é\"\n");
+}
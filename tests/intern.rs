@@ -0,0 +1,39 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::intern::{intern, parse_interned, unintern, InternedAST, Interner};
+use renpy_parser::parse_scenario_from_string;
+use std::sync::Arc;
+
+const SCRIPT: &str = "label start:\n    show eileen happy\n    e \"Hi.\"\n    e \"Bye.\"\n    hide eileen happy\n    return\n";
+
+#[test]
+fn names_are_interned_while_parsing() {
+    let mut interner = Interner::new();
+    let (ast, errors) = parse_interned(SCRIPT, "intern.rpy", &mut interner).unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(interner.len(), 2);
+
+    let InternedAST::Label(_, _, block, _) = &ast[0] else {
+        panic!("expected a label, got {:?}", ast[0]);
+    };
+    let (InternedAST::Say(_, Some(a), ..), InternedAST::Say(_, Some(b), ..)) =
+        (&block[1], &block[2])
+    else {
+        panic!("expected dialogue, got {:?}", block);
+    };
+    assert!(Arc::ptr_eq(a, b));
+    let (InternedAST::Show(_, shown, _), InternedAST::Hide(_, hidden, _)) = (&block[0], &block[3])
+    else {
+        panic!("expected show and hide, got {:?}", block);
+    };
+    assert!(Arc::ptr_eq(shown, hidden));
+}
+
+#[test]
+fn interning_while_parsing_matches_interning_afterwards() {
+    let (parsed, _) = parse_scenario_from_string(SCRIPT, "intern.rpy").unwrap();
+
+    let (interned, _) = parse_interned(SCRIPT, "intern.rpy", &mut Interner::new()).unwrap();
+    assert_eq!(interned, intern(parsed.clone(), &mut Interner::new()));
+    assert_eq!(unintern(interned), parsed);
+}
@@ -0,0 +1,279 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::interpreter::{statement_ids, Event, Resolver, StatementId, VM};
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parsers::AST;
+use renpy_parser::{parse_scenario_from_string, parse_script};
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "vm.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+fn said<'a>(event: Event<'a>) -> &'a str {
+    match event {
+        Event::Say { what, .. } => what,
+        event => panic!("expected dialogue, got {:?}", event),
+    }
+}
+
+const SCRIPT: &str = "label start:\n    \"one\"\n    \"two\"\n    \"three\"\n";
+
+#[test]
+fn restore_continues_at_the_saved_statement() {
+    let ast = parse(SCRIPT);
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert_eq!(said(vm.step().unwrap()), "one");
+    let state = vm.state().clone();
+
+    // The same script with lines further down
+    let edited = parse(&format!("{}    \"four\"\n", SCRIPT));
+    let mut vm = VM::new(&edited);
+    vm.restore(state).unwrap();
+    assert_eq!(said(vm.step().unwrap()), "two");
+}
+
+#[test]
+fn restore_restarts_the_label_when_lines_moved() {
+    let ast = parse(SCRIPT);
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert_eq!(said(vm.step().unwrap()), "one");
+    let state = vm.state().clone();
+
+    // A line inserted before the saved position shifts it onto "one"
+    let edited = parse("label start:\n    \"zero\"\n    \"one\"\n    \"two\"\n    \"three\"\n");
+    let mut vm = VM::new(&edited);
+    vm.restore(state.clone()).unwrap();
+    assert_eq!(said(vm.step().unwrap()), "zero");
+
+    // As does deleting one, which would skip "two"
+    let edited = parse("label start:\n    \"one\"\n    \"three\"\n");
+    let mut vm = VM::new(&edited);
+    vm.restore(state).unwrap();
+    assert_eq!(said(vm.step().unwrap()), "one");
+}
+
+const STORY: &str = "define e = Character(\"Eileen\")\n\
+    label start:\n\
+    \x20   scene bg room\n\
+    \x20   show eileen happy\n\
+    \x20   play music \"theme.ogg\"\n\
+    \x20   e \"Hi\"\n\
+    \x20   game_mechanic \"roll dice\"\n\
+    \x20   llm_generate e \"greet the player\"\n\
+    \x20   hide eileen\n\
+    \x20   stop music fadeout 1.5\n\
+    \x20   jump end\n\
+    label end:\n\
+    \x20   \"Bye\"\n\
+    \x20   return\n";
+
+#[test]
+fn steps_present_the_statements_in_order() {
+    let ast = parse(STORY);
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert_eq!(vm.label(), Some("start"));
+
+    let mut events = Vec::new();
+    loop {
+        let event = vm.step().unwrap();
+        events.push(event.clone());
+        if event == Event::End {
+            break;
+        }
+    }
+
+    assert_eq!(
+        events,
+        vec![
+            Event::Scene {
+                image: Some("bg room"),
+                layer: "master",
+            },
+            Event::Show {
+                image: "eileen happy"
+            },
+            Event::Play {
+                channel: "music",
+                filename: "theme.ogg",
+            },
+            Event::Say {
+                who: Some("e"),
+                what: "Hi",
+                seen: false,
+            },
+            Event::GameMechanic("roll dice"),
+            Event::LlmGenerate {
+                who: "e",
+                prompt: Some("greet the player"),
+            },
+            Event::Hide { image: "eileen" },
+            Event::Stop {
+                channel: "music",
+                fadeout: Some(1.5),
+            },
+            Event::JumpTaken {
+                from: Some("start"),
+                to: "end",
+            },
+            Event::Say {
+                who: None,
+                what: "Bye",
+                seen: false,
+            },
+            Event::End,
+        ]
+    );
+    assert!(vm.is_over());
+    assert_eq!(vm.step().unwrap(), Event::End);
+}
+
+#[test]
+fn undefined_labels_are_errors() {
+    let ast = parse(STORY);
+    assert!(VM::new(&ast).start("missing").is_err());
+
+    let ast = parse("label start:\n    jump nowhere\n");
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert!(vm.step().is_err());
+}
+
+#[test]
+fn calls_return_to_where_they_were_made() {
+    let ast =
+        parse("label start:\n    \"one\"\n    \"two\"\nlabel aside:\n    \"aside\"\n    return\n");
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert_eq!(said(vm.step().unwrap()), "one");
+
+    vm.call("aside").unwrap();
+    assert_eq!(vm.label(), Some("aside"));
+    assert_eq!(said(vm.step().unwrap()), "aside");
+    assert_eq!(vm.step().unwrap(), Event::Return);
+    assert_eq!(said(vm.step().unwrap()), "two");
+
+    // Running off the end of the label falls into the next one
+    assert_eq!(said(vm.step().unwrap()), "aside");
+    assert_eq!(vm.step().unwrap(), Event::End);
+}
+
+#[test]
+fn jump_expressions_are_resolved_by_the_game() {
+    struct Route;
+
+    impl Resolver for Route {
+        fn jump_expression(&mut self, expression: &str) -> Option<String> {
+            (expression == "route").then(|| "good".to_string())
+        }
+    }
+
+    // The parser has no `jump expression` yet, so the jump is built by hand
+    let mut ast = parse("label start:\n    jump placeholder\nlabel good:\n    \"Good end\"\n");
+    if let AST::Label(_, _, body, _) = &mut ast[0] {
+        body[0] = AST::Jump(2, "route".to_string(), true);
+    }
+
+    let mut vm = VM::new(&ast).with_resolver(Route).start("start").unwrap();
+    assert_eq!(
+        vm.step().unwrap(),
+        Event::JumpTaken {
+            from: Some("start"),
+            to: "good",
+        }
+    );
+    assert_eq!(said(vm.step().unwrap()), "Good end");
+
+    // Without a resolver the jump fails
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    assert!(vm.step().is_err());
+}
+
+#[test]
+fn statements_that_failed_to_parse_are_errors_once() {
+    let options = ParseOptions {
+        recover: true,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(
+        "label start:\n    play video \"a.ogg\"\n    \"after\"\n",
+        "vm.rpy",
+        &options,
+    )
+    .unwrap();
+
+    let mut vm = VM::new(&script.ast).start("start").unwrap();
+    assert!(vm.step().is_err());
+    assert_eq!(said(vm.step().unwrap()), "after");
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn state_is_saved_and_loaded_as_json() {
+    use renpy_parser::interpreter::State;
+
+    let ast = parse("label start:\n    \"one\"\nlabel aside:\n    \"aside\"\n    return\n");
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    vm.call("aside").unwrap();
+
+    let saved = serde_json::to_string(vm.state()).unwrap();
+    let state: State = serde_json::from_str(&saved).unwrap();
+    assert_eq!(&state, vm.state());
+
+    let mut vm = VM::new(&ast);
+    vm.restore(state).unwrap();
+    assert_eq!(said(vm.step().unwrap()), "aside");
+    assert_eq!(vm.step().unwrap(), Event::Return);
+    assert_eq!(said(vm.step().unwrap()), "one");
+}
+
+#[test]
+fn lines_are_seen_across_playthroughs_and_skipped_when_asked() {
+    let source = "label start:\n    \"one\"\n    show bg\n    \"two\"\n    \"three\"\n";
+    let ast = parse(source);
+
+    let mut vm = VM::new(&ast).start("start").unwrap();
+    let first = vm.step().unwrap();
+    assert_eq!(
+        first,
+        Event::Say {
+            who: None,
+            what: "one",
+            seen: false,
+        }
+    );
+    assert!(vm.is_seen(&[0, 0]));
+    assert!(!vm.is_seen(&[0, 2]));
+    let seen = vm.seen().clone();
+
+    let mut vm = VM::new(&ast).with_seen(seen).start("start").unwrap();
+    assert!(matches!(vm.step().unwrap(), Event::Say { seen: true, .. }));
+
+    // Skipping passes over seen lines but still shows the scene
+    let mut vm = VM::new(&ast)
+        .with_seen(vm.seen().clone())
+        .start("start")
+        .unwrap();
+    vm.set_skipping(true);
+    assert!(vm.is_skipping());
+    assert_eq!(vm.step().unwrap(), Event::Show { image: "bg" });
+    assert_eq!(said(vm.step().unwrap()), "two");
+}
+
+#[test]
+fn statement_ids_survive_edits_elsewhere() {
+    let ids = |source: &str| -> Vec<StatementId> {
+        statement_ids(&parse(source))
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    };
+
+    let before = ids("label start:\n    \"Hi\"\n    \"Hi\"\n    e \"Hi\"\n");
+    let after = ids("label start:\n    show bg\n    \"Hi\"\n    \"Hi\"\n    e \"Hi\"\n");
+    assert_eq!(before, after);
+
+    // Repeats of a line and the same line by another speaker or label differ
+    assert_ne!(before[0], before[1]);
+    assert_ne!(before[0], before[2]);
+    assert_ne!(before[0], ids("label other:\n    \"Hi\"\n")[0]);
+}
@@ -0,0 +1,459 @@
+use renpy_parser::diagnostics::Code;
+use renpy_parser::lexer::Lexer;
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parsers::{ParseError, Quoting, AST};
+use renpy_parser::{
+    group_logical_lines, parse_logical_lines, parse_scenario_from_string, LogicalLines,
+};
+use std::borrow::Cow;
+
+#[test]
+fn local_labels() {
+    let (ast, errors) = parse_scenario_from_string(
+        "label start:\n    jump .sub\nlabel .sub:\n    return\n",
+        "local.rpy",
+    )
+    .unwrap();
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(matches!(&ast[0], AST::Label(_, name, ..) if name == "start"));
+    assert!(matches!(
+        ast[0].block().unwrap()[0],
+        AST::Jump(_, ref target, false) if target == ".sub"
+    ));
+    assert!(matches!(&ast[1], AST::Label(_, name, ..) if name == ".sub"));
+}
+
+#[test]
+fn dotted_names() {
+    let (ast, errors) =
+        parse_scenario_from_string("jump chapter.one\njump . sub\n", "dotted.rpy").unwrap();
+
+    assert!(matches!(&ast[0], AST::Jump(_, target, _) if target == "chapter.one"));
+    // The dot of a local name can't be apart from it
+    assert_eq!(ast.len(), 1);
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn non_ascii_dialogue_keeps_its_text_and_byte_offsets() {
+    let source = "эйлин \"Привет, мир\"\nlabel 始め:\n    \"こんにちは、世界！\"\n";
+    let (ast, errors) = parse_scenario_from_string(source, "unicode.rpy").unwrap();
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(
+        ast,
+        [
+            AST::Say(
+                1,
+                Some("эйлин".to_string()),
+                "Привет, мир".to_string(),
+                Quoting::default()
+            ),
+            AST::Label(
+                2,
+                "始め".to_string(),
+                vec![AST::Say(
+                    3,
+                    None,
+                    "こんにちは、世界！".to_string(),
+                    Quoting::default()
+                )],
+                None
+            ),
+        ]
+    );
+
+    let lines = parse_logical_lines(source, "unicode.rpy").unwrap();
+    let spans: Vec<_> = lines.iter().map(|line| line.span()).collect();
+    assert_eq!(spans, [0..33, 34..47, 52..81]);
+    assert_eq!(&source[spans[2].clone()], "\"こんにちは、世界！\"");
+}
+
+#[test]
+fn multi_byte_chars_next_to_tokens_are_counted_in_bytes() {
+    let lines = parse_logical_lines("имя \"日本語\"✨ 終\n", "unicode.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+
+    assert_eq!(l.name().as_deref(), Some("имя"));
+    assert_eq!(l.pos(), 6);
+    assert_eq!(l.string().unwrap().value, "日本語");
+    assert_eq!(l.pos(), 18);
+
+    let error = l.expect_eol().unwrap_err();
+    let error = error.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(error.pos, Some(18));
+    assert_eq!(error.column, Some(10));
+
+    assert_eq!(l.match_("✨").unwrap().as_deref(), Some("✨"));
+    assert_eq!(l.pos(), 21);
+    assert_eq!(l.rest(), "終");
+    assert!(l.eol());
+}
+
+#[test]
+fn patterns_match_the_same_every_time_they_are_used() {
+    let lines = parse_logical_lines("SHOW Eileen happy\n", "case.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+
+    for _ in 0..3 {
+        let mut l = Lexer::borrowed(&blocks, false);
+        l.advance();
+        assert_eq!(l.keyword("show").unwrap(), None);
+        assert_eq!(
+            l.keyword_ignore_case("show").unwrap().as_deref(),
+            Some("SHOW")
+        );
+        assert_eq!(l.match_(r"[a-z]+").unwrap(), None);
+        assert_eq!(
+            l.match_ignore_case(r"[a-z]+").unwrap().as_deref(),
+            Some("Eileen")
+        );
+        assert_eq!(l.match_(r"[a-z]+").unwrap().as_deref(), Some("happy"));
+    }
+}
+
+#[test]
+fn columns_count_stripped_indentation_and_restart_on_each_physical_line() {
+    let source = "label start:\n    label inner:\n        jump \"x\"\n";
+    let script = renpy_parser::parse_script(source, "columns.rpy", &Default::default()).unwrap();
+    let error = script.diagnostics.errors().next().unwrap();
+    assert_eq!((error.line_number, error.column), (3, Some(14)));
+
+    let lines =
+        parse_logical_lines("label start:\n    define x = (1,\n   2) y\n", "columns.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    let mut l = l.subblock_lexer(false);
+    l.advance();
+
+    assert_eq!(l.column(), 5);
+    l.match_(r"define x = \(1,\s*2\)").unwrap().unwrap();
+    assert_eq!(l.column(), 6);
+}
+
+#[test]
+fn triple_quoted_strings_span_lines() {
+    let source = "label start:\n    e \"\"\"First line\n    # not a comment\n\n    \"quoted\" end\"\"\"\n    \"\"\"\"\"\"\n    return\n";
+    let (ast, errors) = parse_scenario_from_string(source, "triple.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let triple = Quoting {
+        triple: true,
+        ..Quoting::default()
+    };
+    assert_eq!(
+        *ast[0].block().unwrap(),
+        [
+            AST::Say(
+                2,
+                Some("e".to_string()),
+                "First line\n    # not a comment\n\n    \"quoted\" end".to_string(),
+                triple
+            ),
+            AST::Say(6, None, String::new(), triple),
+            AST::Return(7, Some(String::new())),
+        ]
+    );
+}
+
+#[test]
+fn escapes_resolve_to_unicode_and_the_source_is_kept() {
+    use renpy_parser::lexer::unescape;
+
+    assert_eq!(unescape(r#"\"a\" \\ \n"#), "\"a\" \\ \n");
+    assert_eq!(unescape(r"\u00e9\u263A\u1F600 \uZ"), "é☺\u{1F60}0 uZ");
+    assert_eq!(unescape(r"\{b} \[name] 100\%"), "{{b} [[name] 100%%");
+    assert_eq!(unescape(r"\q\"), "q\\");
+
+    let lines = parse_logical_lines(r#"e "caf\u00e9 \"x\"" r"C:\new""#, "escapes.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    l.name();
+
+    let escaped = l.string().unwrap();
+    assert_eq!(escaped.value, "café \"x\"");
+    assert_eq!(escaped.source, r#""caf\u00e9 \"x\"""#);
+    let raw = l.string().unwrap();
+    assert!(raw.raw);
+    assert_eq!(raw.value, r"C:\new");
+    assert_eq!(raw.source, r#"r"C:\new""#);
+}
+
+#[test]
+fn peeking_leaves_the_position_alone() {
+    let lines = parse_logical_lines("show  eileen \"hi\"\n", "peek.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+
+    assert_eq!(l.peek_keyword("show").unwrap().as_deref(), Some("show"));
+    assert_eq!(l.peek_word().as_deref(), Some("show"));
+    assert_eq!(l.peek_string(), None);
+    assert_eq!(l.pos(), 0);
+
+    l.keyword("show").unwrap();
+    assert_eq!(l.peek_keyword("eil").unwrap(), None);
+    assert_eq!(l.peek_word().as_deref(), Some("eileen"));
+    assert_eq!(l.pos(), 4);
+
+    l.word();
+    assert_eq!(l.peek_string().unwrap().value, "hi");
+    assert_eq!(l.pos(), 12);
+}
+
+#[test]
+fn python_is_matched_up_to_a_stop_char_outside_brackets_and_strings() {
+    let source = "x = f(a, \"b:c\", [1, {2: 3}]) if y: z\nf(a])\n";
+    let lines = parse_logical_lines(source, "python.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+
+    l.advance();
+    assert_eq!(
+        l.python_expression().unwrap().as_deref(),
+        Some("x = f(a, \"b:c\", [1, {2: 3}]) if y")
+    );
+    assert!(l.match_(":").unwrap().is_some());
+    assert_eq!(l.delimited_python(",").unwrap().as_deref(), Some("z"));
+    assert_eq!(l.delimited_python(",").unwrap(), None);
+
+    l.advance();
+    assert!(l.python_expression().is_err());
+}
+
+#[test]
+fn numbers_are_parsed_and_out_of_range_ones_are_errors() {
+    let source = "-42 1.5e3 .5\n99999999999999999999 2\n";
+    let lines = parse_logical_lines(source, "numbers.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    assert_eq!(l.int_value().unwrap(), Some(-42));
+    assert_eq!(l.float_value().unwrap(), Some(1500.0));
+    assert_eq!(l.float_value().unwrap(), Some(0.5));
+    assert_eq!(l.int_value().unwrap(), None);
+
+    l.advance();
+    let error = l.int_value().unwrap_err();
+    let error = error.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(error.code, Code::InvalidNumber);
+    assert_eq!(
+        error.message,
+        "integer '99999999999999999999' is out of range"
+    );
+    assert!(l.float_value().unwrap().is_none());
+    assert_eq!(l.int_value().unwrap_err().to_string(), error.to_string());
+}
+
+#[test]
+fn reverting_to_a_checkpoint_restores_the_line_and_position() {
+    let lines = parse_logical_lines("e \"Hi\"\nreturn\n", "checkpoint.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    assert!(l.name().is_some());
+    let state = l.checkpoint();
+    assert_eq!(state.pos(), 1);
+
+    assert!(l.string().is_some());
+    assert!(l.advance());
+    assert!(!l.advance());
+    assert!(l.eob());
+    assert_ne!(l.checkpoint(), state);
+
+    l.revert(state);
+    assert!(!l.eob());
+    assert_eq!(l.checkpoint(), state);
+    assert_eq!(l.text(), "e \"Hi\"");
+    assert_eq!(l.string().unwrap().value, "Hi");
+}
+
+#[test]
+fn borrowed_and_owned_lexers_walk_the_same_sub_blocks() {
+    let source = "label start:\n    e \"Hi\"\n    return\n";
+    let lines = parse_logical_lines(source, "blocks.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    assert!(matches!(blocks[0].subblocks[0].text, Cow::Borrowed(_)));
+
+    let mut borrowed = Lexer::borrowed(&blocks, false);
+    let mut owned = Lexer::new(blocks.clone(), false);
+    for l in [&mut borrowed, &mut owned] {
+        l.advance();
+        let mut sub = l.subblock_lexer(false);
+        let mut texts = vec![];
+        while sub.advance() {
+            texts.push(sub.text().to_string());
+        }
+        assert_eq!(texts, ["e \"Hi\"", "return"]);
+        assert_eq!(sub.get_location(), 3);
+    }
+}
+
+#[test]
+fn keywords_only_match_whole_words() {
+    let source = "returning_home \"hi\"\nreturn\n";
+    let lines = parse_logical_lines(source, "words.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    assert_eq!(l.keyword("return").unwrap(), None);
+    assert_eq!(l.name().as_deref(), Some("returning_home"));
+    l.advance();
+    assert_eq!(l.keyword("return").unwrap().as_deref(), Some("return"));
+
+    let (ast, _) = parse_scenario_from_string(source, "words.rpy").unwrap();
+    assert!(
+        matches!(&ast[0], AST::Say(_, Some(who), what, _) if who == "returning_home" && what == "hi")
+    );
+    assert!(matches!(ast[1], AST::Return(..)));
+}
+
+#[test]
+fn reserved_words_can_be_added_and_freed() {
+    let lines = parse_logical_lines("show cutscene\n", "reserved.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let names = |options: &ParseOptions| {
+        let mut l = Lexer::borrowed(&blocks, false).with_options(options);
+        l.advance();
+        let first = l.name().map(|name| name.to_string());
+        l.word();
+        (first, l.name().map(|name| name.to_string()))
+    };
+
+    assert_eq!(
+        names(&ParseOptions::default()),
+        (None, Some("cutscene".into()))
+    );
+    assert_eq!(
+        names(&ParseOptions {
+            reserved_words: vec!["cutscene".into()],
+            unreserved_words: vec!["show".into()],
+            ..ParseOptions::default()
+        }),
+        (Some("show".into()), None)
+    );
+}
+
+#[test]
+fn patterns_are_anchored_at_the_current_position() {
+    let lines = parse_logical_lines("Play music \"a.ogg\"\n", "anchored.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    assert_eq!(l.match_("music").unwrap(), None);
+    assert_eq!(l.match_ignore_case("m?usic").unwrap(), None);
+
+    let error = l.require("play").unwrap_err();
+    let error = error.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(error.code, Code::ExpectedToken);
+    assert_eq!(error.message, "expected 'play' not found");
+
+    assert_eq!(l.require_ignore_case("play").unwrap(), "Play");
+    assert_eq!(l.match_("^music").unwrap().as_deref(), Some("music"));
+}
+
+#[test]
+fn patterns_keep_matching_past_the_compiled_pattern_cache() {
+    let lines = parse_logical_lines("play music \"a.ogg\"\n", "anchored.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+
+    for n in 0..1000 {
+        let start = l.checkpoint();
+        assert_eq!(l.match_(&format!("x{}", n)).unwrap(), None);
+        assert_eq!(l.match_("pla").unwrap().as_deref(), Some("pla"));
+        l.revert(start);
+    }
+}
+
+#[test]
+fn raw_statements_take_the_rest_of_the_line_and_their_block() {
+    let source = "python early:\n    x = 1\n    if x:\n        y = 2\nreturn\n";
+    let lines = parse_logical_lines(source, "raw.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+    l.keyword("python").unwrap();
+
+    let (text, block) = l.renpy_statement();
+    assert_eq!(text, "early:");
+    assert_eq!(
+        block.iter().map(|b| b.text.as_ref()).collect::<Vec<_>>(),
+        ["x = 1", "if x:"]
+    );
+    assert_eq!(block[1].subblocks[0].text, "y = 2");
+    assert_eq!(l.text(), "return");
+}
+
+#[test]
+fn blocks_can_be_inspected_without_a_sub_lexer() {
+    let source = "label empty:\nlabel start:\n    e \"Hi\"\n    menu:\n        \"Go\":\n            return\n";
+    let lines = parse_logical_lines(source, "inspect.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    assert!(!l.has_block());
+    assert_eq!(l.subblock_len(), 0);
+
+    l.advance();
+    assert!(!l.has_block());
+    assert!(l.subblock().is_empty());
+
+    l.advance();
+    assert!(l.has_block());
+    assert_eq!(l.subblock_len(), 2);
+    assert_eq!(l.subblock()[1].text, "menu:");
+    assert_eq!(l.subblock()[1].subblocks.len(), 1);
+    assert_eq!(l.text(), "label start:");
+}
+
+#[test]
+fn sub_lexers_borrow_their_parents_blocks() {
+    let source = "label a:\n    menu:\n        \"Go\":\n            return\nlabel b:\n    return\n";
+    let lines = parse_logical_lines(source, "nested.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let mut l = Lexer::borrowed(&blocks, false);
+    l.advance();
+
+    let mut menu = l.subblock_lexer(false);
+    menu.advance();
+    let mut choices = menu.subblock_lexer(false);
+    choices.advance();
+    assert!(std::ptr::eq(
+        choices.subblock(),
+        blocks[0].subblocks[0].subblocks[0].subblocks.as_slice()
+    ));
+
+    l.advance();
+    assert_eq!(l.text(), "label b:");
+    assert_eq!(choices.text(), "\"Go\":");
+    assert!(!choices.advance());
+}
+
+#[test]
+fn logical_lines_are_read_lazily() {
+    let source = "label start:\n\n    e \"Hi\" # greeting\n    $ x = (1,\n        2)\n    e \"never closed\n";
+    let mut lines = LogicalLines::new(source, "lazy.rpy");
+    let spans: Vec<_> = lines
+        .by_ref()
+        .take(3)
+        .map(|line| line.unwrap().span())
+        .collect();
+    assert_eq!(
+        spans
+            .iter()
+            .map(|span| &source[span.clone()])
+            .collect::<Vec<_>>(),
+        ["label start:", "e \"Hi\"", "$ x = (1,\n        2)"]
+    );
+    assert_eq!(lines.blank_lines, [2]);
+    assert_eq!(lines.comments.len(), 1);
+
+    assert!(lines.next().unwrap().is_err());
+    assert!(parse_logical_lines(source, "lazy.rpy").is_err());
+}
@@ -0,0 +1,263 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::diagnostics::{Code, Diagnostic, Severity};
+use renpy_parser::lint::text::{CheckText, DialogueText};
+use renpy_parser::lint::{lint, Level, LintConfig, LintContext, Linter, Rule};
+use renpy_parser::options::{CommentHandling, ParseOptions};
+use renpy_parser::parsers::{walk, NodeRef, AST};
+use renpy_parser::{parse_scenario_from_string, parse_script};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn configs_naming_unknown_rules_fail() {
+    let mut config = LintConfig::default();
+    config.rules.insert("dead-code".to_string(), Level::Allow);
+    assert!(Linter::default().with_config(&config).is_ok());
+
+    config.rules.insert("ded-code".to_string(), Level::Allow);
+    let err = Linter::default().with_config(&config).unwrap_err();
+    assert_eq!(err.to_string(), "unknown lint rule 'ded-code'");
+}
+
+fn duplicates(source: &str) -> Vec<Diagnostic> {
+    let (ast, _) = parse_scenario_from_string(source, "labels.rpy").unwrap();
+    lint(&ast)
+        .into_iter()
+        .filter(|d| d.code == Code::DuplicateLabel)
+        .collect()
+}
+
+#[test]
+fn duplicate_labels_point_at_both_definitions() {
+    let found = duplicates("label start:\n    return\nlabel start:\n    return\n");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].node.index, 3);
+    assert_eq!(found[0].related.len(), 1);
+    assert_eq!(found[0].related[0].node.index, 1);
+}
+
+#[test]
+fn local_labels_are_duplicates_only_under_the_same_global_label() {
+    let source = "label start:\n    label .s:\n        return\nlabel other:\n    label .s:\n        return\n";
+    assert!(duplicates(source).is_empty());
+
+    let source =
+        "label start:\n    label .s:\n        return\n    label start.s:\n        return\n";
+    let found = duplicates(source);
+    assert_eq!(found.len(), 1);
+    assert_eq!(
+        found[0].message,
+        "label 'start.s' is defined more than once"
+    );
+    assert_eq!(found[0].related[0].node.index, 2);
+}
+
+/// Flags narration, lines of dialogue without a speaker
+struct NoNarration;
+
+impl Rule for NoNarration {
+    fn name(&self) -> &str {
+        "no-narration"
+    }
+
+    fn check(&self, ctx: &LintContext) -> Vec<Diagnostic> {
+        let mut rv = Vec::new();
+        walk(ctx.ast, &mut |path, node| {
+            if let AST::Say(_, None, _, _) = node {
+                let node = NodeRef::new(path, node);
+                rv.push(Diagnostic::warning(
+                    node,
+                    Code::TextCheck,
+                    "narration".into(),
+                ));
+            }
+        });
+        rv
+    }
+}
+
+#[test]
+fn rules_run_at_their_levels_unless_suppressed() {
+    let source = "label start:\n    \"one\"\n    # renpy-parser: disable=no-narration\n    \"two\"\n    \"three\"\n    return\n";
+    let options = ParseOptions {
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(source, "rules.rpy", &options).unwrap();
+    let found = |linter: &Linter| {
+        linter
+            .check_script(&script)
+            .into_iter()
+            .map(|d| (d.severity, d.node.index, d.node.path))
+            .collect::<Vec<_>>()
+    };
+
+    let linter = Linter::new().with(NoNarration);
+    assert_eq!(
+        found(&linter),
+        [
+            (Severity::Warning, 2, vec![0, 0]),
+            (Severity::Warning, 5, vec![0, 2]),
+        ]
+    );
+    let linter = linter.with_level("no-narration", Level::Deny);
+    assert_eq!(found(&linter)[0].0, Severity::Error);
+    let linter = linter.with_level("no-narration", Level::Allow);
+    assert!(found(&linter).is_empty());
+
+    assert!(Linter::default()
+        .rules()
+        .all(|rule| rule.name() != "no-narration"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn levels_are_read_from_toml() {
+    let config =
+        LintConfig::from_toml("[rules]\ndead-code = \"allow\"\nfall-through = \"deny\"\n").unwrap();
+    let linter = Linter::default().with_config(&config).unwrap();
+    let level = |name: &str| linter.level(linter.rules().find(|r| r.name() == name).unwrap());
+    assert_eq!(level("dead-code"), Level::Allow);
+    assert_eq!(level("fall-through"), Level::Deny);
+    assert_eq!(level("undefined-label"), Level::Deny);
+    assert!(LintConfig::from_toml("[rules]\ndead-code = \"sometimes\"\n").is_err());
+}
+
+/// Lines and messages of the findings with the code
+fn found(source: &str, code: Code) -> Vec<(usize, String)> {
+    let (ast, errors) = parse_scenario_from_string(source, "rules.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    lint(&ast)
+        .into_iter()
+        .filter(|d| d.code == code)
+        .map(|d| (d.node.index, d.message))
+        .collect()
+}
+
+#[test]
+fn jumps_to_undefined_labels_suggest_the_closest_one() {
+    let source = "label start:\n    jump strat\n    label .local:\n        jump .local\nlabel other:\n    jump .locl\n    jump nowhere_near\n    jump start.local\n";
+    assert_eq!(
+        found(source, Code::UndefinedLabel),
+        [
+            (
+                2,
+                "jump to undefined label 'strat'; did you mean 'start'?".to_string()
+            ),
+            (6, "jump to undefined label '.locl'".to_string()),
+            (7, "jump to undefined label 'nowhere_near'".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn hides_follow_what_shows_and_scenes_put_on_screen() {
+    let source = "label start:\n    scene bg room\n    show eileen happy\n    hide bg\n    hide eileen\n    scene black\n    hide eileen\n    return\n";
+    assert_eq!(
+        found(source, Code::HideWithoutShow),
+        [(7, "hide of 'eileen' which was never shown".to_string())]
+    );
+}
+
+#[test]
+fn characters_are_cross_referenced_with_their_speakers() {
+    let source = "define e = Character(\"Eileen\")\ndefine m = Character(\"Mary\")\nlabel start:\n    e \"Hi\"\n    s \"Who?\"\n    s \"Me\"\n    return\n";
+    assert_eq!(
+        found(source, Code::UnusedCharacter),
+        [(2, "character 'm' is defined but never speaks".to_string())]
+    );
+    assert_eq!(
+        found(source, Code::UndefinedCharacter),
+        [
+            (5, "speaker 's' is not defined as a Character".to_string()),
+            (6, "speaker 's' is not defined as a Character".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn renpys_own_speakers_need_no_definition() {
+    let source = "label start:\n    narrator \"Once upon a time\"\n    extend \" there was a script\"\n    centered \"The End\"\n    return\n";
+    assert!(found(source, Code::UndefinedCharacter).is_empty());
+}
+
+#[test]
+fn labels_falling_through_name_the_label_they_run_into() {
+    let source = "label a:\n    \"x\"\nlabel b:\n    jump a\nlabel c:\n    label .inner:\n        \"y\"\nlabel d:\n    \"z\"\n";
+    assert_eq!(
+        found(source, Code::FallThrough),
+        [
+            (
+                1,
+                "label 'a' ends without a return or jump, falling through into 'b'".to_string()
+            ),
+            (
+                6,
+                "label '.inner' ends without a return or jump, falling through into 'd'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn dialogue_without_text_of_its_own_is_flagged() {
+    let source = "label start:\n    \"   \"\n    \"[name]\"\n    \"{b}{/b}\"\n    \"Hi [name]\"\n    \"{i}Hi{/i}\"\n    return\n";
+    let lines: Vec<_> = found(source, Code::EmptyDialogue)
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect();
+    assert_eq!(lines, [2, 3, 4]);
+    assert_eq!(
+        found(source, Code::EmptyDialogue)[1].1,
+        "dialogue \"[name]\" has no text of its own"
+    );
+}
+
+#[test]
+fn statements_after_a_return_or_jump_in_the_same_block_are_dead() {
+    let source = "label start:\n    jump b\n    \"never\"\n    \"again\"\nlabel b:\n    label .inner:\n        return\n        \"dead\"\n    \"alive\"\n    return\nlabel c:\n    return\n";
+    assert_eq!(
+        found(source, Code::DeadCode),
+        [
+            (3, "unreachable after the jump on line 2".to_string()),
+            (8, "unreachable after the return on line 7".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn text_checkers_see_dialogue_only() {
+    let source = "define teh = Character(\"Teh\")\nlabel start:\n    # renpy-parser: disable=spelling\n    teh \"teh first\"\n    label .inner:\n        \"teh second\"\n    show teh\n    return\n";
+    let options = ParseOptions {
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(source, "text.rpy", &options).unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let spelling = CheckText::new("spelling", move |line: &DialogueText| {
+        let label = line.label.map(str::to_string);
+        log.lock()
+            .unwrap()
+            .push((line.speaker.map(str::to_string), label));
+        match line.text.contains("teh") {
+            true => vec![format!("'teh' in \"{}\"", line.text)],
+            false => vec![],
+        }
+    });
+    let found = Linter::new().with(spelling).check_script(&script);
+
+    assert_eq!(
+        found.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        ["warning[RPY1010] on line 6: 'teh' in \"teh second\""]
+    );
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            (Some("teh".to_string()), Some("start".to_string())),
+            (None, Some(".inner".to_string())),
+        ]
+    );
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "lsp")]
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use serde_json::{json, Value};
+
+const SCRIPT: &str = "define e = Character(\"Eileen\")\nlabel start:\n    e \"Hi\"\n    play video \"theme.ogg\"\n    jump ending\nlabel ending:\n    return\n";
+const URI: &str = "file:///game/script.rpy";
+
+struct Client {
+    connection: Connection,
+    next_id: i32,
+}
+
+impl Client {
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = Request::new(RequestId::from(self.next_id), method.into(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(response) => response.response_result.unwrap(),
+            other => panic!("expected a response, got {:?}", other),
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let notification = Notification::new(method.into(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn position(&mut self, method: &str, line: u32, character: u32) -> Value {
+        let params = json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        });
+        self.request(method, params)
+    }
+}
+
+#[test]
+fn the_server_answers_about_an_open_script() {
+    let (server, connection) = Connection::memory();
+    let thread = std::thread::spawn(move || renpy_parser::lsp::run(&server));
+    let mut client = Client {
+        connection,
+        next_id: 0,
+    };
+
+    let initialized = client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(initialized["capabilities"]["definitionProvider"], true);
+    client.notify("initialized", json!({}));
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "renpy", "version": 1, "text": SCRIPT } }),
+    );
+    let Message::Notification(published) = client.connection.receiver.recv().unwrap() else {
+        panic!("expected diagnostics");
+    };
+    assert_eq!(published.method, "textDocument/publishDiagnostics");
+    let diagnostics = published.params["diagnostics"].as_array().unwrap();
+    let codes: Vec<_> = diagnostics
+        .iter()
+        .map(|d| {
+            (
+                d["code"].as_str().unwrap(),
+                d["range"]["start"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(codes, [("RPY0205", 3)]);
+
+    let definition = client.position("textDocument/definition", 4, 10);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 5, "character": 6 })
+    );
+    let definition = client.position("textDocument/definition", 2, 4);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 0, "character": 7 })
+    );
+
+    let completion = client.position("textDocument/completion", 4, 9);
+    let labels: Vec<_> = completion
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, ["ending", "start"]);
+    let completion = client.position("textDocument/completion", 2, 4);
+    assert_eq!(completion.as_array().unwrap().len(), 3);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let names: Vec<_> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["e", "start", "ending"]);
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    thread.join().unwrap().unwrap();
+}
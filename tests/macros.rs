@@ -0,0 +1,33 @@
+#![cfg(feature = "macros")]
+
+use renpy_parser::{parse_scenario_from_string, renpy};
+
+#[test]
+fn inline_scripts_match_parsed_ones() {
+    let ast = renpy! {
+        define e = Character("Eileen")
+        label start:
+            scene "bg room"
+            show eileen
+            e "Hello"
+            play music "theme.ogg"
+            stop music fadeout 1.5
+            jump end
+        label end:
+            return
+    };
+
+    let source = "define e = Character(\"Eileen\")\n\
+        label start:\n\
+        \x20   scene bg room\n\
+        \x20   show eileen\n\
+        \x20   e \"Hello\"\n\
+        \x20   play music \"theme.ogg\"\n\
+        \x20   stop music fadeout 1.5\n\
+        \x20   jump end\n\
+        label end:\n\
+        \x20   return\n";
+    let (parsed, errors) = parse_scenario_from_string(source, "inline.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(ast, parsed);
+}
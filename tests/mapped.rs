@@ -0,0 +1,74 @@
+#![cfg(feature = "mmap")]
+
+use renpy_parser::borrowed::BorrowedAST;
+use renpy_parser::encoding::EncodingFallback;
+use renpy_parser::mapped::MappedScript;
+use std::borrow::Cow;
+use std::fs;
+
+#[test]
+fn mapped_scripts_borrow_from_the_mapping() {
+    let path = std::env::temp_dir().join(format!("renpy-mapped-{}.rpy", std::process::id()));
+    fs::write(&path, "label start:\n    e \"Hello\"\n").unwrap();
+
+    let script = MappedScript::open(&path).unwrap();
+    let (ast, errors) = script.parse("mapped.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let mapping = script.source().as_bytes().as_ptr_range();
+    let BorrowedAST::Label(_, Cow::Borrowed(name), block, _) = &ast[0] else {
+        panic!("expected a borrowed label, got {:?}", ast[0]);
+    };
+    assert!(mapping.contains(&name.as_ptr()));
+    let BorrowedAST::Say(_, _, Cow::Borrowed(what), _) = &block[0] else {
+        panic!("expected borrowed dialogue, got {:?}", block[0]);
+    };
+    assert_eq!(*what, "Hello");
+    assert!(mapping.contains(&what.as_ptr()));
+
+    drop(script);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mapped_scripts_are_decoded_like_read_ones() {
+    let path = std::env::temp_dir().join(format!("renpy-bom-{}.rpy", std::process::id()));
+    let labels = |script: &MappedScript| {
+        let (ast, errors) = script.parse("bom.rpy").unwrap();
+        let names: Vec<_> = ast
+            .iter()
+            .map(|node| match node {
+                BorrowedAST::Label(_, name, ..) => name.to_string(),
+                other => panic!("expected a label, got {:?}", other),
+            })
+            .collect();
+        (names, errors)
+    };
+
+    fs::write(&path, "\u{feff}label start:\n    return\n").unwrap();
+    let script = MappedScript::open(&path).unwrap();
+    assert_eq!(labels(&script), (vec!["start".to_string()], vec![]));
+    drop(script);
+
+    let mut utf16 = vec![0xff, 0xfe];
+    utf16.extend(
+        "label début:\n    return\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    fs::write(&path, utf16).unwrap();
+    let script = MappedScript::open(&path).unwrap();
+    assert_eq!(labels(&script), (vec!["début".to_string()], vec![]));
+    drop(script);
+
+    fs::write(&path, b"label d\xe9but:\n    return\n").unwrap();
+    assert!(MappedScript::open(&path).is_err());
+    let script = MappedScript::open_with(&path, EncodingFallback::Latin1).unwrap();
+    let (names, errors) = labels(&script);
+    assert_eq!(names, ["début"]);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("Latin-1"), "{:?}", errors);
+    drop(script);
+
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::merge::{merge, merge_all};
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::parsers::AST;
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "merge.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+#[test]
+fn additions_follow_the_base_script() {
+    let one = parse("label start:\n    jump two\n");
+    let two = parse("label two:\n    jump three\n");
+    let three = parse("label three:\n    return\n");
+
+    assert_eq!(
+        merge_all([one, two, three]).unwrap(),
+        parse("label start:\n    jump two\nlabel two:\n    jump three\nlabel three:\n    return\n")
+    );
+}
+
+#[test]
+fn duplicate_labels_are_conflicts() {
+    let base = parse("label start:\n    return\nlabel end:\n    return\n");
+    let addition = parse("define x = 1\nlabel end:\n    return\n");
+
+    let conflict = merge(base, addition).unwrap_err();
+    assert_eq!(conflict.to_string(), "duplicate label definitions: end");
+    assert_eq!(conflict.duplicates[0].base.index, 3);
+    assert_eq!(conflict.duplicates[0].addition.index, 2);
+}
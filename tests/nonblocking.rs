@@ -0,0 +1,32 @@
+#![cfg(feature = "async")]
+
+use renpy_parser::nonblocking::{
+    parse_scenario_from_file_async, parse_scenario_from_file_offloaded,
+};
+use renpy_parser::parse_scenario_from_string;
+use std::fs;
+
+#[test]
+fn files_parse_the_same_whether_read_or_parsed_off_the_runtime() {
+    let source = "label start:\n    e \"Hello\"\n    play\n    return\n";
+    let path = std::env::temp_dir().join(format!("renpy-async-{}.rpy", std::process::id()));
+    fs::write(&path, source).unwrap();
+    let filename = path.to_str().unwrap();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let read = runtime
+        .block_on(parse_scenario_from_file_async(filename))
+        .unwrap();
+    let offloaded = runtime
+        .block_on(parse_scenario_from_file_offloaded(filename))
+        .unwrap();
+    assert_eq!(read, parse_scenario_from_string(source, filename).unwrap());
+    assert_eq!(offloaded, read);
+
+    fs::remove_file(&path).unwrap();
+    assert!(runtime
+        .block_on(parse_scenario_from_file_async(filename))
+        .is_err());
+}
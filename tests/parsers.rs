@@ -0,0 +1,536 @@
+#[cfg(feature = "std")]
+use renpy_parser::borrowed::parse_borrowed;
+use renpy_parser::diagnostics::Code;
+#[cfg(feature = "std")]
+use renpy_parser::intern::{intern, parse_interned, unintern, Interner};
+use renpy_parser::lexer::Lexer;
+use renpy_parser::options::{
+    CommentHandling, IndentRule, ParseOptions, TabPolicy, WhitespacePolicy,
+};
+use renpy_parser::parsers::{
+    inject_into_label, inject_node, normalize, parse_block, remove_node, renumber, walk,
+    ParseError, Quoting, AST,
+};
+#[cfg(feature = "std")]
+use renpy_parser::writer::to_source;
+use renpy_parser::{
+    group_logical_lines, parse_logical_lines, parse_scenario_from_string, parse_script,
+};
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "edit.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+fn indices(ast: &[AST]) -> Vec<usize> {
+    let mut rv = Vec::new();
+    walk(ast, &mut |_, node| rv.push(node.index()));
+    rv
+}
+
+#[test]
+fn removing_a_statement_shifts_the_ones_after_it() {
+    let ast = parse("label start:\n    \"one\"\n    \"two\"\n    \"three\"\n");
+    let ast = remove_node(ast, 3);
+    assert_eq!(indices(&ast), [1, 2, 3]);
+    assert_eq!(ast, parse("label start:\n    \"one\"\n    \"three\"\n"));
+}
+
+#[test]
+fn removing_a_label_shifts_by_its_whole_body() {
+    let ast = parse(
+        "label first:\n    \"one\"\n    \"two\"\nlabel second:\n    \"three\"\ninit:\n    define x = 1\n",
+    );
+    let ast = remove_node(ast, 1);
+    assert_eq!(
+        ast,
+        parse("label second:\n    \"three\"\ninit:\n    define x = 1\n")
+    );
+
+    let ast = remove_node(ast, 3);
+    assert_eq!(ast, parse("label second:\n    \"three\"\n"));
+}
+
+#[test]
+fn injected_statements_land_inside_label_bodies() {
+    let ast = parse("label start:\n    \"one\"\n    \"three\"\nlabel end:\n    return\n");
+    let two = AST::Say(3, None, "two".to_string(), Quoting::default());
+    let ast = inject_node(ast, two);
+    assert_eq!(
+        ast,
+        parse("label start:\n    \"one\"\n    \"two\"\n    \"three\"\nlabel end:\n    return\n")
+    );
+
+    let ast = inject_into_label(ast, "start", AST::Jump(0, "end".to_string(), false)).unwrap();
+    assert_eq!(indices(&ast), [1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(
+        ast,
+        parse("label start:\n    \"one\"\n    \"two\"\n    \"three\"\n    jump end\nlabel end:\n    return\n")
+    );
+    assert!(inject_into_label(ast, "missing", AST::Return(0, None)).is_err());
+}
+
+#[test]
+fn renumbering_numbers_nested_blocks_in_document_order() {
+    let mut ast = vec![
+        AST::Label(
+            7,
+            "start".to_string(),
+            vec![AST::Init(7, vec![AST::Define(0, "x = 1".to_string())], 0)],
+            None,
+        ),
+        AST::Return(2, None),
+    ];
+    renumber(&mut ast);
+    assert_eq!(indices(&ast), [1, 2, 3, 4]);
+
+    let ast = remove_node(ast, 2);
+    assert_eq!(indices(&ast), [1, 2]);
+}
+
+#[test]
+fn cosmetically_different_scripts_normalize_equal() {
+    let mut tidy = parse(
+        "label start:\n    scene bg room\n    show eileen happy\n    e \"Hi there\"\n    return\n",
+    );
+    let mut messy = parse(
+        "label start:\n\n    scene  bg   room onlayer master\n    show eileen    happy\n    e 'Hi   there'\n\n    return\n",
+    );
+    assert_ne!(tidy, messy);
+
+    normalize(&mut tidy);
+    normalize(&mut messy);
+    assert_eq!(tidy, messy);
+
+    let mut shown =
+        parse("show eileen at left onlayer master with dissolve\nshow eileen onlayer back\n");
+    normalize(&mut shown);
+    let clauses: Vec<_> = shown
+        .iter()
+        .map(|node| match node {
+            AST::Show(_, _, clauses) => clauses.as_deref(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        clauses,
+        [Some("at left with dissolve"), Some("onlayer back")]
+    );
+}
+
+#[test]
+fn recovered_statements_keep_their_error_and_text() {
+    let options = ParseOptions {
+        recover: true,
+        ..ParseOptions::default()
+    };
+    let source = "label start:\n    play video \"a.ogg\"\n    return\n";
+    let mut ast = parse_script(source, "edit.rpy", &options).unwrap().ast;
+
+    let error = &mut ast[0].block_mut().unwrap()[0];
+    assert_eq!(
+        *error,
+        AST::Error {
+            loc: 2,
+            message: "Play or sound is required".to_string(),
+            raw_text: "play video \"a.ogg\"".to_string(),
+        }
+    );
+    error.set_index(7);
+    assert_eq!(error.index(), 7);
+    error.set_index(2);
+
+    let ast = remove_node(ast, 2);
+    assert_eq!(indices(&ast), [1, 2]);
+}
+
+fn warnings(source: &str) -> Vec<(Code, usize, Option<usize>)> {
+    let script = parse_script(source, "warnings.rpy", &ParseOptions::default()).unwrap();
+    script
+        .diagnostics
+        .warnings()
+        .map(|w| (w.code, w.line_number, w.column))
+        .collect()
+}
+
+#[test]
+fn image_clauses_are_kept_as_written() {
+    let ast = parse(
+        "label start:\n    scene bg room onlayer back with fade\n    show eileen happy at left  with dissolve\n    hide eileen\n",
+    );
+    let AST::Label(_, _, block, _) = &ast[0] else {
+        panic!("expected a label, got {:?}", ast[0]);
+    };
+    assert_eq!(
+        block[..],
+        [
+            AST::Scene(
+                2,
+                Some("bg room".to_string()),
+                "back".to_string(),
+                Some("onlayer back with fade".to_string()),
+            ),
+            AST::Show(
+                3,
+                "eileen happy".to_string(),
+                Some("at left  with dissolve".to_string()),
+            ),
+            AST::Hide(4, "eileen".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn uninterpreted_clauses_are_warned_about() {
+    assert_eq!(
+        warnings("show eileen at left with dissolve\n"),
+        [
+            (Code::UnsupportedClause, 1, Some(13)),
+            (Code::UnsupportedClause, 1, Some(21)),
+        ]
+    );
+    assert!(warnings("scene bg onlayer back\n").is_empty());
+}
+
+#[test]
+fn blocks_indented_unlike_earlier_ones_are_warned_about() {
+    let source = "label a:\n    \"one\"\nlabel b:\n  \"two\"\ninit:\n    define x = 1\n";
+    assert_eq!(
+        warnings(source),
+        [(Code::SuspiciousIndentation, 4, Some(3))]
+    );
+}
+
+#[test]
+fn image_manipulators_are_deprecated() {
+    assert_eq!(
+        warnings("define bg = im.Scale(\"bg.png\", 800, 600)\n"),
+        [(Code::DeprecatedForm, 1, Some(13))]
+    );
+    assert!(warnings("define bg = Transform(\"im.png\", zoom=2)\n").is_empty());
+    assert!(warnings("define anim.x = 1\n").is_empty());
+}
+
+#[test]
+fn dialogue_keeps_how_it_was_quoted() {
+    let ast = parse("r\"C:\\new\"\nr 'hi'\ne '''triple'''\n");
+    let raw = Quoting {
+        raw: true,
+        ..Quoting::default()
+    };
+    let single = Quoting {
+        quote_char: '\'',
+        ..Quoting::default()
+    };
+    let triple = Quoting {
+        triple: true,
+        ..single
+    };
+    assert_eq!(
+        ast,
+        [
+            AST::Say(1, None, "C:\\new".to_string(), raw),
+            AST::Say(2, Some("r".to_string()), "hi".to_string(), single),
+            AST::Say(3, Some("e".to_string()), "triple".to_string(), triple),
+        ]
+    );
+
+    #[cfg(feature = "std")]
+    {
+        let source = to_source(&ast);
+        assert_eq!(source, "r\"C:\\new\"\nr 'hi'\ne '''triple'''\n");
+        assert_eq!(parse(&source), ast);
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn scripts_are_read_from_any_reader() {
+    use renpy_parser::parse_scenario_from_reader;
+
+    let bytes: &[u8] = b"\xef\xbb\xbflabel start:\n    \"hi\"\n";
+    let (ast, errors) = parse_scenario_from_reader(bytes, "archive.rpy").unwrap();
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(ast, parse("label start:\n    \"hi\"\n"));
+    assert!(parse_scenario_from_reader(&b"\"\xff\"\n"[..], "archive.rpy").is_err());
+}
+
+/// One of every statement, with every optional field set
+#[cfg(feature = "std")]
+fn every_statement() -> Vec<AST> {
+    let text = |text: &str| text.to_string();
+    let some = |text: &str| Some(text.to_string());
+    let triple = Quoting {
+        quote_char: '\'',
+        raw: true,
+        triple: true,
+    };
+
+    vec![
+        AST::Define(1, text("e = Character(\"Eileen\")")),
+        AST::Init(2, vec![AST::Include(3, text("common.rpy"))], -5),
+        AST::Label(
+            4,
+            text("start"),
+            vec![
+                AST::Scene(5, some("bg room"), text("back"), some("with fade")),
+                AST::Show(6, text("eileen happy"), some("at left")),
+                AST::Say(7, some("e"), text("Hi"), Quoting::default()),
+                AST::Say(8, None, text("It's late"), triple),
+                AST::Hide(9, text("eileen"), some("with dissolve")),
+                AST::Play(10, text("music"), text("theme.ogg")),
+                AST::Stop(11, text("music"), some("fadeout"), Some(1.5)),
+                AST::GameMechanic(12, text("inventory")),
+                AST::LLMGenerate(13, text("e"), some("greet the player")),
+                AST::Custom(14, text("mood"), text("happy")),
+                AST::Error {
+                    loc: 15,
+                    message: text("expected statement."),
+                    raw_text: text("???"),
+                },
+                AST::Jump(16, text("ending"), true),
+            ],
+            some("(player)"),
+        ),
+        AST::Return(17, some("42")),
+    ]
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn every_tree_converts_every_statement() {
+    let ast = every_statement();
+
+    assert_eq!(unintern(intern(ast.clone(), &mut Interner::new())), ast);
+
+    #[cfg(feature = "ast_arena")]
+    {
+        let bump = bumpalo::Bump::new();
+        let arena = renpy_parser::arena::alloc(&bump, &ast);
+        let owned: Vec<AST> = arena.iter().map(AST::from).collect();
+        assert_eq!(owned, ast);
+    }
+
+    #[cfg(feature = "serde")]
+    {
+        use renpy_parser::export::Document;
+        assert_eq!(Document::new(&ast).into_ast().unwrap(), ast);
+    }
+
+    let source = "define e = Character(\"Eileen\")\n\
+        label start:\n\
+        \x20   scene bg room onlayer back with fade\n\
+        \x20   e r'''It's late'''\n\
+        \x20   stop music fadeout 1.5\n\
+        \x20   llm_generate e \"greet the player\"\n\
+        \x20   return 42\n";
+    let expected = parse(source);
+    let (borrowed, _) = parse_borrowed(source, "edit.rpy").unwrap();
+    let borrowed: Vec<AST> = borrowed.into_iter().map(AST::from).collect();
+    assert_eq!(borrowed, expected);
+    let (interned, _) = parse_interned(source, "edit.rpy", &mut Interner::new()).unwrap();
+    assert_eq!(unintern(interned), expected);
+}
+
+#[test]
+fn tabs_are_rejected_unless_expanded() {
+    let source = "label start:\n\t\"one\"\n\t\tx = 1\n";
+    let err = parse_script(source, "tabs.rpy", &ParseOptions::default()).unwrap_err();
+    let err = err.downcast::<ParseError>().unwrap();
+    assert_eq!((err.code, err.line_number), (Code::TabCharacter, 2));
+
+    let options = ParseOptions {
+        tabs: TabPolicy::ExpandTo(4),
+        ..ParseOptions::default()
+    };
+    let script = parse_script(
+        "label start:\n\t\"one\"\n  \t\"two\"\n",
+        "tabs.rpy",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        script.ast,
+        parse("label start:\n    \"one\"\n    \"two\"\n")
+    );
+    let warnings: Vec<_> = script
+        .diagnostics
+        .warnings()
+        .map(|w| (w.code, w.line_number))
+        .collect();
+    assert_eq!(warnings, [(Code::TabExpanded, 2), (Code::TabExpanded, 3)]);
+}
+
+#[test]
+fn indentation_rules_report_expected_and_found_depths() {
+    let source = "label start:\n    \"one\"\n    menu:\n      \"two\"\n";
+    let error = |indent| {
+        let options = ParseOptions {
+            indent,
+            ..ParseOptions::default()
+        };
+        let err = parse_script(source, "indent.rpy", &options).unwrap_err();
+        let err = err.downcast::<ParseError>().unwrap();
+        (err.code, err.line_number, err.message, err.column)
+    };
+
+    let options = ParseOptions::default();
+    assert!(parse_script(source, "indent.rpy", &options).is_ok());
+    assert_eq!(
+        error(IndentRule::Consistent),
+        (
+            Code::IndentMismatch,
+            4,
+            "indentation mismatch: expected 8 spaces, found 6".to_string(),
+            Some(7)
+        )
+    );
+    assert_eq!(error(IndentRule::Width(2)).1, 2);
+}
+
+#[test]
+fn strings_keep_their_whitespace_unless_collapsing_is_asked_for() {
+    let source = "e \"Two  spaces,\n    then a line\"\n";
+    let dialogue = |whitespace| {
+        let options = ParseOptions {
+            whitespace,
+            ..ParseOptions::default()
+        };
+        match &parse_script(source, "whitespace.rpy", &options)
+            .unwrap()
+            .ast[0]
+        {
+            AST::Say(_, _, what, _) => what.clone(),
+            other => panic!("expected dialogue, got {:?}", other),
+        }
+    };
+
+    assert_eq!(
+        dialogue(WhitespacePolicy::Preserve),
+        "Two  spaces,\n    then a line"
+    );
+    assert_eq!(
+        dialogue(WhitespacePolicy::Collapse),
+        "Two spaces, then a line"
+    );
+}
+
+#[test]
+fn scripts_in_memory_report_errors_against_their_virtual_filename() {
+    let (ast, errors) = parse_scenario_from_string("play\nreturn\n", "memory/start.rpy").unwrap();
+    assert!(matches!(ast[..], [AST::Return(2, _)]));
+    assert_eq!(
+        errors,
+        ["On line 1 of memory/start.rpy: Play or sound is required"]
+    );
+
+    let error = parse_scenario_from_string("e \"hi\n", "memory/start.rpy").unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("On line 1 of memory/start.rpy"));
+    assert!(parse_scenario_from_string("", "empty.rpy")
+        .unwrap()
+        .0
+        .is_empty());
+}
+
+#[test]
+fn options_change_how_scripts_are_read() {
+    let source = "game_mechanic \"roll\"\nplay\nreturn # done\n";
+    let parse = |options: ParseOptions| parse_script(source, "options.rpy", &options);
+
+    let default = parse(ParseOptions::default()).unwrap();
+    assert!(matches!(&default.ast[0], AST::GameMechanic(1, what) if what == "roll"));
+    assert_eq!(default.diagnostics.errors().count(), 1);
+    assert!(default.comments.is_none());
+
+    let ren_py = parse(ParseOptions {
+        extended_statements: false,
+        ..ParseOptions::default()
+    })
+    .unwrap();
+    assert!(matches!(&ren_py.ast[0], AST::Say(1, Some(who), _, _) if who == "game_mechanic"));
+
+    let strict = parse(ParseOptions {
+        strict: true,
+        ..ParseOptions::default()
+    });
+    assert_eq!(
+        strict.unwrap_err().to_string(),
+        "On line 2 of options.rpy: Play or sound is required"
+    );
+
+    let attached = parse(ParseOptions {
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    })
+    .unwrap();
+    assert_eq!(attached.ast, default.ast);
+    assert_eq!(
+        attached.comments.unwrap().get(3).unwrap().trailing[0].text,
+        " done"
+    );
+}
+
+#[test]
+fn block_errors_keep_where_they_happened() {
+    let lines = parse_logical_lines("jump 5\nreturn\n", "errors.rpy").unwrap();
+    let blocks = group_logical_lines(lines).unwrap();
+    let (ast, errors) = parse_block(&mut Lexer::borrowed(&blocks, true));
+    assert_eq!(ast.len(), 1);
+
+    let [error] = &errors[..] else {
+        panic!("expected one error, got {:?}", errors);
+    };
+    assert_eq!(error.filename, "errors.rpy");
+    assert_eq!(error.line_number, 1);
+    assert_eq!(error.line.as_deref(), Some("jump 5"));
+    assert_eq!(error.message, "end of line expected");
+    assert_eq!((error.pos, error.column), (Some(5), Some(6)));
+}
+
+#[test]
+fn failing_statements_are_replaced_in_place_when_recovering() {
+    let source = "scene bg\njump 5\nlabel start:\n    play\n    return\n";
+    let recover = ParseOptions {
+        recover: true,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(source, "recover.rpy", &recover).unwrap();
+
+    assert_eq!(indices(&script.ast), [1, 2, 3, 4, 5]);
+    assert!(matches!(&script.ast[1], AST::Error { raw_text, .. } if raw_text == "jump 5"));
+    assert!(
+        matches!(&script.ast[2], AST::Label(3, _, body, _) if matches!(body[0], AST::Error { loc: 4, .. }))
+    );
+    assert_eq!(script.diagnostics.errors().count(), 2);
+
+    let (ast, errors) = parse_scenario_from_string(source, "recover.rpy").unwrap();
+    assert_eq!(indices(&ast), [1]);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn broken_input_is_an_error_rather_than_a_panic() {
+    #[cfg(feature = "fs")]
+    assert!(renpy_parser::parse_scenario_from_file("no/such/script.rpy").is_err());
+
+    let dedented = parse_scenario_from_string("label start:\n    return\n  return\n", "bad.rpy");
+    assert!(dedented.is_err());
+
+    let (ast, errors) =
+        parse_scenario_from_string("stop music fadeout 1.5\nstop music fadeout\n", "stop.rpy")
+            .unwrap();
+    assert_eq!(
+        ast,
+        [AST::Stop(
+            1,
+            "music".into(),
+            Some("fadeout".into()),
+            Some(1.5)
+        )]
+    );
+    assert_eq!(errors, ["On line 2 of stop.rpy: end of line expected"]);
+}
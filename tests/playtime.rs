@@ -0,0 +1,35 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::playtime::{line_time, ReadingSpeed, MAX_LINE_TIME};
+use std::time::Duration;
+
+#[test]
+fn pauses_out_of_range_are_clamped_or_ignored() {
+    let speed = ReadingSpeed::default();
+    let plain = line_time("hello there", &speed);
+
+    assert_eq!(line_time("hello {w=1e39} there", &speed), plain);
+    assert_eq!(line_time("hello {w=inf} there", &speed), plain);
+    assert_eq!(line_time("hello {p=NaN} there", &speed), plain);
+    assert_eq!(line_time("hello {w=-5} there", &speed), plain);
+    assert_eq!(line_time("hello {w} there", &speed), plain);
+    assert_eq!(line_time("hello {w=1e30} there", &speed), MAX_LINE_TIME);
+
+    let paused = line_time("hello {w=1.5} there", &speed) - plain;
+    assert!(paused.abs_diff(Duration::from_millis(1500)) < Duration::from_millis(1));
+}
+
+#[test]
+fn reading_speeds_that_are_not_numbers() {
+    let speed = ReadingSpeed {
+        words_per_minute: f32::NAN,
+        seconds_per_line: f32::NAN,
+    };
+    assert_eq!(line_time("hello there", &speed), Duration::ZERO);
+
+    let speed = ReadingSpeed {
+        words_per_minute: 200.0,
+        seconds_per_line: f32::INFINITY,
+    };
+    assert_eq!(line_time("hello there", &speed), MAX_LINE_TIME);
+}
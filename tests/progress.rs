@@ -0,0 +1,58 @@
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parse_script;
+use renpy_parser::progress::{Cancelled, Observer, ParseEvent};
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+
+const SCRIPT: &str = "label start:\n    e \"Hi\"\n    play\nreturn\n";
+
+fn events(stop_after: usize) -> (anyhow::Result<()>, Vec<String>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let options = ParseOptions {
+        observer: Observer::new(move |event| {
+            let mut log = log.lock().unwrap();
+            log.push(match event {
+                ParseEvent::FileStarted(file) => format!("start {}", file),
+                ParseEvent::LinesScanned(_, lines) => format!("{} lines", lines),
+                ParseEvent::StatementParsed(node) => format!("statement {}", node.index()),
+                ParseEvent::Error(error) => format!("error {}", error.line_number),
+                ParseEvent::FileFinished(file) => format!("finish {}", file),
+            });
+            match log.len() >= stop_after {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        }),
+        ..ParseOptions::hardened()
+    };
+
+    let result = parse_script(SCRIPT, "progress.rpy", &options).map(|_| ());
+    let seen = seen.lock().unwrap().clone();
+    (result, seen)
+}
+
+#[test]
+fn observers_hear_about_every_step() {
+    let (result, seen) = events(usize::MAX);
+    result.unwrap();
+    assert_eq!(
+        seen,
+        [
+            "start progress.rpy",
+            "4 lines",
+            "statement 2",
+            "error 3",
+            "statement 1",
+            "statement 4",
+            "finish progress.rpy",
+        ]
+    );
+}
+
+#[test]
+fn breaking_cancels_the_parse() {
+    let (result, seen) = events(3);
+    assert!(result.unwrap_err().downcast_ref::<Cancelled>().is_some());
+    assert_eq!(seen, ["start progress.rpy", "4 lines", "statement 2"]);
+}
@@ -0,0 +1,251 @@
+#![cfg(feature = "fs")]
+
+use renpy_parser::diagnostics::Code;
+use renpy_parser::project::{parse_project, Project};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn project_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("renpy-project-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, source) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+fn codes(project: &Project) -> Vec<Code> {
+    project.diagnostics.iter().map(|d| d.code).collect()
+}
+
+fn file_names(project: &Project, dir: &Path) -> Vec<String> {
+    project
+        .files
+        .iter()
+        .map(|file| {
+            let path = file.path.strip_prefix(dir).unwrap();
+            path.to_string_lossy().replace('\\', "/")
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_scripts_are_reported() {
+    let dir = project_dir(
+        "unreadable",
+        &[("script.rpy", "label start:\n    return\n")],
+    );
+    std::os::unix::fs::symlink(dir.join("missing.rpy"), dir.join("broken.rpy")).unwrap();
+
+    let project = parse_project(&dir).unwrap();
+    assert_eq!(file_names(&project, &dir), ["script.rpy"]);
+    assert_eq!(codes(&project), [Code::Unreadable]);
+    assert!(project.diagnostics[0].path.ends_with("broken.rpy"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_directory_loops_are_read_once() {
+    let dir = project_dir(
+        "loop",
+        &[
+            ("script.rpy", "label start:\n    jump chapter\n"),
+            ("chapters/one.rpy", "label chapter:\n    return\n"),
+        ],
+    );
+    std::os::unix::fs::symlink(&dir, dir.join("chapters/game")).unwrap();
+
+    let project = parse_project(&dir).unwrap();
+    assert_eq!(
+        file_names(&project, &dir),
+        ["chapters/one.rpy", "script.rpy"]
+    );
+    assert!(project.diagnostics.is_empty(), "{:?}", project.diagnostics);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_project_directories_fail() {
+    let dir = std::env::temp_dir().join("renpy-project-does-not-exist");
+    assert!(parse_project(&dir).is_err());
+}
+
+#[test]
+fn include_cycles_no_other_script_leads_to_are_reported() {
+    let dir = project_dir(
+        "cycle",
+        &[
+            ("a.rpy", "label a:\n    \"from a\"\ninclude \"b.rpy\"\n"),
+            ("b.rpy", "label b:\n    \"from b\"\ninclude \"a.rpy\"\n"),
+        ],
+    );
+
+    let project = parse_project(&dir).unwrap();
+    assert_eq!(file_names(&project, &dir), ["a.rpy"]);
+    assert_eq!(codes(&project), [Code::IncludeCycle]);
+    assert!(project.diagnostics[0].path.ends_with("b.rpy"));
+    assert!(project.labels.contains_key("a") && project.labels.contains_key("b"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn included_statements_know_their_file() {
+    let dir = project_dir(
+        "origins",
+        &[
+            (
+                "script.rpy",
+                "label start:\n    \"one\"\n    include \"chapter.rpy\"\n    jump ending\n",
+            ),
+            ("chapter.rpy", "\"two\"\njump nowhere\n"),
+            ("ending.rpy", "label ending:\n    return\n"),
+        ],
+    );
+
+    let project = parse_project(&dir).unwrap();
+    let script = &project.files[1];
+    assert!(script.path.ends_with("script.rpy"));
+    assert!(script.origin(&[0]).ends_with("script.rpy"));
+    assert!(script.origin(&[0, 0]).ends_with("script.rpy"));
+    assert!(script.origin(&[0, 1]).ends_with("chapter.rpy"));
+    assert!(script.origin(&[0, 2]).ends_with("chapter.rpy"));
+    assert!(script.origin(&[0, 3]).ends_with("script.rpy"));
+
+    // The jump is on line 2 of the included file
+    assert_eq!(codes(&project), [Code::UndefinedLabel]);
+    let diagnostic = &project.diagnostics[0];
+    assert!(diagnostic.path.ends_with("chapter.rpy"), "{}", diagnostic);
+    assert_eq!(diagnostic.node.as_ref().unwrap().index, 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn suppression_comments_apply_in_the_file_they_are_in() {
+    let dir = project_dir(
+        "suppressed",
+        &[
+            (
+                "script.rpy",
+                "label start:\n    include \"chapter.rpy\"\n    # renpy-parser: disable=undefined-label\n    jump missing\n    jump gone\n",
+            ),
+            (
+                "chapter.rpy",
+                "\"two\"\njump nowhere  # renpy-parser: disable=undefined-label\njump elsewhere\n",
+            ),
+        ],
+    );
+
+    let project = parse_project(&dir).unwrap();
+    let found: Vec<_> = project
+        .diagnostics
+        .iter()
+        .map(|d| (d.path.file_name().unwrap().to_str().unwrap(), d.code))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("chapter.rpy", Code::UndefinedLabel),
+            ("script.rpy", Code::UndefinedLabel)
+        ]
+    );
+    assert!(project.diagnostics[0].message.contains("elsewhere"));
+    assert!(project.diagnostics[1].message.contains("gone"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn labels_defined_again_in_included_files_are_reported_with_both_files() {
+    let dir = project_dir(
+        "duplicate",
+        &[
+            ("a.rpy", "label start:\n    return\ninclude \"b.inc\"\n"),
+            ("b.inc", "label start:\n    return\n"),
+        ],
+    );
+
+    let project = parse_project(&dir).unwrap();
+    assert_eq!(codes(&project), [Code::DuplicateLabel]);
+    let diagnostic = &project.diagnostics[0];
+    assert!(diagnostic.path.ends_with("b.inc"), "{}", diagnostic);
+    assert_eq!(diagnostic.node.as_ref().unwrap().index, 1);
+
+    assert_eq!(diagnostic.related.len(), 1);
+    let first = &diagnostic.related[0];
+    assert!(first.path.ends_with("a.rpy"), "{}", diagnostic);
+    assert_eq!(first.node.index, 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn labels_defined_in_two_scripts_are_reported_with_both_files() {
+    let dir = project_dir(
+        "duplicate-files",
+        &[
+            ("a.rpy", "label start:\n    return\n"),
+            ("b.rpy", "label start:\n    return\n"),
+        ],
+    );
+
+    let project = parse_project(&dir).unwrap();
+    assert_eq!(codes(&project), [Code::DuplicateLabel]);
+    let diagnostic = &project.diagnostics[0];
+    assert!(diagnostic.path.ends_with("b.rpy"), "{}", diagnostic);
+    assert!(
+        diagnostic.related[0].path.ends_with("a.rpy"),
+        "{}",
+        diagnostic
+    );
+    assert_eq!(diagnostic.related[0].node.index, 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn scripts_parsed_across_threads_come_back_in_path_order() {
+    let sources: Vec<_> = (0..40)
+        .map(|i| {
+            let path = format!("chapter{:02}.rpy", i);
+            let next = (i + 1) % 40;
+            let source = match i % 7 {
+                0 => format!("label c{}:\n    play\n    jump c{}\n", i, next),
+                _ => format!("label c{}:\n    e \"{}\"\n    jump c{}\n", i, i, next),
+            };
+            (path, source)
+        })
+        .collect();
+    let files: Vec<_> = sources
+        .iter()
+        .map(|(path, source)| (path.as_str(), source.as_str()))
+        .collect();
+    let dir = project_dir("parallel", &files);
+
+    let first = parse_project(&dir).unwrap();
+    let names = file_names(&first, &dir);
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    assert_eq!(names.len(), 40);
+    // Labels failing to parse are left out, so jumps to them are undefined
+    let mut expected = vec![Code::InvalidAudioChannel; 6];
+    expected.extend([Code::UndefinedLabel; 6]);
+    assert_eq!(codes(&first), expected);
+
+    for _ in 0..5 {
+        let again = parse_project(&dir).unwrap();
+        assert_eq!(file_names(&again, &dir), names);
+        assert_eq!(again.diagnostics, first.diagnostics);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
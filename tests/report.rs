@@ -0,0 +1,30 @@
+#![cfg(feature = "miette")]
+
+use miette::Diagnostic;
+use renpy_parser::diagnostics::Severity;
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parse_script;
+use renpy_parser::report::reports;
+
+#[test]
+fn reports_point_at_the_offending_word() {
+    let source = "\u{feff}label start:\n    play video \"a.ogg\"\n    return\n";
+    let script = parse_script(source, "game/script.rpy", &ParseOptions::default()).unwrap();
+    let reports = reports(&script.diagnostics, source);
+    let [report] = &reports[..] else {
+        panic!("expected one report, got {:?}", reports);
+    };
+
+    assert_eq!(report.severity, Severity::Error);
+    assert_eq!(report.code().unwrap().to_string(), "RPY0205");
+    assert_eq!(
+        report.help().unwrap().to_string(),
+        "play and stop take either music or sound"
+    );
+    let label = report.labels().unwrap().next().unwrap();
+    assert_eq!(&source[label.offset()..][..label.len()], "video");
+
+    let rendered = report.render();
+    assert!(rendered.contains("game/script.rpy:2:"), "{}", rendered);
+    assert!(rendered.contains("play video \"a.ogg\""), "{}", rendered);
+}
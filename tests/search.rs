@@ -0,0 +1,96 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::options::{CommentHandling, ParseOptions};
+use renpy_parser::parsers::AST;
+use renpy_parser::search::{search, search_script, MatchKind, Pattern};
+use renpy_parser::{parse_scenario_from_string, parse_script};
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "search.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+const SCRIPT: &str = "label start:\n\
+    \x20   e \"The key is under the mat\"\n\
+    \x20   label .inner:\n\
+    \x20       \"A key, a keyhole\"\n\
+    \x20   jump end\n\
+    label end:\n\
+    \x20   show key\n\
+    \x20   return\n";
+
+#[test]
+fn dialogue_matches_know_their_speaker_and_labels() {
+    let ast = parse(SCRIPT);
+    let found = search(&ast, &Pattern::from("key"));
+    let inner = vec!["start".to_string(), ".inner".to_string()];
+
+    let summary: Vec<_> = found
+        .iter()
+        .map(|m| {
+            (
+                m.line_number,
+                m.labels.clone(),
+                m.speaker.as_deref(),
+                m.span.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (2, vec!["start".to_string()], Some("e"), 4..7),
+            (4, inner.clone(), None, 2..5),
+            (4, inner, None, 9..12),
+        ]
+    );
+
+    // Only dialogue is searched, not the image names
+    assert_eq!(found[1].text, "A key, a keyhole");
+    let node = found[0].node.as_ref().unwrap();
+    assert!(matches!(node.resolve(&ast), Some(AST::Say(2, ..))));
+    assert!(found.iter().all(|m| m.kind == MatchKind::Dialogue));
+    assert!(search(&ast, &Pattern::from("KEY")).is_empty());
+}
+
+#[test]
+fn regular_expressions_find_every_occurrence() {
+    let ast = parse(SCRIPT);
+    let found = search(&ast, &Pattern::regex(r"\bkey\w*").unwrap());
+    let spans: Vec<_> = found.iter().map(|m| &m.text[m.span.clone()]).collect();
+    assert_eq!(spans, ["key", "key", "keyhole"]);
+
+    assert!(Pattern::regex("(").is_err());
+    assert!(search(&ast, &Pattern::from("")).is_empty());
+}
+
+#[test]
+fn comments_are_searched_when_attached() {
+    let source =
+        "# key first\nlabel start:\n    \"a key\"  # key trailing\n    return\n# key last\n";
+    let options = ParseOptions {
+        comments: CommentHandling::Attach,
+        ..ParseOptions::default()
+    };
+    let script = parse_script(source, "search.rpy", &options).unwrap();
+    let found = search_script(&script, &Pattern::from("key"));
+
+    let summary: Vec<_> = found
+        .iter()
+        .map(|m| (m.line_number, m.kind, m.labels.len(), m.node.is_some()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, MatchKind::Comment, 1, true),
+            (3, MatchKind::Dialogue, 1, true),
+            (3, MatchKind::Comment, 1, true),
+            (5, MatchKind::Comment, 0, false),
+        ]
+    );
+
+    // Without comments attached there's only the dialogue
+    let script = parse_script(source, "search.rpy", &ParseOptions::default()).unwrap();
+    assert_eq!(search_script(&script, &Pattern::from("key")).len(), 1);
+}
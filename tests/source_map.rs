@@ -0,0 +1,16 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::source_map::parse_with_source_map;
+
+#[test]
+fn nodes_map_to_their_byte_ranges() {
+    let source = "define x = (1,\n    2)\nlabel start:\n    \"hi\"\n\n    return\n";
+    let (ast, map, errors) = parse_with_source_map(source, "map.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let span = |i: usize| map.span(&ast[i]).map(|span| &source[span]);
+    assert_eq!(span(0), Some("define x = (1,\n    2)"));
+    assert_eq!(span(1), Some("label start:\n    \"hi\"\n\n    return"));
+    assert_eq!(map.line(4).map(|span| &source[span]), Some("\"hi\""));
+    assert_eq!(map.line(5), None);
+}
@@ -0,0 +1,73 @@
+use renpy_parser::lexer::Lexer;
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parse_script;
+use renpy_parser::parsers::AST;
+use renpy_parser::statements::{LineStatement, StatementParser, Statements};
+
+/// `achieve <points>`, written back as `achieve +<points>`
+struct Achieve;
+
+impl StatementParser for Achieve {
+    fn keyword(&self) -> &str {
+        "achieve"
+    }
+
+    fn parse(&self, l: &mut Lexer, loc: usize) -> anyhow::Result<AST> {
+        let points = l.int_value()?.unwrap_or(1);
+        l.expect_eol()?;
+        l.advance();
+        Ok(AST::Custom(loc, "achieve".to_string(), points.to_string()))
+    }
+
+    fn render(&self, node: &AST) -> Option<String> {
+        match node {
+            AST::Custom(_, keyword, points) if keyword == "achieve" => {
+                Some(format!("achieve +{}", points))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn options() -> ParseOptions {
+    ParseOptions {
+        statements: Statements::new()
+            .with(Achieve)
+            .with(LineStatement::new("show")),
+        ..ParseOptions::default()
+    }
+}
+
+#[test]
+fn custom_statements_come_before_the_built_in_ones() {
+    let source = "label start:\n    achieve 5\n    achieve\n    show eileen happy\nachieve \"x\"\n";
+    let script = parse_script(source, "custom.rpy", &options()).unwrap();
+    let custom = |loc, keyword: &str, text: &str| AST::Custom(loc, keyword.into(), text.into());
+
+    let AST::Label(_, _, body, _) = &script.ast[0] else {
+        panic!("expected a label, got {:?}", script.ast);
+    };
+    assert_eq!(
+        *body,
+        [
+            custom(2, "achieve", "5"),
+            custom(3, "achieve", "1"),
+            custom(4, "show", "eileen happy"),
+        ]
+    );
+    assert_eq!(script.diagnostics.errors().next().unwrap().line_number, 5);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn custom_statements_are_written_back_by_their_parsers() {
+    use renpy_parser::writer::Formatter;
+
+    let options = options();
+    let script = parse_script("achieve 5\nshow eileen\n", "custom.rpy", &options).unwrap();
+    let formatter = Formatter {
+        statements: options.statements.clone(),
+        ..Formatter::default()
+    };
+    assert_eq!(formatter.format(&script.ast), "achieve +5\nshow eileen\n");
+}
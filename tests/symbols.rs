@@ -0,0 +1,46 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::symbols::{labels, resolve_targets};
+
+#[test]
+fn unresolved_jumps_name_their_file() {
+    let source = "label start:\n    jump ending\n    jump start\n";
+    let (ast, _) = parse_scenario_from_string(source, "story.rpy").unwrap();
+
+    let unresolved = resolve_targets(&ast, "story.rpy").unwrap_err();
+    assert_eq!(unresolved.len(), 1);
+    assert_eq!(unresolved[0].target, "ending");
+    assert_eq!(unresolved[0].filename, "story.rpy");
+    assert_eq!(unresolved[0].line_number, 2);
+}
+
+#[test]
+fn local_labels_are_qualified_by_their_global_label() {
+    let source = "label start:\n    label .sub:\n        return\nlabel ending:\n    label .sub:\n        return\n";
+    let (ast, _) = parse_scenario_from_string(source, "story.rpy").unwrap();
+
+    let labels = labels(&ast);
+    let mut names: Vec<&str> = labels.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, ["ending", "ending.sub", "start", "start.sub"]);
+
+    assert_eq!(labels["start.sub"].line_number, 2);
+    assert_eq!(labels["start.sub"].parent.as_deref(), Some("start"));
+    assert_eq!(labels["ending.sub"].line_number, 5);
+    assert_eq!(labels["ending.sub"].parent.as_deref(), Some("ending"));
+    assert_eq!(labels["ending.sub"].depth, 1);
+}
+
+#[test]
+fn local_jumps_resolve_against_their_global_label() {
+    let source = "label start:\n    label .sub:\n        jump .sub\n    jump start.sub\nlabel ending:\n    jump .sub\n    jump ending.sub\n";
+    let (ast, _) = parse_scenario_from_string(source, "story.rpy").unwrap();
+
+    let unresolved = resolve_targets(&ast, "story.rpy").unwrap_err();
+    let targets: Vec<_> = unresolved
+        .iter()
+        .map(|jump| (jump.target.as_str(), jump.line_number))
+        .collect();
+    assert_eq!(targets, [(".sub", 6), ("ending.sub", 7)]);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::tokens::{tokenize, TokenKind};
+
+#[test]
+fn scripts_are_split_into_typed_tokens() {
+    let source = "\u{feff}label start: # go\n    e \"Hi,\\\" there\" 1.5e3\n    \"open";
+    let tokens: Vec<_> = tokenize(source)
+        .into_iter()
+        .map(|token| {
+            assert_eq!(&source[token.span.clone()], token.text);
+            (token.kind, token.text)
+        })
+        .collect();
+
+    assert_eq!(
+        tokens,
+        [
+            (TokenKind::Keyword, "label"),
+            (TokenKind::Name, "start"),
+            (TokenKind::Punct, ":"),
+            (TokenKind::Comment, "# go"),
+            (TokenKind::Name, "e"),
+            (TokenKind::String, "\"Hi,\\\" there\""),
+            (TokenKind::Number, "1.5e3"),
+            (TokenKind::String, "\"open"),
+        ]
+    );
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::translation::{translatable_strings, translate_template};
+
+#[test]
+fn identifiers_are_computed_like_renpy_does() {
+    let source =
+        "label start:\n    e \"Hello\"\n    e \"Hello\"\nlabel chapter.one:\n    \"Narration\"\n";
+    let (ast, errors) = parse_scenario_from_string(source, "translation.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let strings = translatable_strings(&ast);
+
+    let identifiers: Vec<&str> = strings.iter().map(|s| s.identifier.as_str()).collect();
+    assert_eq!(
+        identifiers,
+        ["start_8d695913", "start_8d695913_1", "chapter_one_2909c244"]
+    );
+    assert_eq!(strings[2].text, "Narration");
+    assert_eq!(strings[2].node.index, 5);
+
+    let template = translate_template(&strings[..1], "french");
+    assert_eq!(
+        template,
+        "translate french start_8d695913:\n\n    # e \"Hello\"\n    e \"Hello\"\n\n"
+    );
+}
+
+#[test]
+fn local_labels_and_spacing_are_encoded_like_renpy_does() {
+    let source = "label start:\n    label .sub:\n        \"Hi\"\n    e \"a  b\"\n";
+    let (ast, errors) = parse_scenario_from_string(source, "translation.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    let strings = translatable_strings(&ast);
+
+    assert_eq!(strings[0].identifier, "start_sub_80ac6338");
+    assert_eq!(strings[0].label.as_deref(), Some("start.sub"));
+    assert_eq!(strings[1].identifier, "start_9a1a32c4");
+    assert_eq!(strings[1].code, "e \"a \\ b\"");
+}
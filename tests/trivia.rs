@@ -0,0 +1,82 @@
+use renpy_parser::options::ParseOptions;
+use renpy_parser::parse_script;
+use renpy_parser::trivia::{parse_scenario_with_comments, Comment, LineEnding, SourceFormat};
+
+fn comment(line_number: usize, text: &str, trailing: bool) -> Comment {
+    Comment {
+        line_number,
+        text: text.to_string(),
+        trailing,
+        blank_lines_before: 0,
+    }
+}
+
+#[test]
+fn comments_are_attached_to_the_statements_they_describe() {
+    let source = "# The story\nlabel start:\n    # greeting\n    e \"Hi\" # first line\n    return\n# the end\n";
+    let (ast, comments, errors) = parse_scenario_with_comments(source, "trivia.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(ast.len(), 1);
+
+    assert_eq!(
+        comments.get(2).unwrap().leading,
+        [comment(1, " The story", false)]
+    );
+    let say = comments.get(4).unwrap();
+    assert_eq!(say.leading, [comment(3, " greeting", false)]);
+    assert_eq!(say.trailing, [comment(4, " first line", true)]);
+    assert_eq!(comments.get(5), None);
+    assert_eq!(comments.dangling, [comment(6, " the end", false)]);
+}
+
+#[test]
+fn blank_lines_are_counted_and_written_back() {
+    let source = "define x = 1\n\n\n# chapter one\n\nlabel start:\n    \"a\"\n\n    \"b\"\n";
+    let (ast, comments, _) = parse_scenario_with_comments(source, "trivia.rpy").unwrap();
+
+    assert_eq!(ast.len(), 2);
+
+    let chapter = comments.get(6).unwrap();
+    assert_eq!(chapter.leading[0].blank_lines_before, 2);
+    assert_eq!(chapter.blank_lines_before, 1);
+    assert_eq!(comments.get(9).unwrap().blank_lines_before, 1);
+
+    #[cfg(feature = "std")]
+    {
+        use renpy_parser::writer::Formatter;
+
+        let formatter = Formatter {
+            indent_width: 2,
+            preserve_blank_lines: true,
+            ..Formatter::default()
+        };
+        assert_eq!(
+            formatter.format_with_comments(&ast, &comments),
+            source.replace("    ", "  ")
+        );
+    }
+}
+
+#[test]
+fn line_endings_and_byte_order_marks_are_recorded() {
+    let source = "\u{feff}label start:\r\n    e \"Hi\"\r\n    return\n";
+    let script = parse_script(source, "format.rpy", &ParseOptions::default()).unwrap();
+    assert_eq!(
+        script.format,
+        SourceFormat {
+            line_ending: LineEnding::CrLf,
+            bom: true,
+        }
+    );
+    assert_eq!(SourceFormat::detect("a\nb\r\n"), SourceFormat::default());
+    assert_eq!(script.format.apply("return\n"), "\u{feff}return\r\n");
+
+    #[cfg(feature = "std")]
+    {
+        let formatted = renpy_parser::writer::format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "\u{feff}label start:\r\n    e \"Hi\"\r\n    return\r\n"
+        );
+    }
+}
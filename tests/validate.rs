@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::diagnostics::Code;
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::validate::validate;
+
+fn findings(source: &str) -> Vec<(Code, usize, Vec<usize>)> {
+    let (ast, errors) = parse_scenario_from_string(source, "validate.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    validate(&ast)
+        .into_iter()
+        .map(|d| (d.code, d.node.index, d.node.path))
+        .collect()
+}
+
+#[test]
+fn broken_references_point_at_their_statements() {
+    let source = "define e = Character(\"Eileen\")\n\
+        label start:\n\
+        \x20   show eileen\n\
+        \x20   e \"Hi\"\n\
+        \x20   m \"Who?\"\n\
+        \x20   hide eileen\n\
+        \x20   hide lucy\n\
+        \x20   play music \"theme.ogg\"\n\
+        \x20   stop music\n\
+        \x20   stop sound\n\
+        \x20   jump missing\n";
+    let mut found = findings(source);
+    found.sort_by_key(|(_, index, _)| *index);
+    assert_eq!(
+        found,
+        [
+            (Code::UndefinedCharacter, 5, vec![1, 2]),
+            (Code::HideWithoutShow, 7, vec![1, 4]),
+            (Code::StopWithoutPlay, 10, vec![1, 7]),
+            (Code::UndefinedLabel, 11, vec![1, 8]),
+        ]
+    );
+}
+
+#[test]
+fn consistent_scripts_have_no_findings() {
+    let source = "define e = Character(\"Eileen\")\n\
+        label start:\n\
+        \x20   show eileen\n\
+        \x20   e \"Hi\"\n\
+        \x20   hide eileen\n\
+        \x20   jump end\n\
+        label end:\n\
+        \x20   scene bg room\n\
+        \x20   hide bg\n\
+        \x20   return\n";
+    assert!(findings(source).is_empty());
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "std")]
+
+use renpy_parser::parse_scenario_from_string;
+use renpy_parser::parsers::AST;
+use renpy_parser::writer::{format_source, to_source, Formatter, QuoteStyle};
+
+fn parse(source: &str) -> Vec<AST> {
+    let (ast, errors) = parse_scenario_from_string(source, "writer.rpy").unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    ast
+}
+
+/// Parses, writes and parses again, expecting the same AST with the same indices
+fn round_trip(source: &str) -> String {
+    let ast = parse(source);
+    let written = to_source(&ast);
+    assert_eq!(parse(&written), ast, "written as:\n{}", written);
+    written
+}
+
+#[test]
+fn multi_line_statements_keep_the_lines_after_them() {
+    let written = round_trip("define x = (1,\n    2)\ne \"hi\"\n");
+    assert_eq!(written, "define x = (1,\n    2)\ne \"hi\"\n");
+
+    round_trip("label start:\n    define y = [\n        1,\n        2,\n    ]\n    \"after\"\n    return\n");
+}
+
+#[test]
+fn layers_clauses_and_return_values_round_trip() {
+    round_trip(
+        "label start:\n    scene bg room onlayer back\n    scene bg night with fade\n    show eileen happy at left with dissolve\n    hide eileen\n    return 42\n",
+    );
+}
+
+#[test]
+fn fadeouts_round_trip() {
+    let written = round_trip("stop music fadeout 1.0\nstop sound fadeout 0.25\nstop sound\n");
+    assert_eq!(
+        written,
+        "stop music fadeout 1.0\nstop sound fadeout 0.25\nstop sound\n"
+    );
+}
+
+#[test]
+fn fadeout_lengths_are_written_with_a_decimal_point() {
+    let fadeout = |i, length| {
+        AST::Stop(
+            i,
+            "music".to_string(),
+            Some("fadeout".to_string()),
+            Some(length),
+        )
+    };
+    let ast = [fadeout(1, 3.0), fadeout(2, 1e16), fadeout(3, 1e-7)];
+    let written = to_source(&ast);
+    assert_eq!(
+        written,
+        "stop music fadeout 3.0\nstop music fadeout 10000000000000000.0\nstop music fadeout 0.0000001\n"
+    );
+    assert_eq!(parse(&written), ast);
+}
+
+#[test]
+fn dialogue_and_defines_round_trip() {
+    round_trip(
+        "define e = Character(\"Eileen\")\nlabel start:\n    e \"She said \\\"hi\\\".\"\n    \"Narration\"\n    jump end\nlabel end:\n    return\n",
+    );
+}
+
+#[test]
+fn formatters_write_in_a_house_style() {
+    let ast = parse("label start:\n    e \"It's\"\n    jump end\nlabel end:\n    return\n");
+    let formatter = Formatter {
+        indent_width: 2,
+        quote_style: QuoteStyle::Single,
+        blank_lines_between_labels: 2,
+        ..Formatter::default()
+    };
+    let written = formatter.format(&ast);
+    assert_eq!(
+        written,
+        "label start:\n  e 'It\\'s'\n  jump end\n\n\nlabel end:\n  return\n"
+    );
+    assert_eq!(
+        parse(&written),
+        parse("label start:\n    e 'It\\'s'\n    jump end\n\n\nlabel end:\n    return\n")
+    );
+}
+
+#[test]
+fn scripts_are_formatted_keeping_comments_and_blank_lines() {
+    let source = "# intro\nlabel   start :\n  e   'Hi  there' # greeting\n\n  jump  next\nlabel next:\n        return\n";
+    let formatted = format_source(source).unwrap();
+    assert_eq!(
+        formatted,
+        "# intro\nlabel start:\n    e \"Hi there\"  # greeting\n\n    jump next\nlabel next:\n    return\n"
+    );
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+
+    assert!(format_source("label start:\n    play video \"a.ogg\"\n").is_err());
+}